@@ -0,0 +1,247 @@
+//! Animated block textures like in Minecraft resource packs.
+//!
+//! An animated texture is a vertical strip of square frames. The `.png.mcmeta` file next to it
+//! says in which order the frames are shown and for how many ticks each, and whether the shader
+//! blends between consecutive frames.
+
+use serde::Deserialize;
+
+use crate::{
+    error::{Context, Result},
+    sky::TICKS_PER_SECOND,
+};
+
+#[derive(Deserialize)]
+struct Metadata {
+    animation: Option<AnimationMetadata>,
+}
+
+#[derive(Deserialize)]
+struct AnimationMetadata {
+    #[serde(default)]
+    interpolate: bool,
+    /// ticks per frame for frames without their own time
+    #[serde(default = "default_frame_time")]
+    frametime: u32,
+    /// all frames from top to bottom if missing
+    frames: Option<Vec<FrameMetadata>>,
+}
+
+fn default_frame_time() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FrameMetadata {
+    Index(u32),
+    Timed { index: u32, time: Option<u32> },
+}
+
+/// A frame of the strip and how many ticks it is shown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Frame {
+    index: u32,
+    ticks: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextureAnimation {
+    /// number of frames in the strip
+    pub frame_count: u32,
+    frames: Vec<Frame>,
+    interpolate: bool,
+}
+
+impl TextureAnimation {
+    /// A texture that always shows the top frame.
+    pub fn still() -> Self {
+        Self {
+            frame_count: 1,
+            frames: vec![Frame { index: 0, ticks: 1 }],
+            interpolate: false,
+        }
+    }
+
+    /// Reads the contents of a `.png.mcmeta` file for a texture of `width` by `height` pixels.
+    ///
+    /// Textures without an `animation` section are still.
+    pub fn parse(mcmeta: &str, width: u32, height: u32) -> Result<Self> {
+        let metadata: Metadata =
+            serde_json::from_str(mcmeta).context("parse the texture metadata")?;
+        let animation = match metadata.animation {
+            Some(animation) => animation,
+            None => return Ok(Self::still()),
+        };
+        let frame_count = (height / width.max(1)).max(1);
+        // a frame has to be shown for at least one tick
+        let frametime = animation.frametime.max(1);
+        let frames: Vec<_> = match animation.frames {
+            Some(frames) => frames
+                .into_iter()
+                .map(|frame| match frame {
+                    FrameMetadata::Index(index) => Frame {
+                        index,
+                        ticks: frametime,
+                    },
+                    FrameMetadata::Timed { index, time } => Frame {
+                        index,
+                        ticks: time.unwrap_or(frametime).max(1),
+                    },
+                })
+                // frames outside of the strip can't be shown
+                .filter(|frame| frame.index < frame_count)
+                .collect(),
+            None => (0..frame_count)
+                .map(|index| Frame {
+                    index,
+                    ticks: frametime,
+                })
+                .collect(),
+        };
+        if frames.is_empty() {
+            return Ok(Self::still());
+        }
+        Ok(Self {
+            frame_count,
+            frames,
+            interpolate: animation.interpolate,
+        })
+    }
+
+    /// Ticks until the animation starts over.
+    fn length(&self) -> u32 {
+        self.frames.iter().map(|frame| frame.ticks).sum()
+    }
+
+    /// What the shader needs to show the animation `seconds` after it started: the frame to show,
+    /// the frame after it, how far to blend towards that one and the number of frames in the
+    /// strip.
+    pub fn shader_data(&self, seconds: f32) -> [f32; 4] {
+        let length = self.length();
+        let ticks = (seconds * TICKS_PER_SECOND) % length as f32;
+        let mut start = 0.0;
+        for (position, frame) in self.frames.iter().enumerate() {
+            let end = start + frame.ticks as f32;
+            if ticks < end || position == self.frames.len() - 1 {
+                let next = self.frames[(position + 1) % self.frames.len()];
+                let blend = if self.interpolate {
+                    ((ticks - start) / frame.ticks as f32).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                return [
+                    frame.index as f32,
+                    next.index as f32,
+                    blend,
+                    self.frame_count as f32,
+                ];
+            }
+            start = end;
+        }
+        unreachable!("animations have at least one frame")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(animation: &TextureAnimation) -> Vec<(u32, u32)> {
+        animation
+            .frames
+            .iter()
+            .map(|frame| (frame.index, frame.ticks))
+            .collect()
+    }
+
+    fn assert_shader_data(animation: &TextureAnimation, ticks: f32, expected: [f32; 4]) {
+        let actual = animation.shader_data(ticks / TICKS_PER_SECOND);
+        assert!(
+            actual
+                .iter()
+                .zip(expected)
+                .all(|(actual, expected)| (actual - expected).abs() < 1e-4),
+            "{:?} != {:?} after {} ticks",
+            actual,
+            expected,
+            ticks
+        );
+    }
+
+    #[test]
+    fn textures_without_an_animation_are_still() {
+        assert_eq!(
+            TextureAnimation::parse("{}", 16, 64).unwrap(),
+            TextureAnimation::still()
+        );
+        assert!(TextureAnimation::parse("{", 16, 16).is_err());
+    }
+
+    #[test]
+    fn all_frames_are_shown_without_a_frame_list() {
+        let animation = TextureAnimation::parse(r#"{"animation": {}}"#, 16, 48).unwrap();
+        assert_eq!(animation.frame_count, 3);
+        assert_eq!(frames(&animation), [(0, 1), (1, 1), (2, 1)]);
+        assert!(!animation.interpolate);
+    }
+
+    #[test]
+    fn indices_and_timed_frames_can_be_mixed() {
+        let mcmeta = r#"{"animation": {
+            "frametime": 2,
+            "frames": [1, {"index": 0, "time": 5}, {"index": 2}]
+        }}"#;
+        let animation = TextureAnimation::parse(mcmeta, 16, 48).unwrap();
+        assert_eq!(frames(&animation), [(1, 2), (0, 5), (2, 2)]);
+        assert_eq!(animation.length(), 9);
+    }
+
+    #[test]
+    fn frames_outside_of_the_strip_are_skipped() {
+        let mcmeta = r#"{"animation": {"frames": [0, 7, {"index": 2, "time": 3}, 1]}}"#;
+        let animation = TextureAnimation::parse(mcmeta, 16, 32).unwrap();
+        assert_eq!(animation.frame_count, 2);
+        assert_eq!(frames(&animation), [(0, 1), (1, 1)]);
+
+        let mcmeta = r#"{"animation": {"frames": [4, 5]}}"#;
+        assert_eq!(
+            TextureAnimation::parse(mcmeta, 16, 32).unwrap(),
+            TextureAnimation::still()
+        );
+    }
+
+    #[test]
+    fn frames_are_shown_for_at_least_one_tick() {
+        let mcmeta = r#"{"animation": {"frametime": 0, "frames": [0, {"index": 1, "time": 0}]}}"#;
+        let animation = TextureAnimation::parse(mcmeta, 16, 32).unwrap();
+        assert_eq!(frames(&animation), [(0, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn interpolated_frames_blend_into_the_next_one() {
+        let mcmeta = r#"{"animation": {"frametime": 4, "interpolate": true}}"#;
+        let animation = TextureAnimation::parse(mcmeta, 16, 32).unwrap();
+        assert_shader_data(&animation, 0.0, [0.0, 1.0, 0.0, 2.0]);
+        assert_shader_data(&animation, 1.0, [0.0, 1.0, 0.25, 2.0]);
+        assert_shader_data(&animation, 6.0, [1.0, 0.0, 0.5, 2.0]);
+
+        let mcmeta = r#"{"animation": {"frametime": 4}}"#;
+        let animation = TextureAnimation::parse(mcmeta, 16, 32).unwrap();
+        assert_shader_data(&animation, 1.0, [0.0, 1.0, 0.0, 2.0]);
+    }
+
+    #[test]
+    fn animations_start_over_after_the_last_frame() {
+        let mcmeta = r#"{"animation": {"frames": [2, {"index": 0, "time": 3}]}}"#;
+        let animation = TextureAnimation::parse(mcmeta, 16, 48).unwrap();
+        assert_shader_data(&animation, 0.5, [2.0, 0.0, 0.0, 3.0]);
+        assert_shader_data(&animation, 3.5, [0.0, 2.0, 0.0, 3.0]);
+        assert_shader_data(&animation, 4.5, [2.0, 0.0, 0.0, 3.0]);
+        assert_shader_data(&animation, 4000.0 + 1.5, [0.0, 2.0, 0.0, 3.0]);
+        assert_eq!(
+            TextureAnimation::still().shader_data(123.4),
+            [0.0, 0.0, 0.0, 1.0]
+        );
+    }
+}
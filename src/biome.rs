@@ -0,0 +1,231 @@
+//! Biomes and the colors grass, leaves and water take on in them.
+//!
+//! Temperature and rainfall change smoothly over the world. Like in Minecraft, grass and foliage
+//! colors are looked up in colormap images by these two values, and water has a color per biome.
+//! The mesh blends the colors of neighboring columns so there are no hard edges between biomes.
+
+use crate::{
+    error::{Context, Result},
+    utils::decode_png_rgba8,
+};
+
+/// In blocks, roughly how far it is between a hot and a cold place.
+const CLIMATE_SCALE: f32 = 96.0;
+
+/// What a block's texture is multiplied with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Tint {
+    Grass,
+    Foliage,
+    Water,
+}
+
+/// The weather of one column of the world, both values are from 0 to 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Climate {
+    pub temperature: f32,
+    pub downfall: f32,
+}
+
+impl Climate {
+    /// The climate of the column at `x`, `z` in a world generated from `seed`.
+    pub fn at(seed: u64, x: i32, z: i32) -> Self {
+        let (x, z) = (x as f32 / CLIMATE_SCALE, z as f32 / CLIMATE_SCALE);
+        Self {
+            temperature: value_noise(seed, x, z),
+            // a different part of the same noise
+            downfall: value_noise(seed ^ 0x9e37_79b9_7f4a_7c15, x, z),
+        }
+    }
+
+    pub fn biome(self) -> Biome {
+        match (self.temperature, self.downfall) {
+            (t, _) if t < 0.25 => Biome::Taiga,
+            (t, d) if t > 0.75 && d < 0.4 => Biome::Desert,
+            (t, d) if t > 0.55 && d > 0.7 => Biome::Swamp,
+            (_, d) if d > 0.5 => Biome::Forest,
+            _ => Biome::Plains,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Biome {
+    Plains,
+    Forest,
+    Desert,
+    Swamp,
+    Taiga,
+}
+
+impl Biome {
+    pub fn name(self) -> &'static str {
+        match self {
+            Biome::Plains => "plains",
+            Biome::Forest => "forest",
+            Biome::Desert => "desert",
+            Biome::Swamp => "swamp",
+            Biome::Taiga => "taiga",
+        }
+    }
+
+    /// The water colors of the matching Minecraft biomes.
+    pub fn water_color(self) -> [f32; 3] {
+        let rgb: u32 = match self {
+            Biome::Plains | Biome::Forest | Biome::Desert => 0x3f76e4,
+            Biome::Swamp => 0x617b64,
+            Biome::Taiga => 0x3d57d6,
+        };
+        [
+            (rgb >> 16) as f32 / 255.0,
+            (rgb >> 8 & 0xff) as f32 / 255.0,
+            (rgb & 0xff) as f32 / 255.0,
+        ]
+    }
+}
+
+/// A colormap image, hot places are on the left and wet places at the top.
+#[derive(Clone, Debug)]
+pub struct Colormap {
+    width: u32,
+    height: u32,
+    /// RGB from 0 to 1, row by row
+    pixels: Vec<[f32; 3]>,
+}
+
+impl Colormap {
+    pub fn from_png(png_bytes: &[u8]) -> Result<Self> {
+        let (width, height, rgba) = decode_png_rgba8(png_bytes).context("decode the colormap")?;
+        let pixels = rgba
+            .chunks_exact(4)
+            .map(|pixel| [0, 1, 2].map(|channel| pixel[channel] as f32 / 255.0))
+            .collect();
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn color(&self, climate: Climate) -> [f32; 3] {
+        // rain only counts where it is warm enough, so the map is a triangle
+        let temperature = climate.temperature.clamp(0.0, 1.0);
+        let downfall = climate.downfall.clamp(0.0, 1.0) * temperature;
+        let x = ((1.0 - temperature) * (self.width - 1) as f32) as u32;
+        let y = ((1.0 - downfall) * (self.height - 1) as f32) as u32;
+        self.pixels[(y * self.width + x) as usize]
+    }
+}
+
+/// Everything needed to work out the tint of a block.
+#[derive(Clone, Debug)]
+pub struct BiomeColors {
+    pub grass: Colormap,
+    pub foliage: Colormap,
+}
+
+impl BiomeColors {
+    pub fn tint(&self, tint: Tint, climate: Climate) -> [f32; 3] {
+        match tint {
+            Tint::Grass => self.grass.color(climate),
+            Tint::Foliage => self.foliage.color(climate),
+            Tint::Water => climate.biome().water_color(),
+        }
+    }
+}
+
+/// Smooth noise from 0 to 1 that changes over about one unit.
+fn value_noise(seed: u64, x: f32, z: f32) -> f32 {
+    let (x0, z0) = (x.floor(), z.floor());
+    let (fx, fz) = (smoothstep(x - x0), smoothstep(z - z0));
+    let corner = |dx: i32, dz: i32| hash(seed, x0 as i32 + dx, z0 as i32 + dz);
+    let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * fx;
+    let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * fx;
+    top + (bottom - top) * fz
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// A random value from 0 to 1 for a lattice point.
+fn hash(seed: u64, x: i32, z: i32) -> f32 {
+    let mut h = seed ^ (x as u32 as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    h ^= (z as u32 as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+    // splitmix64 finalizer
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^= h >> 31;
+    (h >> 40) as f32 / (1u64 << 24) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 3 by 3 colormap where every pixel holds its own position.
+    fn colormap() -> Colormap {
+        Colormap {
+            width: 3,
+            height: 3,
+            pixels: (0..3)
+                .flat_map(|y| (0..3).map(move |x| [x as f32, y as f32, 0.0]))
+                .collect(),
+        }
+    }
+
+    fn climate(temperature: f32, downfall: f32) -> Climate {
+        Climate {
+            temperature,
+            downfall,
+        }
+    }
+
+    #[test]
+    fn colormap_corners() {
+        let colormap = colormap();
+        // hot and wet in the top left, hot and dry in the bottom left
+        assert_eq!(colormap.color(climate(1.0, 1.0)), [0.0, 0.0, 0.0]);
+        assert_eq!(colormap.color(climate(1.0, 0.0)), [0.0, 2.0, 0.0]);
+        // cold places are always in the bottom right, however much it rains
+        assert_eq!(colormap.color(climate(0.0, 0.0)), [2.0, 2.0, 0.0]);
+        assert_eq!(colormap.color(climate(0.0, 1.0)), [2.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn downfall_is_scaled_by_the_temperature() {
+        let colormap = colormap();
+        assert_eq!(colormap.color(climate(0.5, 1.0)), [1.0, 1.0, 0.0]);
+        assert_eq!(colormap.color(climate(0.5, 0.0)), [1.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn out_of_range_climates_are_clamped() {
+        let colormap = colormap();
+        assert_eq!(colormap.color(climate(2.0, 3.0)), [0.0, 0.0, 0.0]);
+        assert_eq!(colormap.color(climate(1.5, -1.0)), [0.0, 2.0, 0.0]);
+        assert_eq!(colormap.color(climate(-1.0, 2.0)), [2.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn biomes_follow_the_climate() {
+        assert_eq!(climate(0.2, 0.9).biome(), Biome::Taiga);
+        assert_eq!(climate(0.25, 0.2).biome(), Biome::Plains);
+        assert_eq!(climate(0.8, 0.3).biome(), Biome::Desert);
+        assert_eq!(climate(0.75, 0.3).biome(), Biome::Plains);
+        assert_eq!(climate(0.8, 0.4).biome(), Biome::Plains);
+        assert_eq!(climate(0.6, 0.8).biome(), Biome::Swamp);
+        assert_eq!(climate(0.55, 0.8).biome(), Biome::Forest);
+        assert_eq!(climate(0.4, 0.6).biome(), Biome::Forest);
+        assert_eq!(climate(0.4, 0.5).biome(), Biome::Plains);
+    }
+
+    #[test]
+    fn climates_stay_in_range() {
+        for x in -50..50 {
+            let climate = Climate::at(7, x * 13, x * -29);
+            assert!((0.0..=1.0).contains(&climate.temperature));
+            assert!((0.0..=1.0).contains(&climate.downfall));
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::time::Duration;
+
+use nalgebra::{IsometryMatrix3, Point3, Rotation3, Translation3, Vector3};
+
+use crate::{
+    input::Action,
+    physics::PlayerInput,
+    world::{render_direction_to_world, BLOCK_SIZE},
+};
+
+const MAX_PITCH: f64 = 89.0 * std::f64::consts::PI / 180.0;
+
+#[derive(Clone, Copy, Debug, Default)]
+struct HeldActions {
+    forward: bool,
+    backward: bool,
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+    sprint: bool,
+    slow: bool,
+}
+
+/// How fast the camera flies and turns.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraSettings {
+    /// blocks per second
+    pub speed: f32,
+    pub sprint_multiplier: f32,
+    pub slow_multiplier: f32,
+    /// radians per mouse count
+    pub mouse_sensitivity: f64,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            speed: 10.0,
+            sprint_multiplier: 3.0,
+            slow_multiplier: 0.25,
+            mouse_sensitivity: 0.025,
+        }
+    }
+}
+
+/// A free flying first person camera.
+///
+/// Actions only change which movement is held, the actual movement happens in [`Camera::update`]
+/// so it doesn't depend on the key repeat rate.
+#[derive(Clone, Debug)]
+pub struct Camera {
+    pub pitch: f64,
+    pub yaw: f64,
+    /// the translation of the view matrix, so the negated camera position
+    pub translation: Translation3<f32>,
+
+    pub settings: CameraSettings,
+    /// sideways and forward movement from an analog stick, between -1 and 1
+    pub analog_movement: [f32; 2],
+
+    held: HeldActions,
+}
+
+fn axis(positive: bool, negative: bool) -> f32 {
+    positive as i32 as f32 - negative as i32 as f32
+}
+
+impl Camera {
+    pub fn new(translation: Translation3<f32>, settings: CameraSettings) -> Self {
+        Self {
+            pitch: 0.0,
+            yaw: 0.0,
+            translation,
+            settings,
+            analog_movement: [0.0, 0.0],
+            held: HeldActions::default(),
+        }
+    }
+
+    pub fn build_rotation(&self) -> Rotation3<f32> {
+        let rot1 = Rotation3::new(Vector3::new(self.pitch as f32, 0.0, 0.0));
+
+        let rot2 = Rotation3::new(Vector3::new(0.0, self.yaw as f32, 0.0));
+        rot1 * rot2
+    }
+
+    pub fn view(&self) -> IsometryMatrix3<f32> {
+        self.build_rotation() * self.translation
+    }
+
+    pub fn position(&self) -> Point3<f32> {
+        Point3::from(-self.translation.vector)
+    }
+
+    /// The direction the camera looks at in world space.
+    pub fn direction(&self) -> Vector3<f32> {
+        self.build_rotation().inverse() * Vector3::new(0.0, 0.0, -1.0)
+    }
+
+    /// Returns whether the action is used by the camera.
+    pub fn handle_action(&mut self, action: Action, pressed: bool) -> bool {
+        let held = match action {
+            Action::MoveForward => &mut self.held.forward,
+            Action::MoveBackward => &mut self.held.backward,
+            Action::MoveLeft => &mut self.held.left,
+            Action::MoveRight => &mut self.held.right,
+            Action::Jump => &mut self.held.up,
+            Action::Sneak => &mut self.held.down,
+            Action::Sprint => &mut self.held.sprint,
+            Action::Slow => &mut self.held.slow,
+            _ => return false,
+        };
+        *held = pressed;
+        true
+    }
+
+    /// Forgets all held actions, e.g. because the window lost focus and we won't see them released.
+    pub fn release_all(&mut self) {
+        self.held = HeldActions::default();
+    }
+
+    pub fn handle_mouse_motion(&mut self, delta: (f64, f64)) {
+        self.rotate(
+            -delta.0 * self.settings.mouse_sensitivity,
+            -delta.1 * self.settings.mouse_sensitivity,
+        );
+    }
+
+    pub fn rotate(&mut self, yaw: f64, pitch: f64) {
+        self.yaw += yaw;
+        self.pitch = (self.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// The held movement in view space, x is right and z is backwards.
+    fn local_movement(&self) -> Vector3<f32> {
+        Vector3::new(
+            axis(self.held.right, self.held.left) + self.analog_movement[0],
+            0.0,
+            axis(self.held.backward, self.held.forward) - self.analog_movement[1],
+        )
+    }
+
+    /// The held actions as input for a walking player, which only moves horizontally
+    /// no matter where the camera looks.
+    pub fn player_input(&self) -> PlayerInput {
+        let yaw = Rotation3::new(Vector3::new(0.0, self.yaw as f32, 0.0));
+        let mut movement = render_direction_to_world(yaw.inverse() * self.local_movement());
+        if movement.norm() > 1.0 {
+            movement.normalize_mut();
+        }
+        PlayerInput {
+            movement,
+            jump: self.held.up,
+            sneak: self.held.down,
+            sprint: self.held.sprint,
+        }
+    }
+
+    pub fn update(&mut self, delta: Duration) {
+        let local = self.local_movement();
+        // up and down always follow the world axis, y points down
+        let vertical = Vector3::new(0.0, axis(self.held.down, self.held.up), 0.0);
+
+        let mut movement = self.build_rotation().inverse() * local + vertical;
+        if movement == Vector3::zeros() {
+            return;
+        }
+        // a stick that is only pushed halfway moves slower, diagonal keys don't move faster
+        if movement.norm() > 1.0 {
+            movement.normalize_mut();
+        }
+
+        let mut speed = self.settings.speed * BLOCK_SIZE;
+        if self.held.sprint {
+            speed *= self.settings.sprint_multiplier;
+        }
+        if self.held.slow {
+            speed *= self.settings.slow_multiplier;
+        }
+
+        // the translation is the negated position
+        self.translation.vector -= movement * speed * delta.as_secs_f32();
+    }
+}
@@ -0,0 +1,51 @@
+use std::time::{Duration, Instant};
+
+/// Keeps track of the simulated time that passes between frames.
+///
+/// Normally this follows the wall clock, but while recording every frame advances the
+/// simulation by exactly `fixed_timestep` so the recording is smooth no matter how long
+/// a frame actually took to render.
+pub struct FrameClock {
+    last_frame: Instant,
+    pub fixed_timestep: Option<Duration>,
+    pub simulation_time: Duration,
+    /// wall clock time per frame, smoothed over the last few frames
+    pub average_frame_time: Duration,
+}
+
+impl FrameClock {
+    pub fn new() -> Self {
+        Self {
+            last_frame: Instant::now(),
+            fixed_timestep: None,
+            simulation_time: Duration::ZERO,
+            average_frame_time: Duration::ZERO,
+        }
+    }
+
+    /// Starts a new frame and returns the simulated time since the previous one.
+    pub fn tick(&mut self) -> Duration {
+        let now = Instant::now();
+        let wall_delta = now - self.last_frame;
+        self.last_frame = now;
+        self.average_frame_time = self.average_frame_time.mul_f64(0.95) + wall_delta.mul_f64(0.05);
+
+        let delta = self.fixed_timestep.unwrap_or(wall_delta);
+        self.simulation_time += delta;
+        delta
+    }
+
+    pub fn fps(&self) -> f64 {
+        if self.average_frame_time.is_zero() {
+            0.0
+        } else {
+            1.0 / self.average_frame_time.as_secs_f64()
+        }
+    }
+}
+
+impl Default for FrameClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,579 @@
+//! Parsing and running console commands.
+//!
+//! Commands only talk to the game through [`CommandContext`], so they can run against anything
+//! that implements it, not just a running window.
+
+use std::{collections::BTreeMap, fmt};
+
+use nalgebra::Point3;
+
+use crate::{
+    physics::MovementMode,
+    sky::{DAY_LENGTH, MIDNIGHT, NOON},
+    world::Block,
+};
+
+/// `/fill` refuses to change more blocks than this at once.
+pub const MAX_FILL_VOLUME: i64 = 32 * 32 * 32;
+
+/// What commands can look at and change.
+pub trait CommandContext {
+    /// The player's feet in world coordinates.
+    fn position(&self) -> Point3<f32>;
+    fn teleport(&mut self, position: Point3<f32>);
+    fn seed(&self) -> u64;
+    /// Changes all blocks at once so meshes are only rebuilt once.
+    fn set_blocks(&mut self, blocks: &[(Point3<i32>, Block)]);
+    fn set_time(&mut self, time: u32);
+    /// Stops or continues the day night cycle.
+    fn set_time_paused(&mut self, paused: bool);
+    fn set_movement_mode(&mut self, mode: MovementMode);
+    /// In chunks around the camera.
+    fn set_render_distance(&mut self, chunks: u32);
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CommandError {
+    Unknown(String),
+    /// returned by commands, the registry replaces it with [`CommandError::Usage`]
+    WrongArguments,
+    Usage(&'static str),
+    InvalidArgument(String),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Unknown(name) => write!(f, "Unknown command {}, try /help", name),
+            CommandError::WrongArguments => write!(f, "Wrong arguments"),
+            CommandError::Usage(usage) => write!(f, "Usage: {}", usage),
+            CommandError::InvalidArgument(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+pub type CommandResult = Result<String, CommandError>;
+
+pub struct Command<C> {
+    pub usage: &'static str,
+    pub run: fn(&mut C, &[&str]) -> CommandResult,
+}
+
+pub struct CommandRegistry<C> {
+    commands: BTreeMap<&'static str, Command<C>>,
+}
+
+impl<C> Default for CommandRegistry<C> {
+    fn default() -> Self {
+        Self {
+            commands: BTreeMap::new(),
+        }
+    }
+}
+
+impl<C: CommandContext> CommandRegistry<C> {
+    /// All built in commands.
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::default();
+        registry.register("tp", "/tp <x> <y> <z>", tp);
+        registry.register("seed", "/seed", seed);
+        registry.register("setblock", "/setblock <x> <y> <z> <block>", setblock);
+        registry.register("fill", "/fill <x1> <y1> <z1> <x2> <y2> <z2> <block>", fill);
+        registry.register(
+            "time",
+            "/time <set <day|noon|night|midnight|ticks>|pause|resume>",
+            time,
+        );
+        registry.register("gamemode", "/gamemode <walking|noclip>", gamemode);
+        registry.register(
+            "render_distance",
+            "/render_distance <chunks>",
+            render_distance,
+        );
+        registry
+    }
+}
+
+impl<C> CommandRegistry<C> {
+    pub fn register(
+        &mut self,
+        name: &'static str,
+        usage: &'static str,
+        run: fn(&mut C, &[&str]) -> CommandResult,
+    ) {
+        self.commands.insert(name, Command { usage, run });
+    }
+
+    /// Runs a line like `/tp 0 10 0` and returns the message to show.
+    pub fn execute(&self, context: &mut C, line: &str) -> CommandResult {
+        let tokens = tokenize(line)?;
+        let arguments: Vec<_> = tokens.iter().map(String::as_str).collect();
+        let (name, arguments) = match arguments.split_first() {
+            Some(split) => split,
+            None => return Ok(String::new()),
+        };
+        if *name == "help" {
+            return Ok(self.help());
+        }
+        let command = self
+            .commands
+            .get(name)
+            .ok_or_else(|| CommandError::Unknown(name.to_string()))?;
+        (command.run)(context, arguments).map_err(|e| match e {
+            CommandError::WrongArguments => CommandError::Usage(command.usage),
+            e => e,
+        })
+    }
+
+    pub fn help(&self) -> String {
+        let usages: Vec<_> = self
+            .commands
+            .values()
+            .map(|command| command.usage)
+            .collect();
+        format!("Commands: {}", usages.join(", "))
+    }
+}
+
+/// Splits a command line into the command name and its arguments, the leading `/` is optional.
+///
+/// Arguments in double quotes can contain spaces, `\"` and `\\` inside of them are a quote and a
+/// backslash.
+pub fn tokenize(line: &str) -> Result<Vec<String>, CommandError> {
+    let line = line.trim();
+    let mut tokens = Vec::new();
+    let mut characters = line.strip_prefix('/').unwrap_or(line).chars();
+    // `None` between arguments, an empty quoted argument still counts
+    let mut token: Option<String> = None;
+    while let Some(character) = characters.next() {
+        match character {
+            '"' => {
+                let token = token.get_or_insert_with(String::new);
+                loop {
+                    match characters.next() {
+                        Some('"') => break,
+                        Some('\\') => match characters.next() {
+                            Some(escaped @ ('"' | '\\')) => token.push(escaped),
+                            Some(other) => {
+                                token.push('\\');
+                                token.push(other);
+                            }
+                            None => break,
+                        },
+                        Some(character) => token.push(character),
+                        None => {
+                            return Err(CommandError::InvalidArgument(
+                                "Missing closing quote".to_owned(),
+                            ))
+                        }
+                    }
+                }
+            }
+            character if character.is_whitespace() => tokens.extend(token.take()),
+            character => token.get_or_insert_with(String::new).push(character),
+        }
+    }
+    tokens.extend(token);
+    Ok(tokens)
+}
+
+/// A coordinate that is either absolute or relative to `current` when it starts with `~`.
+pub fn parse_coordinate(argument: &str, current: f32) -> Result<f32, CommandError> {
+    let invalid = || CommandError::InvalidArgument(format!("{} is not a coordinate", argument));
+    let coordinate = match argument.strip_prefix('~') {
+        Some("") => current,
+        Some(offset) => current + offset.parse::<f32>().map_err(|_| invalid())?,
+        None => argument.parse().map_err(|_| invalid())?,
+    };
+    // NaN and infinity parse as floats but there are no blocks there
+    if coordinate.is_finite() {
+        Ok(coordinate)
+    } else {
+        Err(invalid())
+    }
+}
+
+fn parse_position(arguments: &[&str], current: Point3<f32>) -> Result<Point3<f32>, CommandError> {
+    Ok(Point3::new(
+        parse_coordinate(arguments[0], current.x)?,
+        parse_coordinate(arguments[1], current.y)?,
+        parse_coordinate(arguments[2], current.z)?,
+    ))
+}
+
+fn parse_block_position(
+    arguments: &[&str],
+    current: Point3<f32>,
+) -> Result<Point3<i32>, CommandError> {
+    Ok(parse_position(arguments, current)?.map(|c| c.floor() as i32))
+}
+
+fn parse_block(argument: &str) -> Result<Block, CommandError> {
+    Block::from_name(argument)
+        .ok_or_else(|| CommandError::InvalidArgument(format!("Unknown block {}", argument)))
+}
+
+fn tp<C: CommandContext>(context: &mut C, arguments: &[&str]) -> CommandResult {
+    if arguments.len() != 3 {
+        return Err(CommandError::WrongArguments);
+    }
+    let position = parse_position(arguments, context.position())?;
+    context.teleport(position);
+    Ok(format!(
+        "Teleported to {:.2} {:.2} {:.2}",
+        position.x, position.y, position.z
+    ))
+}
+
+fn seed<C: CommandContext>(context: &mut C, _arguments: &[&str]) -> CommandResult {
+    Ok(format!("Seed: {}", context.seed()))
+}
+
+fn setblock<C: CommandContext>(context: &mut C, arguments: &[&str]) -> CommandResult {
+    if arguments.len() != 4 {
+        return Err(CommandError::WrongArguments);
+    }
+    let position = parse_block_position(&arguments[..3], context.position())?;
+    let block = parse_block(arguments[3])?;
+    context.set_blocks(&[(position, block)]);
+    Ok(format!(
+        "Placed {} at {} {} {}",
+        block.name(),
+        position.x,
+        position.y,
+        position.z
+    ))
+}
+
+fn fill<C: CommandContext>(context: &mut C, arguments: &[&str]) -> CommandResult {
+    if arguments.len() != 7 {
+        return Err(CommandError::WrongArguments);
+    }
+    let a = parse_block_position(&arguments[..3], context.position())?;
+    let b = parse_block_position(&arguments[3..6], context.position())?;
+    let block = parse_block(arguments[6])?;
+
+    let min = a.inf(&b);
+    let max = a.sup(&b);
+    // in i64 because the extent of two far apart coordinates doesn't fit into an i32
+    let volume = (0..3)
+        .map(|axis| max[axis] as i64 - min[axis] as i64 + 1)
+        .fold(1, i64::saturating_mul);
+    if !(1..=MAX_FILL_VOLUME).contains(&volume) {
+        return Err(CommandError::InvalidArgument(format!(
+            "Can't fill {} blocks, the limit is {}",
+            volume, MAX_FILL_VOLUME
+        )));
+    }
+
+    let mut blocks = Vec::with_capacity(volume as usize);
+    for x in min.x..=max.x {
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                blocks.push((Point3::new(x, y, z), block));
+            }
+        }
+    }
+    context.set_blocks(&blocks);
+    Ok(format!("Filled {} blocks with {}", volume, block.name()))
+}
+
+fn time<C: CommandContext>(context: &mut C, arguments: &[&str]) -> CommandResult {
+    let value = match arguments {
+        ["set", value] => *value,
+        ["pause"] => {
+            context.set_time_paused(true);
+            return Ok("Paused the time".to_owned());
+        }
+        ["resume"] => {
+            context.set_time_paused(false);
+            return Ok("Resumed the time".to_owned());
+        }
+        _ => return Err(CommandError::WrongArguments),
+    };
+    let time = match value {
+        "day" => 1000,
+        "noon" => NOON,
+        "night" => 13000,
+        "midnight" => MIDNIGHT,
+        ticks => {
+            ticks
+                .parse::<u32>()
+                .map_err(|_| CommandError::InvalidArgument(format!("{} is not a time", ticks)))?
+                % DAY_LENGTH
+        }
+    };
+    context.set_time(time);
+    Ok(format!("Set the time to {}", time))
+}
+
+fn gamemode<C: CommandContext>(context: &mut C, arguments: &[&str]) -> CommandResult {
+    let mode = match arguments {
+        ["walking" | "survival"] => MovementMode::Walking,
+        ["noclip" | "spectator"] => MovementMode::Noclip,
+        _ => return Err(CommandError::WrongArguments),
+    };
+    context.set_movement_mode(mode);
+    Ok(format!("Movement mode: {:?}", mode))
+}
+
+fn render_distance<C: CommandContext>(context: &mut C, arguments: &[&str]) -> CommandResult {
+    let chunks = match arguments {
+        [chunks] => chunks.parse::<u32>().map_err(|_| {
+            CommandError::InvalidArgument(format!("{} is not a number of chunks", chunks))
+        })?,
+        _ => return Err(CommandError::WrongArguments),
+    };
+    if chunks == 0 {
+        return Err(CommandError::InvalidArgument(
+            "The render distance has to be at least 1".to_owned(),
+        ));
+    }
+    context.set_render_distance(chunks);
+    Ok(format!("Render distance: {} chunks", chunks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct TestContext {
+        position: Point3<f32>,
+        blocks: Vec<(Point3<i32>, Block)>,
+        time: Option<u32>,
+        paused: Option<bool>,
+        mode: Option<MovementMode>,
+        render_distance: Option<u32>,
+    }
+
+    impl CommandContext for TestContext {
+        fn position(&self) -> Point3<f32> {
+            self.position
+        }
+
+        fn teleport(&mut self, position: Point3<f32>) {
+            self.position = position;
+        }
+
+        fn seed(&self) -> u64 {
+            42
+        }
+
+        fn set_blocks(&mut self, blocks: &[(Point3<i32>, Block)]) {
+            self.blocks.extend_from_slice(blocks);
+        }
+
+        fn set_time(&mut self, time: u32) {
+            self.time = Some(time);
+        }
+
+        fn set_time_paused(&mut self, paused: bool) {
+            self.paused = Some(paused);
+        }
+
+        fn set_movement_mode(&mut self, mode: MovementMode) {
+            self.mode = Some(mode);
+        }
+
+        fn set_render_distance(&mut self, chunks: u32) {
+            self.render_distance = Some(chunks);
+        }
+    }
+
+    fn run(context: &mut TestContext, line: &str) -> CommandResult {
+        CommandRegistry::with_defaults().execute(context, line)
+    }
+
+    #[test]
+    fn lines_are_split_into_arguments() {
+        assert_eq!(tokenize("/tp 1 2  3").unwrap(), ["tp", "1", "2", "3"]);
+        assert_eq!(tokenize("  seed ").unwrap(), ["seed"]);
+        assert!(tokenize("/").unwrap().is_empty());
+        assert!(tokenize("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn quoted_arguments_keep_their_spaces() {
+        assert_eq!(
+            tokenize(r#"/say "hello world" again"#).unwrap(),
+            ["say", "hello world", "again"]
+        );
+        assert_eq!(tokenize(r#"a"b c"d"#).unwrap(), ["ab cd"]);
+        assert_eq!(tokenize(r#"a "" b"#).unwrap(), ["a", "", "b"]);
+        assert_eq!(
+            tokenize(r#""say \"hi\"" "back\\slash" "\n""#).unwrap(),
+            [r#"say "hi""#, r"back\slash", r"\n"]
+        );
+        assert_eq!(
+            tokenize(r#"/say "open"#),
+            Err(CommandError::InvalidArgument(
+                "Missing closing quote".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn coordinates_can_be_relative() {
+        assert_eq!(parse_coordinate("3.5", 10.0), Ok(3.5));
+        assert_eq!(parse_coordinate("~", 10.0), Ok(10.0));
+        assert_eq!(parse_coordinate("~-2", 10.0), Ok(8.0));
+        assert!(parse_coordinate("~x", 10.0).is_err());
+        assert!(parse_coordinate("north", 10.0).is_err());
+    }
+
+    #[test]
+    fn coordinates_must_be_finite() {
+        for argument in ["NaN", "inf", "-infinity", "~NaN", "~inf", "1e39"] {
+            assert_eq!(
+                parse_coordinate(argument, 10.0),
+                Err(CommandError::InvalidArgument(format!(
+                    "{} is not a coordinate",
+                    argument
+                )))
+            );
+        }
+        let mut context = TestContext::default();
+        assert!(run(&mut context, "/tp NaN 0 0").is_err());
+        assert_eq!(context.position, Point3::origin());
+    }
+
+    #[test]
+    fn tp_moves_the_player() {
+        let mut context = TestContext::default();
+        assert_eq!(
+            run(&mut context, "/tp 1 2.5 -3"),
+            Ok("Teleported to 1.00 2.50 -3.00".to_owned())
+        );
+        assert_eq!(context.position, Point3::new(1.0, 2.5, -3.0));
+        run(&mut context, "/tp ~1 ~ ~-1").unwrap();
+        assert_eq!(context.position, Point3::new(2.0, 2.5, -4.0));
+    }
+
+    #[test]
+    fn time_set_accepts_names_and_ticks() {
+        let mut context = TestContext::default();
+        assert_eq!(
+            run(&mut context, "/time set noon"),
+            Ok(format!("Set the time to {}", NOON))
+        );
+        assert_eq!(context.time, Some(NOON));
+        run(&mut context, "/time set midnight").unwrap();
+        assert_eq!(context.time, Some(MIDNIGHT));
+        run(&mut context, &format!("/time set {}", DAY_LENGTH + 5)).unwrap();
+        assert_eq!(context.time, Some(5));
+        run(&mut context, "/time pause").unwrap();
+        assert_eq!(context.paused, Some(true));
+        run(&mut context, "/time resume").unwrap();
+        assert_eq!(context.paused, Some(false));
+    }
+
+    #[test]
+    fn missing_arguments_show_the_usage() {
+        let mut context = TestContext::default();
+        assert_eq!(
+            run(&mut context, "/tp 1 2"),
+            Err(CommandError::Usage("/tp <x> <y> <z>"))
+        );
+        assert!(matches!(
+            run(&mut context, "/time set"),
+            Err(CommandError::Usage(_))
+        ));
+        assert!(matches!(
+            run(&mut context, "/gamemode"),
+            Err(CommandError::Usage(_))
+        ));
+        assert_eq!(context.position, Point3::origin());
+        assert_eq!(context.time, None);
+    }
+
+    #[test]
+    fn invalid_arguments_are_explained() {
+        let mut context = TestContext::default();
+        assert_eq!(
+            run(&mut context, "/tp 1 up 3"),
+            Err(CommandError::InvalidArgument(
+                "up is not a coordinate".to_owned()
+            ))
+        );
+        assert_eq!(
+            run(&mut context, "/time set later"),
+            Err(CommandError::InvalidArgument(
+                "later is not a time".to_owned()
+            ))
+        );
+        assert_eq!(
+            run(&mut context, "/setblock 0 0 0 cheese"),
+            Err(CommandError::InvalidArgument(
+                "Unknown block cheese".to_owned()
+            ))
+        );
+        assert!(run(&mut context, "/render_distance 0").is_err());
+        assert!(run(&mut context, "/fill 0 0 0 100 100 100 stone").is_err());
+        assert!(context.blocks.is_empty());
+        assert_eq!(context.render_distance, None);
+    }
+
+    #[test]
+    fn unknown_commands_are_errors() {
+        let mut context = TestContext::default();
+        assert_eq!(
+            run(&mut context, "/fly"),
+            Err(CommandError::Unknown("fly".to_owned()))
+        );
+        assert_eq!(run(&mut context, ""), Ok(String::new()));
+        assert!(run(&mut context, "/help")
+            .unwrap()
+            .contains("/tp <x> <y> <z>"));
+    }
+
+    #[test]
+    fn blocks_are_placed_and_filled() {
+        let mut context = TestContext {
+            position: Point3::new(0.5, 1.0, 0.5),
+            ..Default::default()
+        };
+        run(&mut context, "/setblock ~ ~-1 ~ stone").unwrap();
+        assert_eq!(context.blocks, [(Point3::new(0, 0, 0), Block::Stone)]);
+        context.blocks.clear();
+        assert_eq!(
+            run(&mut context, "/fill 1 0 0 0 1 1 dirt"),
+            Ok("Filled 8 blocks with dirt".to_owned())
+        );
+        assert_eq!(context.blocks.len(), 8);
+    }
+
+    #[test]
+    fn huge_fills_are_rejected_before_allocating() {
+        let mut context = TestContext::default();
+        assert_eq!(
+            run(&mut context, "/fill 0 0 0 32 31 31 stone"),
+            Err(CommandError::InvalidArgument(format!(
+                "Can't fill 33792 blocks, the limit is {}",
+                MAX_FILL_VOLUME
+            )))
+        );
+        // the extent between these overflows an i32 and the volume an i64
+        for line in [
+            "/fill -2147483648 0 0 2147483647 0 0 stone",
+            "/fill -1e30 -1e30 -1e30 1e30 1e30 1e30 stone",
+        ] {
+            assert!(run(&mut context, line).is_err(), "{}", line);
+        }
+        assert!(context.blocks.is_empty());
+        run(&mut context, "/fill 0 0 0 31 31 31 stone").unwrap();
+        assert_eq!(context.blocks.len() as i64, MAX_FILL_VOLUME);
+    }
+
+    #[test]
+    fn settings_commands_change_the_context() {
+        let mut context = TestContext::default();
+        run(&mut context, "/gamemode noclip").unwrap();
+        assert_eq!(context.mode, Some(MovementMode::Noclip));
+        run(&mut context, "/render_distance 4").unwrap();
+        assert_eq!(context.render_distance, Some(4));
+        assert_eq!(run(&mut context, "/seed"), Ok("Seed: 42".to_owned()));
+    }
+}
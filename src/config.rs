@@ -0,0 +1,423 @@
+use std::path::PathBuf;
+
+use crate::{
+    camera::CameraSettings,
+    fog::{FogMode, FogSettings},
+    gamepad::GamepadSettings,
+    shadow::{ShadowSettings, MAX_CASCADES},
+};
+
+pub const USAGE: &str = "Usage: rust-vulkan [OPTIONS]
+
+Options:
+    --gpu <INDEX|NAME>        use the device with this index or whose name contains NAME
+    --list-devices            print the available devices and their features, then exit
+    --validation              require the Khronos validation layer
+    --no-validation           don't enable the Khronos validation layer
+                              (default: enabled if it is installed)
+    --debug-severity <LEVEL>  least severe Vulkan debug message that is logged,
+                              one of error, warning, info, verbose (default: warning)
+    --debug-types <TYPES>     comma separated Vulkan debug message types that are logged,
+                              any of general, validation, performance (default: all)
+    --panic-on-validation-error
+                              abort after the frame in which a validation error was reported
+                              (default: enabled in debug builds)
+    --no-panic-on-validation-error
+                              only log validation errors
+    --bindings <FILE>         input bindings, reloaded with F5 (default: bindings.toml)
+    --fly-speed <F>           noclip flying speed in blocks per second (default: 10)
+    --sprint-multiplier <F>   flying speed factor while sprinting (default: 3)
+    --slow-multiplier <F>     flying speed factor while moving slowly (default: 0.25)
+    --mouse-sensitivity <F>   camera turn in radians per mouse count (default: 0.025)
+    --no-gamepad              ignore game controllers
+    --gamepad-dead-zone <F>   stick deflection between 0 and 1 that is ignored (default: 0.15)
+    --gamepad-sensitivity <F> camera turn rate in radians per second at full deflection (default: 3)
+    --record-dir <DIR>        directory for recorded frames (default: recording)
+    --record-fps <FPS>        simulated frames per second while recording (default: 60)
+    --record-command <CMD>    pipe raw RGBA frames into this command instead of writing PNGs,
+                              {width}, {height} and {fps} are replaced with the frame format
+                              e.g. \"ffmpeg -f rawvideo -pix_fmt rgba -s {width}x{height} -r {fps} -i - out.mp4\"
+    --fog <MODE>              distance fog, one of off, linear, exp (default: linear)
+    --fog-start <F>           where linear fog starts as a fraction of the render distance
+                              (default: 0.6)
+    --shadow-cascades <N>     number of shadow cascades from 0 (no shadows) to 4 (default: 3)
+    --shadow-resolution <PX>  width and height of every shadow cascade (default: 2048)
+    -h, --help                print this help";
+
+/// Why [`Config::parse`] didn't return a config.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ArgsError {
+    /// the usage was asked for, which isn't a failure
+    Help,
+    /// explains what is wrong with the arguments
+    Invalid(String),
+}
+
+impl From<String> for ArgsError {
+    fn from(message: String) -> Self {
+        Self::Invalid(message)
+    }
+}
+
+impl From<&str> for ArgsError {
+    fn from(message: &str) -> Self {
+        Self::Invalid(message.to_owned())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GpuSelector {
+    Index(usize),
+    /// case insensitive substring of the device name
+    Name(String),
+}
+
+impl GpuSelector {
+    pub fn parse(value: &str) -> Self {
+        match value.parse() {
+            Ok(index) => GpuSelector::Index(index),
+            Err(_) => GpuSelector::Name(value.to_owned()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DebugSeverity {
+    Verbose,
+    Info,
+    Warning,
+    Error,
+}
+
+impl DebugSeverity {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "error" => Ok(DebugSeverity::Error),
+            "warning" => Ok(DebugSeverity::Warning),
+            "info" => Ok(DebugSeverity::Info),
+            "verbose" => Ok(DebugSeverity::Verbose),
+            _ => Err(format!("Unknown debug severity {}", value)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DebugTypes {
+    pub general: bool,
+    pub validation: bool,
+    pub performance: bool,
+}
+
+impl DebugTypes {
+    pub fn all() -> Self {
+        Self {
+            general: true,
+            validation: true,
+            performance: true,
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut types = Self {
+            general: false,
+            validation: false,
+            performance: false,
+        };
+        for name in value.split(',') {
+            match name.trim() {
+                "general" => types.general = true,
+                "validation" => types.validation = true,
+                "performance" => types.performance = true,
+                name => return Err(format!("Unknown debug message type {}", name)),
+            }
+        }
+        Ok(types)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub gpu: Option<GpuSelector>,
+    pub list_devices: bool,
+    /// `None` enables validation only if the layer is available
+    pub validation: Option<bool>,
+    pub debug_severity: DebugSeverity,
+    pub debug_types: DebugTypes,
+    pub panic_on_validation_error: bool,
+    pub bindings: PathBuf,
+    pub camera: CameraSettings,
+    pub gamepad: bool,
+    pub gamepad_settings: GamepadSettings,
+    pub record_directory: PathBuf,
+    pub record_fps: u32,
+    pub record_command: Option<String>,
+    pub fog: FogSettings,
+    pub shadows: ShadowSettings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            gpu: None,
+            list_devices: false,
+            validation: None,
+            debug_severity: DebugSeverity::Warning,
+            debug_types: DebugTypes::all(),
+            // debug builds and tests fail loudly on API misuse
+            panic_on_validation_error: cfg!(debug_assertions),
+            bindings: PathBuf::from("bindings.toml"),
+            camera: CameraSettings::default(),
+            gamepad: true,
+            gamepad_settings: GamepadSettings::default(),
+            record_directory: PathBuf::from("recording"),
+            record_fps: 60,
+            record_command: None,
+            fog: FogSettings::default(),
+            shadows: ShadowSettings::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn from_args() -> Result<Self, ArgsError> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, ArgsError> {
+        let mut config = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for {}", arg))
+            };
+            match arg.as_str() {
+                "--gpu" => config.gpu = Some(GpuSelector::parse(&value()?)),
+                "--list-devices" => config.list_devices = true,
+                "--validation" => config.validation = Some(true),
+                "--no-validation" => config.validation = Some(false),
+                "--debug-severity" => config.debug_severity = DebugSeverity::parse(&value()?)?,
+                "--debug-types" => config.debug_types = DebugTypes::parse(&value()?)?,
+                "--panic-on-validation-error" => config.panic_on_validation_error = true,
+                "--no-panic-on-validation-error" => config.panic_on_validation_error = false,
+                "--bindings" => config.bindings = PathBuf::from(value()?),
+                "--fly-speed" => config.camera.speed = positive(&arg, &value()?)? as f32,
+                "--sprint-multiplier" => {
+                    config.camera.sprint_multiplier = positive(&arg, &value()?)? as f32;
+                }
+                "--slow-multiplier" => {
+                    config.camera.slow_multiplier = positive(&arg, &value()?)? as f32;
+                }
+                "--mouse-sensitivity" => {
+                    config.camera.mouse_sensitivity = positive(&arg, &value()?)?;
+                }
+                "--no-gamepad" => config.gamepad = false,
+                "--gamepad-dead-zone" => {
+                    config.gamepad_settings.dead_zone = value()?
+                        .parse()
+                        .ok()
+                        .filter(|dead_zone| (0.0..1.0).contains(dead_zone))
+                        .ok_or("--gamepad-dead-zone needs a number between 0 and 1")?;
+                }
+                "--gamepad-sensitivity" => {
+                    config.gamepad_settings.look_sensitivity = value()?
+                        .parse()
+                        .map_err(|_| "--gamepad-sensitivity needs a number")?;
+                }
+                "--record-dir" => config.record_directory = PathBuf::from(value()?),
+                "--record-fps" => {
+                    config.record_fps = value()?
+                        .parse()
+                        .ok()
+                        .filter(|&fps| fps > 0)
+                        .ok_or("--record-fps needs a positive integer")?;
+                }
+                "--record-command" => config.record_command = Some(value()?),
+                "--fog" => config.fog.mode = FogMode::parse(&value()?)?,
+                "--fog-start" => {
+                    config.fog.start = value()?
+                        .parse()
+                        .ok()
+                        .filter(|start| (0.0..1.0).contains(start))
+                        .ok_or("--fog-start needs a number between 0 and 1")?;
+                }
+                "--shadow-cascades" => {
+                    config.shadows.cascades = value()?
+                        .parse()
+                        .ok()
+                        .filter(|&cascades| cascades <= MAX_CASCADES)
+                        .ok_or("--shadow-cascades needs an integer from 0 to 4")?;
+                }
+                "--shadow-resolution" => {
+                    config.shadows.resolution = value()?
+                        .parse()
+                        .ok()
+                        .filter(|resolution| (256..=4096).contains(resolution))
+                        .ok_or("--shadow-resolution needs an integer from 256 to 4096")?;
+                }
+                "-h" | "--help" => return Err(ArgsError::Help),
+                _ => return Err(format!("Unknown argument {}\n\n{}", arg, USAGE).into()),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn positive(arg: &str, value: &str) -> Result<f64, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|&value: &f64| value > 0.0 && value.is_finite())
+        .ok_or_else(|| format!("{} needs a positive number", arg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Config, ArgsError> {
+        Config::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn error(args: &[&str]) -> String {
+        match parse(args) {
+            Err(ArgsError::Invalid(message)) => message,
+            other => panic!("{:?} is not invalid: {:?}", args, other),
+        }
+    }
+
+    #[test]
+    fn no_arguments_give_the_defaults() {
+        let config = parse(&[]).unwrap();
+        assert_eq!(config.gpu, None);
+        assert_eq!(config.validation, None);
+        assert_eq!(config.debug_severity, DebugSeverity::Warning);
+        assert_eq!(config.debug_types, DebugTypes::all());
+        assert_eq!(config.record_directory, PathBuf::from("recording"));
+        assert_eq!(config.record_fps, 60);
+        assert_eq!(config.record_command, None);
+        assert_eq!(config.bindings, PathBuf::from("bindings.toml"));
+        assert!(config.gamepad);
+        assert_eq!(config.panic_on_validation_error, cfg!(debug_assertions));
+    }
+
+    #[test]
+    fn panicking_on_validation_errors_can_be_switched() {
+        assert!(
+            parse(&["--panic-on-validation-error"])
+                .unwrap()
+                .panic_on_validation_error
+        );
+        assert!(
+            !parse(&["--no-panic-on-validation-error"])
+                .unwrap()
+                .panic_on_validation_error
+        );
+    }
+
+    #[test]
+    fn gpu_is_an_index_or_a_name() {
+        assert_eq!(
+            parse(&["--gpu", "1"]).unwrap().gpu,
+            Some(GpuSelector::Index(1))
+        );
+        assert_eq!(
+            parse(&["--gpu", "GeForce"]).unwrap().gpu,
+            Some(GpuSelector::Name("GeForce".to_owned()))
+        );
+    }
+
+    #[test]
+    fn values_are_parsed() {
+        let config = parse(&[
+            "--no-validation",
+            "--debug-severity",
+            "info",
+            "--debug-types",
+            "general, performance",
+            "--record-dir",
+            "frames",
+            "--record-fps",
+            "30",
+            "--record-command",
+            "ffmpeg -i -",
+            "--shadow-cascades",
+            "2",
+            "--fly-speed",
+            "20",
+            "--mouse-sensitivity",
+            "0.01",
+            "--no-gamepad",
+        ])
+        .unwrap();
+        assert_eq!(config.validation, Some(false));
+        assert_eq!(config.debug_severity, DebugSeverity::Info);
+        assert_eq!(
+            config.debug_types,
+            DebugTypes {
+                general: true,
+                validation: false,
+                performance: true,
+            }
+        );
+        assert_eq!(config.record_directory, PathBuf::from("frames"));
+        assert_eq!(config.record_fps, 30);
+        assert_eq!(config.record_command.as_deref(), Some("ffmpeg -i -"));
+        assert_eq!(config.shadows.cascades, 2);
+        assert_eq!(config.camera.speed, 20.0);
+        assert_eq!(config.camera.mouse_sensitivity, 0.01);
+        assert!(!config.gamepad);
+    }
+
+    #[test]
+    fn missing_value_is_an_error() {
+        assert_eq!(error(&["--record-fps"]), "Missing value for --record-fps");
+    }
+
+    #[test]
+    fn invalid_values_are_errors() {
+        assert_eq!(
+            error(&["--record-fps", "0"]),
+            "--record-fps needs a positive integer"
+        );
+        assert_eq!(
+            error(&["--gamepad-dead-zone", "1.5"]),
+            "--gamepad-dead-zone needs a number between 0 and 1"
+        );
+        assert_eq!(
+            error(&["--shadow-cascades", "5"]),
+            "--shadow-cascades needs an integer from 0 to 4"
+        );
+        assert_eq!(
+            error(&["--slow-multiplier", "0"]),
+            "--slow-multiplier needs a positive number"
+        );
+        assert_eq!(
+            error(&["--fly-speed", "inf"]),
+            "--fly-speed needs a positive number"
+        );
+        assert_eq!(
+            error(&["--debug-severity", "loud"]),
+            "Unknown debug severity loud"
+        );
+        assert_eq!(
+            error(&["--debug-types", "general,memory"]),
+            "Unknown debug message type memory"
+        );
+    }
+
+    #[test]
+    fn unknown_argument_shows_the_usage() {
+        let message = error(&["--fullscreen"]);
+        assert!(message.starts_with("Unknown argument --fullscreen"));
+        assert!(message.ends_with(USAGE));
+    }
+
+    #[test]
+    fn help_is_not_an_error() {
+        assert_eq!(parse(&["--help"]).unwrap_err(), ArgsError::Help);
+        assert_eq!(parse(&["--fog", "off", "-h"]).unwrap_err(), ArgsError::Help);
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::VecDeque;
+
+/// Output lines that are kept around.
+const MAX_OUTPUT_LINES: usize = 10;
+/// Previous commands that can be recalled with the arrow keys.
+const MAX_HISTORY: usize = 50;
+
+/// The text input of the command console.
+///
+/// This only does the editing, running the entered commands is up to the caller.
+#[derive(Default)]
+pub struct Console {
+    pub open: bool,
+    pub input: String,
+    pub output: VecDeque<String>,
+    history: Vec<String>,
+    /// position while browsing the history, `None` while editing a new line
+    history_index: Option<usize>,
+    /// the key that opened the console may also produce a character in the same frame, which
+    /// shouldn't be typed
+    ignore_next_character: bool,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens the console with `prefix` already typed.
+    pub fn open(&mut self, prefix: &str) {
+        self.open = true;
+        self.input = prefix.to_owned();
+        self.history_index = None;
+        self.ignore_next_character = true;
+    }
+
+    /// Characters from later frames are typed, even if the opening key didn't produce one.
+    pub fn end_frame(&mut self) {
+        self.ignore_next_character = false;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.input.clear();
+    }
+
+    pub fn type_character(&mut self, character: char) {
+        if std::mem::take(&mut self.ignore_next_character) || character.is_control() {
+            return;
+        }
+        self.input.push(character);
+    }
+
+    pub fn backspace(&mut self) {
+        self.ignore_next_character = false;
+        self.input.pop();
+    }
+
+    /// Closes the console and returns the entered line.
+    pub fn submit(&mut self) -> String {
+        let line = std::mem::take(&mut self.input);
+        if !line.trim().is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            if self.history.len() > MAX_HISTORY {
+                self.history.remove(0);
+            }
+        }
+        self.close();
+        line
+    }
+
+    /// Replaces the input with an older (`true`) or newer entry from the history.
+    pub fn browse_history(&mut self, older: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+        let index = match (self.history_index, older) {
+            (None, true) => Some(self.history.len() - 1),
+            (None, false) => None,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) if index + 1 < self.history.len() => Some(index + 1),
+            (Some(_), false) => None,
+        };
+        self.history_index = index;
+        self.input = index.map_or_else(String::new, |index| self.history[index].clone());
+    }
+
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.output.push_back(line.into());
+        if self.output.len() > MAX_OUTPUT_LINES {
+            self.output.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_text(console: &mut Console, text: &str) {
+        for character in text.chars() {
+            console.type_character(character);
+        }
+    }
+
+    #[test]
+    fn the_opening_character_is_not_typed() {
+        let mut console = Console::new();
+        console.open("/");
+        type_text(&mut console, "/tp");
+        assert_eq!(console.input, "/tp");
+    }
+
+    #[test]
+    fn keys_without_a_character_dont_swallow_the_next_one() {
+        let mut console = Console::new();
+        console.open("");
+        console.end_frame();
+        type_text(&mut console, "seed");
+        assert_eq!(console.input, "seed");
+    }
+
+    #[test]
+    fn control_characters_are_ignored() {
+        let mut console = Console::new();
+        console.open("");
+        console.end_frame();
+        type_text(&mut console, "a\u{8}\rb");
+        assert_eq!(console.input, "ab");
+        console.backspace();
+        assert_eq!(console.input, "a");
+    }
+
+    #[test]
+    fn submitted_lines_can_be_recalled() {
+        let mut console = Console::new();
+        for line in ["/seed", "/tp 0 0 0", "/tp 0 0 0", "  "] {
+            console.open(line);
+            assert_eq!(console.submit(), line);
+            assert!(!console.open);
+        }
+        console.open("");
+        console.browse_history(true);
+        assert_eq!(console.input, "/tp 0 0 0");
+        // repeated lines and empty ones aren't kept twice
+        console.browse_history(true);
+        assert_eq!(console.input, "/seed");
+        console.browse_history(true);
+        assert_eq!(console.input, "/seed");
+        console.browse_history(false);
+        assert_eq!(console.input, "/tp 0 0 0");
+        console.browse_history(false);
+        assert_eq!(console.input, "");
+    }
+
+    #[test]
+    fn old_output_is_dropped() {
+        let mut console = Console::new();
+        for line in 0..MAX_OUTPUT_LINES + 3 {
+            console.print(line.to_string());
+        }
+        assert_eq!(console.output.len(), MAX_OUTPUT_LINES);
+        assert_eq!(console.output.front().unwrap(), "3");
+    }
+}
@@ -0,0 +1,129 @@
+use std::sync::{Arc, Mutex};
+
+use log::Level;
+use vulkano::{
+    device::{Device, DeviceOwned},
+    instance::{
+        debug::{
+            DebugUtilsMessageSeverity, DebugUtilsMessageType, DebugUtilsMessenger,
+            DebugUtilsMessengerCreateInfo, Message,
+        },
+        Instance,
+    },
+    VulkanObject,
+};
+
+use crate::{
+    config::{Config, DebugSeverity, DebugTypes},
+    error::{Context, Result},
+};
+
+fn message_severity(min_severity: DebugSeverity) -> DebugUtilsMessageSeverity {
+    DebugUtilsMessageSeverity {
+        error: true,
+        warning: min_severity <= DebugSeverity::Warning,
+        information: min_severity <= DebugSeverity::Info,
+        verbose: min_severity <= DebugSeverity::Verbose,
+    }
+}
+
+fn message_type(types: DebugTypes) -> DebugUtilsMessageType {
+    DebugUtilsMessageType {
+        general: types.general,
+        validation: types.validation,
+        performance: types.performance,
+    }
+}
+
+fn log_level(severity: DebugUtilsMessageSeverity) -> Level {
+    if severity.error {
+        Level::Error
+    } else if severity.warning {
+        Level::Warn
+    } else if severity.information {
+        Level::Info
+    } else {
+        Level::Debug
+    }
+}
+
+fn type_name(ty: DebugUtilsMessageType) -> &'static str {
+    if ty.validation {
+        "validation"
+    } else if ty.performance {
+        "performance"
+    } else {
+        "general"
+    }
+}
+
+/// Forwards the messages of the Vulkan debug utils to the `log` crate.
+///
+/// The messenger is unregistered when this is dropped, so it has to live as long as the renderer.
+pub struct DebugMessenger {
+    _messenger: DebugUtilsMessenger,
+    first_validation_error: Arc<Mutex<Option<String>>>,
+    panic_on_validation_error: bool,
+}
+
+impl DebugMessenger {
+    pub fn new(instance: Arc<Instance>, config: &Config) -> Result<Self> {
+        let first_validation_error = Arc::new(Mutex::new(None));
+        let callback_validation_error = first_validation_error.clone();
+
+        let create_info = DebugUtilsMessengerCreateInfo {
+            message_severity: message_severity(config.debug_severity),
+            message_type: message_type(config.debug_types),
+            ..DebugUtilsMessengerCreateInfo::user_callback(Arc::new(move |msg: &Message| {
+                log::log!(
+                    target: "vulkan",
+                    log_level(msg.severity),
+                    "[{}] {}: {}",
+                    type_name(msg.ty),
+                    msg.layer_prefix.unwrap_or("unknown"),
+                    msg.description
+                );
+
+                if msg.severity.error && msg.ty.validation {
+                    let mut first = callback_validation_error.lock().unwrap();
+                    if first.is_none() {
+                        *first = Some(msg.description.to_owned());
+                    }
+                }
+            }))
+        };
+
+        // SAFETY: the callback doesn't call into Vulkan
+        let messenger = unsafe { DebugUtilsMessenger::new(instance, create_info) }
+            .context("create the debug messenger")?;
+
+        Ok(Self {
+            _messenger: messenger,
+            first_validation_error,
+            panic_on_validation_error: config.panic_on_validation_error,
+        })
+    }
+
+    /// Panics if a validation error was reported and panicking is enabled, which it is in debug
+    /// builds unless `--no-panic-on-validation-error` was given.
+    ///
+    /// The callback itself can't panic because it is called from inside the driver.
+    pub fn check_validation_errors(&self) {
+        if !self.panic_on_validation_error {
+            return;
+        }
+        if let Some(description) = self.first_validation_error.lock().unwrap().take() {
+            panic!("Vulkan validation error: {}", description);
+        }
+    }
+}
+
+/// Names the object so validation messages refer to it by name instead of only by its handle.
+pub fn set_object_name<T: VulkanObject + DeviceOwned>(device: &Device, object: &T, name: &str) {
+    if !device.instance().enabled_extensions().ext_debug_utils {
+        return;
+    }
+    if let Err(e) = device.set_debug_utils_object_name(object, Some(name)) {
+        log::warn!("Failed to set debug name {}: {:?}", name, e);
+    }
+}
@@ -0,0 +1,114 @@
+use std::{error::Error, fmt};
+
+#[derive(Debug)]
+pub enum PoritzCraftError {
+    /// The system doesn't provide something we need, e.g. no device has the required features.
+    Unsupported(String),
+    /// A Vulkan or window system call failed.
+    Failed {
+        context: &'static str,
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// The device was lost, it has to be recreated before rendering can continue.
+    DeviceLost,
+}
+
+impl fmt::Display for PoritzCraftError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoritzCraftError::Unsupported(message) => write!(f, "{}", message),
+            PoritzCraftError::Failed { context, source } => {
+                write!(f, "Failed to {}: {}", context, source)
+            }
+            PoritzCraftError::DeviceLost => write!(f, "The Vulkan device was lost"),
+        }
+    }
+}
+
+impl Error for PoritzCraftError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PoritzCraftError::Failed { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+pub type Result<T, E = PoritzCraftError> = std::result::Result<T, E>;
+
+pub trait Context<T> {
+    /// Wraps the error with a description of what we tried to do, e.g. `"create the swapchain"`.
+    fn context(self, context: &'static str) -> Result<T>;
+}
+
+impl<T, E: Error + Send + Sync + 'static> Context<T> for std::result::Result<T, E> {
+    fn context(self, context: &'static str) -> Result<T> {
+        self.map_err(|e| PoritzCraftError::Failed {
+            context,
+            source: Box::new(e),
+        })
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context(self, context: &'static str) -> Result<T> {
+        self.ok_or_else(|| PoritzCraftError::Failed {
+            context,
+            source: "no value".into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    enum FlushError {
+        DeviceLost,
+        OutOfDate,
+    }
+
+    impl fmt::Display for FlushError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{:?}", self)
+        }
+    }
+
+    impl Error for FlushError {}
+
+    fn wrap<E: Error + Send + Sync + 'static>(error: E) -> PoritzCraftError {
+        Err::<(), _>(error).context("submit").unwrap_err()
+    }
+
+    #[test]
+    fn errors_get_the_context() {
+        match wrap(FlushError::OutOfDate) {
+            PoritzCraftError::Failed { context, source } => {
+                assert_eq!(context, "submit");
+                assert_eq!(source.to_string(), "OutOfDate");
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn lost_devices_are_not_guessed_from_the_name() {
+        // the call sites match the variants that mean a lost device themselves
+        assert!(matches!(
+            wrap(FlushError::DeviceLost),
+            PoritzCraftError::Failed { .. }
+        ));
+    }
+
+    #[test]
+    fn missing_values_get_the_context() {
+        match None::<()>.context("get the graphics queue").unwrap_err() {
+            PoritzCraftError::Failed { context, source } => {
+                assert_eq!(context, "get the graphics queue");
+                assert_eq!(source.to_string(), "no value");
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+}
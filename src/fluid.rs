@@ -0,0 +1,487 @@
+//! Water and lava that flow on scheduled ticks.
+//!
+//! Every fluid block has a level: sources have [`SOURCE_LEVEL`], flowing fluid loses some levels
+//! per block it spreads sideways and disappears at 0. Fluid falling down keeps
+//! [`FALLING_LEVEL`]. A changed block schedules a tick for itself and its neighbors, when the tick
+//! is due the block works out its new state from the blocks around it. This only reads and
+//! writes the [`World`], so it can be stepped without anything being drawn.
+//!
+//! Fluid never flows into chunks that aren't loaded, for fluid next to them they are solid.
+
+use std::collections::{BTreeMap, HashSet};
+
+use nalgebra::{Point3, Vector3};
+
+use crate::world::{Block, Face, World};
+
+pub const SOURCE_LEVEL: u8 = 8;
+/// The level of fluid that has something of the same fluid above it.
+pub const FALLING_LEVEL: u8 = SOURCE_LEVEL - 1;
+
+/// The faces fluid spreads through sideways.
+const HORIZONTAL_FACES: [Face; 4] = [Face::NegX, Face::PosX, Face::NegZ, Face::PosZ];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Fluid {
+    Water,
+    Lava,
+}
+
+impl Fluid {
+    pub const ALL: [Fluid; 2] = [Fluid::Water, Fluid::Lava];
+
+    pub fn block(self) -> Block {
+        match self {
+            Fluid::Water => Block::Water,
+            Fluid::Lava => Block::Lava,
+        }
+    }
+
+    /// Game ticks between a change next to the fluid and the fluid reacting to it.
+    pub fn tick_delay(self) -> u64 {
+        match self {
+            Fluid::Water => 5,
+            Fluid::Lava => 30,
+        }
+    }
+
+    /// How many levels the fluid loses per block it spreads sideways, lava doesn't get as far.
+    fn level_drop(self) -> u8 {
+        match self {
+            Fluid::Water => 1,
+            Fluid::Lava => 2,
+        }
+    }
+}
+
+/// Ticks that are due for changed blocks and their neighbors.
+#[derive(Clone, Debug, Default)]
+pub struct FluidSimulation {
+    /// game ticks stepped so far
+    tick: u64,
+    scheduled: BTreeMap<u64, Vec<Point3<i32>>>,
+    /// everything in `scheduled`, a position is only scheduled once at a time
+    pending: HashSet<Point3<i32>>,
+}
+
+impl FluidSimulation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of positions waiting for their tick.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Schedules ticks for the block at `position` and its neighbors after it changed.
+    pub fn block_changed(&mut self, world: &World, position: Point3<i32>) {
+        self.schedule(world, position);
+        for face in Face::ALL {
+            self.schedule(world, position + face.normal());
+        }
+    }
+
+    /// Schedules a tick for `position` if there is fluid that could change it.
+    fn schedule(&mut self, world: &World, position: Point3<i32>) {
+        if !world.is_loaded(position) {
+            return;
+        }
+        let block = world.block(position);
+        let delay = match block.fluid() {
+            Some(fluid) => fluid.tick_delay(),
+            None if block == Block::Air => {
+                // the fastest fluid that could flow in decides
+                match Face::ALL
+                    .iter()
+                    .filter_map(|face| world.block(position + face.normal()).fluid())
+                    .map(Fluid::tick_delay)
+                    .min()
+                {
+                    Some(delay) => delay,
+                    None => return,
+                }
+            }
+            None => return,
+        };
+        if self.pending.insert(position) {
+            self.scheduled
+                .entry(self.tick + delay)
+                .or_default()
+                .push(position);
+        }
+    }
+
+    /// Advances the simulation by one game tick.
+    ///
+    /// Returns the positions whose block or fluid level changed, their light and meshes have to be
+    /// updated.
+    pub fn step(&mut self, world: &mut World) -> Vec<Point3<i32>> {
+        self.tick += 1;
+        let later = self.scheduled.split_off(&(self.tick + 1));
+        let due = std::mem::replace(&mut self.scheduled, later);
+        let mut changed = Vec::new();
+        for position in due.into_values().flatten() {
+            self.pending.remove(&position);
+            let current = (world.block(position), world.fluid_level(position));
+            let next = next_state(world, position);
+            if next != current {
+                world.set_fluid(position, next.0, next.1);
+                self.block_changed(world, position);
+                changed.push(position);
+            }
+        }
+        changed
+    }
+}
+
+/// What the block at `position` turns into with the blocks around it as they are now.
+///
+/// Returns the block and its fluid level.
+pub fn next_state(world: &World, position: Point3<i32>) -> (Block, u8) {
+    let block = world.block(position);
+    let level = world.fluid_level(position);
+    if !world.is_loaded(position) {
+        return (block, level);
+    }
+    let fluid = match block.fluid() {
+        Some(fluid) => fluid,
+        None if block == Block::Air => {
+            // fluid flows into air, water wins when both could
+            return Fluid::ALL
+                .into_iter()
+                .map(|fluid| (fluid, inflow(world, position, fluid)))
+                .filter(|(_, level)| *level > 0)
+                .fold((Block::Air, 0), |best, (fluid, level)| {
+                    if level > best.1 {
+                        (fluid.block(), level)
+                    } else {
+                        best
+                    }
+                });
+        }
+        None => return (block, 0),
+    };
+
+    if fluid == Fluid::Lava
+        && Face::ALL
+            .iter()
+            .any(|face| world.block(position + face.normal()) == Block::Water)
+    {
+        return (Block::Stone, 0);
+    }
+    if level == SOURCE_LEVEL {
+        return (block, level);
+    }
+    match inflow(world, position, fluid) {
+        0 => (Block::Air, 0),
+        level => (block, level),
+    }
+}
+
+/// The level of `fluid` that flows into `position` from its neighbors, 0 if none does.
+fn inflow(world: &World, position: Point3<i32>, fluid: Fluid) -> u8 {
+    let above = position + Vector3::y();
+    if world.block(above).fluid() == Some(fluid) {
+        return FALLING_LEVEL;
+    }
+
+    let mut sources = 0;
+    let mut level = 0;
+    for face in HORIZONTAL_FACES {
+        let neighbor = position + face.normal();
+        if world.block(neighbor).fluid() != Some(fluid) {
+            continue;
+        }
+        let neighbor_level = world.fluid_level(neighbor);
+        if neighbor_level == SOURCE_LEVEL {
+            sources += 1;
+        }
+        if spreads_sideways(world, neighbor, fluid) {
+            level = level.max(neighbor_level.saturating_sub(fluid.level_drop()));
+        }
+    }
+
+    // water between two sources on solid ground or more water becomes a source itself
+    let below = position - Vector3::y();
+    let supported = is_solid(world, below)
+        || (world.block(below) == Block::Water && world.fluid_level(below) == SOURCE_LEVEL);
+    if fluid == Fluid::Water && sources >= 2 && supported {
+        return SOURCE_LEVEL;
+    }
+    level
+}
+
+/// Fluid only spreads sideways once it can't flow further down.
+fn spreads_sideways(world: &World, position: Point3<i32>, fluid: Fluid) -> bool {
+    let below = position - Vector3::y();
+    if is_solid(world, below) {
+        return true;
+    }
+    match world.block(below) {
+        Block::Air => false,
+        block if block.fluid() == Some(fluid) => world.fluid_level(below) == SOURCE_LEVEL,
+        _ => true,
+    }
+}
+
+/// Unloaded chunks are solid, fluid can't flow into them.
+fn is_solid(world: &World, position: Point3<i32>) -> bool {
+    !world.is_loaded(position) || world.is_solid(position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A stone floor at y = 0 over four chunks.
+    fn floor() -> World {
+        let mut world = World::new();
+        for x in 0..32 {
+            for z in 0..32 {
+                world.set_block(Point3::new(x, 0, z), Block::Stone);
+            }
+        }
+        world
+    }
+
+    fn place(
+        world: &mut World,
+        simulation: &mut FluidSimulation,
+        position: Point3<i32>,
+        block: Block,
+    ) {
+        world.set_block(position, block);
+        simulation.block_changed(world, position);
+    }
+
+    fn run(world: &mut World, simulation: &mut FluidSimulation, ticks: u32) {
+        for _ in 0..ticks {
+            simulation.step(world);
+        }
+    }
+
+    fn state(world: &World, x: i32, y: i32, z: i32) -> (Block, u8) {
+        let position = Point3::new(x, y, z);
+        (world.block(position), world.fluid_level(position))
+    }
+
+    #[test]
+    fn water_spreads_seven_blocks() {
+        let mut world = floor();
+        let mut simulation = FluidSimulation::new();
+        place(
+            &mut world,
+            &mut simulation,
+            Point3::new(16, 1, 16),
+            Block::Water,
+        );
+        run(&mut world, &mut simulation, 200);
+        assert_eq!(state(&world, 16, 1, 16), (Block::Water, SOURCE_LEVEL));
+        assert_eq!(state(&world, 17, 1, 16), (Block::Water, 7));
+        assert_eq!(state(&world, 16, 1, 9), (Block::Water, 1));
+        assert_eq!(state(&world, 16, 1, 8), (Block::Air, 0));
+        // it spreads around corners, so the distance is counted along the axes
+        assert_eq!(state(&world, 19, 1, 20), (Block::Water, 1));
+        assert_eq!(state(&world, 20, 1, 20), (Block::Air, 0));
+        assert_eq!(state(&world, 16, 2, 16), (Block::Air, 0));
+        assert_eq!(simulation.pending(), 0);
+    }
+
+    #[test]
+    fn lava_spreads_three_blocks() {
+        let mut world = floor();
+        let mut simulation = FluidSimulation::new();
+        place(
+            &mut world,
+            &mut simulation,
+            Point3::new(16, 1, 16),
+            Block::Lava,
+        );
+        // lava is slower, after one water delay nothing moved yet
+        run(
+            &mut world,
+            &mut simulation,
+            Fluid::Water.tick_delay() as u32,
+        );
+        assert_eq!(state(&world, 17, 1, 16), (Block::Air, 0));
+        run(&mut world, &mut simulation, 400);
+        assert_eq!(state(&world, 17, 1, 16), (Block::Lava, 6));
+        assert_eq!(state(&world, 19, 1, 16), (Block::Lava, 2));
+        assert_eq!(state(&world, 20, 1, 16), (Block::Air, 0));
+    }
+
+    #[test]
+    fn fluid_falls_before_it_spreads() {
+        let mut world = floor();
+        let mut simulation = FluidSimulation::new();
+        place(
+            &mut world,
+            &mut simulation,
+            Point3::new(10, 6, 10),
+            Block::Water,
+        );
+        run(&mut world, &mut simulation, 200);
+        for y in 1..6 {
+            assert_eq!(
+                state(&world, 10, y, 10),
+                (Block::Water, FALLING_LEVEL),
+                "y {}",
+                y
+            );
+        }
+        // nothing spreads sideways in the air
+        assert_eq!(state(&world, 11, 6, 10), (Block::Air, 0));
+        assert_eq!(state(&world, 11, 2, 10), (Block::Air, 0));
+        // on the ground it spreads from the falling level
+        assert_eq!(state(&world, 11, 1, 10), (Block::Water, FALLING_LEVEL - 1));
+        assert_eq!(state(&world, 10, 1, 4), (Block::Water, 1));
+        assert_eq!(state(&world, 10, 1, 3), (Block::Air, 0));
+    }
+
+    #[test]
+    fn water_between_two_sources_becomes_a_source() {
+        let mut world = floor();
+        let mut simulation = FluidSimulation::new();
+        place(
+            &mut world,
+            &mut simulation,
+            Point3::new(10, 1, 10),
+            Block::Water,
+        );
+        place(
+            &mut world,
+            &mut simulation,
+            Point3::new(12, 1, 10),
+            Block::Water,
+        );
+        run(&mut world, &mut simulation, 100);
+        assert_eq!(state(&world, 11, 1, 10), (Block::Water, SOURCE_LEVEL));
+
+        // the new source stays after one of the others is gone
+        place(
+            &mut world,
+            &mut simulation,
+            Point3::new(12, 1, 10),
+            Block::Air,
+        );
+        run(&mut world, &mut simulation, 100);
+        assert_eq!(state(&world, 11, 1, 10), (Block::Water, SOURCE_LEVEL));
+        // with a single source next to it the removed one is only refilled by flowing water
+        assert_eq!(state(&world, 12, 1, 10), (Block::Water, 7));
+
+        // but not in the air
+        place(
+            &mut world,
+            &mut simulation,
+            Point3::new(20, 5, 20),
+            Block::Stone,
+        );
+        place(
+            &mut world,
+            &mut simulation,
+            Point3::new(22, 5, 20),
+            Block::Stone,
+        );
+        place(
+            &mut world,
+            &mut simulation,
+            Point3::new(20, 6, 20),
+            Block::Water,
+        );
+        place(
+            &mut world,
+            &mut simulation,
+            Point3::new(22, 6, 20),
+            Block::Water,
+        );
+        run(&mut world, &mut simulation, 100);
+        assert_eq!(state(&world, 21, 6, 20).1, SOURCE_LEVEL - 1);
+        assert_eq!(state(&world, 21, 5, 20), (Block::Water, FALLING_LEVEL));
+    }
+
+    #[test]
+    fn lava_sources_dont_multiply() {
+        let mut world = floor();
+        let mut simulation = FluidSimulation::new();
+        place(
+            &mut world,
+            &mut simulation,
+            Point3::new(10, 1, 10),
+            Block::Lava,
+        );
+        place(
+            &mut world,
+            &mut simulation,
+            Point3::new(12, 1, 10),
+            Block::Lava,
+        );
+        run(&mut world, &mut simulation, 400);
+        assert_eq!(state(&world, 11, 1, 10), (Block::Lava, 6));
+    }
+
+    #[test]
+    fn lava_touching_water_turns_into_stone() {
+        let mut world = floor();
+        let mut simulation = FluidSimulation::new();
+        place(
+            &mut world,
+            &mut simulation,
+            Point3::new(10, 1, 10),
+            Block::Lava,
+        );
+        place(
+            &mut world,
+            &mut simulation,
+            Point3::new(12, 1, 10),
+            Block::Water,
+        );
+        run(&mut world, &mut simulation, 400);
+        assert_eq!(state(&world, 10, 1, 10), (Block::Stone, 0));
+        assert_eq!(state(&world, 12, 1, 10), (Block::Water, SOURCE_LEVEL));
+        assert!((0..32).all(|x| (0..32).all(|z| world.block(Point3::new(x, 1, z)) != Block::Lava)));
+    }
+
+    #[test]
+    fn fluid_drains_after_its_source_is_removed() {
+        let mut world = floor();
+        let mut simulation = FluidSimulation::new();
+        let source = Point3::new(16, 3, 16);
+        place(&mut world, &mut simulation, source, Block::Water);
+        run(&mut world, &mut simulation, 200);
+        assert_eq!(state(&world, 16, 1, 20), (Block::Water, 3));
+
+        place(&mut world, &mut simulation, source, Block::Air);
+        run(&mut world, &mut simulation, 400);
+        assert_eq!(
+            world
+                .blocks()
+                .filter(|(_, block)| *block == Block::Water)
+                .count(),
+            0
+        );
+        assert_eq!(simulation.pending(), 0);
+    }
+
+    #[test]
+    fn fluid_doesnt_flow_into_unloaded_chunks() {
+        let mut world = World::new();
+        let mut simulation = FluidSimulation::new();
+        // at the bottom of the only chunk, the one below isn't loaded
+        place(
+            &mut world,
+            &mut simulation,
+            Point3::new(14, 0, 8),
+            Block::Water,
+        );
+        run(&mut world, &mut simulation, 200);
+        assert!(!world.is_loaded(Point3::new(14, -1, 8)));
+        assert!(!world.is_loaded(Point3::new(16, 0, 8)));
+        assert_eq!(state(&world, 14, -1, 8), (Block::Air, 0));
+        assert_eq!(state(&world, 16, 0, 8), (Block::Air, 0));
+        // it spreads over the unloaded chunk like over solid ground
+        assert_eq!(state(&world, 15, 0, 8), (Block::Water, 7));
+        assert_eq!(state(&world, 8, 0, 8), (Block::Water, 2));
+        assert_eq!(world.chunk_positions().count(), 1);
+    }
+}
@@ -0,0 +1,111 @@
+//! Fog that hides where the drawn chunks end by blending the terrain into the sky.
+
+use crate::{
+    fluid::Fluid,
+    world::{BLOCK_SIZE, CHUNK_SIZE},
+};
+
+/// Fog color while the camera is under water.
+const UNDERWATER_COLOR: [f32; 3] = [0.05, 0.2, 0.45];
+/// In blocks, nothing further away can be seen under water.
+const UNDERWATER_DISTANCE: f32 = 12.0;
+const LAVA_COLOR: [f32; 3] = [0.6, 0.1, 0.0];
+/// Lava is barely see-through.
+const LAVA_DISTANCE: f32 = 1.5;
+/// How much of the color is left at the end of exponential fog.
+const EXPONENTIAL_REMAINDER: f32 = 0.02;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FogMode {
+    Off,
+    /// from no fog at the start distance to full fog at the end
+    Linear,
+    /// gets denser with distance, almost opaque at the end
+    Exponential,
+}
+
+impl FogMode {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "off" => Ok(FogMode::Off),
+            "linear" => Ok(FogMode::Linear),
+            "exp" | "exponential" => Ok(FogMode::Exponential),
+            _ => Err(format!("Unknown fog mode {}", value)),
+        }
+    }
+
+    /// How the fragment shader tells the modes apart.
+    fn shader_index(self) -> f32 {
+        match self {
+            FogMode::Off => 0.0,
+            FogMode::Linear => 1.0,
+            FogMode::Exponential => 2.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FogSettings {
+    pub mode: FogMode,
+    /// where linear fog starts as a fraction of the render distance
+    pub start: f32,
+}
+
+impl Default for FogSettings {
+    fn default() -> Self {
+        Self {
+            mode: FogMode::Linear,
+            start: 0.6,
+        }
+    }
+}
+
+/// The fog of one frame, distances are in blocks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fog {
+    pub mode: FogMode,
+    pub color: [f32; 3],
+    pub start: f32,
+    pub end: f32,
+}
+
+impl Fog {
+    /// Fog that ends where the drawn chunks end, in the color of the horizon.
+    pub fn new(settings: &FogSettings, render_distance: u32, sky_color: [f32; 3]) -> Self {
+        let end = (render_distance * CHUNK_SIZE as u32) as f32;
+        Self {
+            mode: settings.mode,
+            color: sky_color,
+            start: end * settings.start,
+            end,
+        }
+    }
+
+    /// Fog while the camera is inside of `fluid`.
+    pub fn in_fluid(fluid: Fluid) -> Self {
+        let (color, end) = match fluid {
+            Fluid::Water => (UNDERWATER_COLOR, UNDERWATER_DISTANCE),
+            Fluid::Lava => (LAVA_COLOR, LAVA_DISTANCE),
+        };
+        Self {
+            mode: FogMode::Exponential,
+            color,
+            start: 0.0,
+            end,
+        }
+    }
+
+    /// The color and the start, end and density in render units as they are passed to the
+    /// fragment shader, the mode is stored in the alpha of the color.
+    pub fn shader_parameters(&self) -> ([f32; 4], [f32; 3]) {
+        let start = self.start * BLOCK_SIZE;
+        let end = self.end * BLOCK_SIZE;
+        // exponential fog starts at `start` as well
+        let density = -EXPONENTIAL_REMAINDER.ln() / (end - start).max(1.0);
+        let [r, g, b] = self.color;
+        (
+            [r, g, b, self.mode.shader_index()],
+            [start, end.max(start + 1.0), density],
+        )
+    }
+}
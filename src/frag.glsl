@@ -1,20 +1,16 @@
 #version 450
 
 #extension GL_EXT_nonuniform_qualifier : enable
+#extension GL_GOOGLE_include_directive : require
 
-layout(location = 0) flat in vec3 v_normal;
-layout(location = 1) in vec2 tex_coords;
-layout(location = 2) in flat uint v_block_type;
-
-layout(location = 0) out vec4 f_color;
-
-layout(set = 1, binding = 0) uniform sampler2D tex[];
-
-const vec3 LIGHT = vec3(1.0, 5.0, 1.0);
+#include "terrain.glsl"
 
 void main() {
-    float brightness = clamp(dot(normalize(v_normal), normalize(LIGHT)), 0.6, 1);
-  
-    vec4 texture_color = texture(tex[nonuniformEXT(v_block_type)], tex_coords);
-    f_color = texture_color * 2.0 * brightness;
-}
\ No newline at end of file
+    vec4 texture_color = block_texture();
+    if (texture_color.a < constants.alpha_cutoff) {
+        discard;
+    }
+
+    vec3 color = texture_color.rgb * v_tint * brightness();
+    f_color = vec4(mix(color, constants.fog_color.rgb, fog_amount()), texture_color.a);
+}
@@ -0,0 +1,312 @@
+use std::{collections::VecDeque, time::Duration};
+
+use gilrs::{Axis, Button, EventType, Gilrs};
+
+use crate::input::Action;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GamepadEvent {
+    Axis(Axis, f32),
+    Button(Button, bool),
+    Disconnected,
+}
+
+/// Where gamepad events come from, so the input handling works without a real controller.
+pub trait GamepadSource {
+    fn next_event(&mut self) -> Option<GamepadEvent>;
+}
+
+pub struct GilrsSource {
+    gilrs: Gilrs,
+}
+
+impl GilrsSource {
+    pub fn new() -> Result<Self, gilrs::Error> {
+        let gilrs = Gilrs::new()?;
+        for (_, gamepad) in gilrs.gamepads() {
+            log::info!("Found gamepad {}", gamepad.name());
+        }
+        Ok(Self { gilrs })
+    }
+}
+
+impl GamepadSource for GilrsSource {
+    fn next_event(&mut self) -> Option<GamepadEvent> {
+        while let Some(event) = self.gilrs.next_event() {
+            let event = match event.event {
+                EventType::AxisChanged(axis, value, _) => GamepadEvent::Axis(axis, value),
+                EventType::ButtonPressed(button, _) => GamepadEvent::Button(button, true),
+                EventType::ButtonReleased(button, _) => GamepadEvent::Button(button, false),
+                EventType::Connected => {
+                    log::info!("Gamepad {} connected", self.gilrs.gamepad(event.id).name());
+                    continue;
+                }
+                EventType::Disconnected => GamepadEvent::Disconnected,
+                _ => continue,
+            };
+            return Some(event);
+        }
+        None
+    }
+}
+
+/// A controller that replays queued events.
+#[derive(Default)]
+pub struct SimulatedGamepad {
+    pub events: VecDeque<GamepadEvent>,
+}
+
+impl GamepadSource for SimulatedGamepad {
+    fn next_event(&mut self) -> Option<GamepadEvent> {
+        self.events.pop_front()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GamepadSettings {
+    /// stick deflections below this are ignored, between 0 and 1
+    pub dead_zone: f32,
+    /// radians per second at full deflection of the right stick
+    pub look_sensitivity: f64,
+}
+
+impl Default for GamepadSettings {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.15,
+            look_sensitivity: 3.0,
+        }
+    }
+}
+
+fn button_action(button: Button) -> Option<Action> {
+    match button {
+        Button::South => Some(Action::Jump),
+        Button::East => Some(Action::Sneak),
+        Button::North => Some(Action::Inventory),
+        Button::LeftThumb => Some(Action::Sprint),
+        Button::RightTrigger2 => Some(Action::Break),
+        Button::LeftTrigger2 => Some(Action::Place),
+        Button::RightTrigger => Some(Action::HotbarNext),
+        Button::LeftTrigger => Some(Action::HotbarPrevious),
+        Button::Select => Some(Action::ToggleDebug),
+        _ => None,
+    }
+}
+
+/// Applies a radial dead zone and rescales the rest of the range to start at 0 again.
+pub fn apply_dead_zone(stick: [f32; 2], dead_zone: f32) -> [f32; 2] {
+    let magnitude = (stick[0] * stick[0] + stick[1] * stick[1]).sqrt();
+    if magnitude <= dead_zone {
+        return [0.0, 0.0];
+    }
+    let scaled = ((magnitude - dead_zone) / (1.0 - dead_zone)).min(1.0);
+    [stick[0] / magnitude * scaled, stick[1] / magnitude * scaled]
+}
+
+pub struct GamepadInput<S> {
+    source: S,
+    pub settings: GamepadSettings,
+    left_stick: [f32; 2],
+    right_stick: [f32; 2],
+    /// buttons that are held down, in the order they were pressed
+    held_buttons: Vec<Button>,
+}
+
+impl<S: GamepadSource> GamepadInput<S> {
+    pub fn new(source: S, settings: GamepadSettings) -> Self {
+        Self {
+            source,
+            settings,
+            left_stick: [0.0, 0.0],
+            right_stick: [0.0, 0.0],
+            held_buttons: Vec::new(),
+        }
+    }
+
+    /// Processes all pending events and returns the actions they press or release.
+    pub fn poll(&mut self) -> Vec<(Action, bool)> {
+        let mut actions = Vec::new();
+        while let Some(event) = self.source.next_event() {
+            match event {
+                GamepadEvent::Axis(Axis::LeftStickX, value) => self.left_stick[0] = value,
+                GamepadEvent::Axis(Axis::LeftStickY, value) => self.left_stick[1] = value,
+                GamepadEvent::Axis(Axis::RightStickX, value) => self.right_stick[0] = value,
+                GamepadEvent::Axis(Axis::RightStickY, value) => self.right_stick[1] = value,
+                GamepadEvent::Axis(..) => {}
+                GamepadEvent::Button(button, pressed) => {
+                    self.held_buttons.retain(|&held| held != button);
+                    if pressed {
+                        self.held_buttons.push(button);
+                    }
+                    if let Some(action) = button_action(button) {
+                        actions.push((action, pressed));
+                    }
+                }
+                GamepadEvent::Disconnected => {
+                    self.left_stick = [0.0, 0.0];
+                    self.right_stick = [0.0, 0.0];
+                    // the released events will never arrive
+                    actions.extend(
+                        self.held_buttons
+                            .drain(..)
+                            .filter_map(button_action)
+                            .map(|action| (action, false)),
+                    );
+                }
+            }
+        }
+        actions
+    }
+
+    /// Sideways and forward movement between -1 and 1 from the left stick.
+    pub fn movement(&self) -> [f32; 2] {
+        apply_dead_zone(self.left_stick, self.settings.dead_zone)
+    }
+
+    /// How far the right stick turns the camera during `delta` as (yaw, pitch) in radians.
+    pub fn look(&self, delta: Duration) -> (f64, f64) {
+        let stick = apply_dead_zone(self.right_stick, self.settings.dead_zone);
+        let scale = self.settings.look_sensitivity * delta.as_secs_f64();
+        // pushing the stick right turns right, pushing it up looks up
+        (-stick[0] as f64 * scale, stick[1] as f64 * scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn gamepad(events: &[GamepadEvent]) -> GamepadInput<SimulatedGamepad> {
+        let source = SimulatedGamepad {
+            events: events.iter().copied().collect(),
+        };
+        GamepadInput::new(source, GamepadSettings::default())
+    }
+
+    fn assert_close(actual: [f32; 2], expected: [f32; 2]) {
+        assert!(
+            (actual[0] - expected[0]).abs() < EPSILON && (actual[1] - expected[1]).abs() < EPSILON,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn dead_zone_ignores_small_deflections() {
+        assert_eq!(apply_dead_zone([0.0, 0.0], 0.2), [0.0, 0.0]);
+        assert_eq!(apply_dead_zone([0.1, -0.1], 0.2), [0.0, 0.0]);
+        // exactly at the threshold still counts as resting
+        assert_eq!(apply_dead_zone([0.2, 0.0], 0.2), [0.0, 0.0]);
+        assert_eq!(apply_dead_zone([0.0, -0.2], 0.2), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn dead_zone_rescales_the_rest_of_the_range() {
+        assert_close(apply_dead_zone([0.6, 0.0], 0.2), [0.5, 0.0]);
+        assert_close(apply_dead_zone([0.0, -1.0], 0.2), [0.0, -1.0]);
+        // the direction is kept, only the length changes
+        let diagonal = 0.6 / 2f32.sqrt();
+        let scaled = 0.5 / 2f32.sqrt();
+        assert_close(apply_dead_zone([diagonal, diagonal], 0.2), [scaled, scaled]);
+        // corners of square sticks go past 1
+        assert_close(
+            apply_dead_zone([1.0, 1.0], 0.2),
+            [1.0 / 2f32.sqrt(), 1.0 / 2f32.sqrt()],
+        );
+    }
+
+    #[test]
+    fn left_stick_moves() {
+        let mut input = gamepad(&[
+            GamepadEvent::Axis(Axis::LeftStickX, 0.05),
+            GamepadEvent::Axis(Axis::LeftStickY, 1.0),
+        ]);
+        assert!(input.poll().is_empty());
+        // a full deflection stays full, the small sideways part only tilts it
+        let length = (0.05f32 * 0.05 + 1.0).sqrt();
+        assert_close(input.movement(), [0.05 / length, 1.0 / length]);
+
+        input.source.events.extend([
+            GamepadEvent::Axis(Axis::LeftStickX, 0.0),
+            GamepadEvent::Axis(Axis::LeftStickY, -0.575),
+        ]);
+        input.poll();
+        assert_close(input.movement(), [0.0, -0.5]);
+    }
+
+    #[test]
+    fn right_stick_turns_by_the_sensitivity() {
+        let mut input = gamepad(&[GamepadEvent::Axis(Axis::RightStickX, 1.0)]);
+        input.poll();
+        let (yaw, pitch) = input.look(Duration::from_millis(500));
+        assert!((yaw + 1.5).abs() < 1e-6, "yaw {}", yaw);
+        assert_eq!(pitch, 0.0);
+
+        input.settings.look_sensitivity = 1.0;
+        input.source.events.extend([
+            GamepadEvent::Axis(Axis::RightStickX, 0.0),
+            GamepadEvent::Axis(Axis::RightStickY, -1.0),
+        ]);
+        input.poll();
+        let (yaw, pitch) = input.look(Duration::from_secs(2));
+        assert_eq!(yaw, 0.0);
+        assert!((pitch + 2.0).abs() < 1e-6, "pitch {}", pitch);
+    }
+
+    #[test]
+    fn resting_sticks_do_nothing() {
+        let mut input = gamepad(&[
+            GamepadEvent::Axis(Axis::LeftStickX, 0.1),
+            GamepadEvent::Axis(Axis::RightStickY, -0.1),
+        ]);
+        input.poll();
+        assert_eq!(input.movement(), [0.0, 0.0]);
+        assert_eq!(input.look(Duration::from_secs(1)), (0.0, 0.0));
+    }
+
+    #[test]
+    fn buttons_press_and_release_actions() {
+        let mut input = gamepad(&[
+            GamepadEvent::Button(Button::South, true),
+            GamepadEvent::Button(Button::RightTrigger2, true),
+            GamepadEvent::Button(Button::Start, true),
+            GamepadEvent::Button(Button::South, false),
+        ]);
+        assert_eq!(
+            input.poll(),
+            [
+                (Action::Jump, true),
+                (Action::Break, true),
+                (Action::Jump, false)
+            ]
+        );
+        assert!(input.poll().is_empty());
+    }
+
+    #[test]
+    fn disconnecting_centers_the_sticks() {
+        let mut input = gamepad(&[
+            GamepadEvent::Axis(Axis::LeftStickY, 1.0),
+            GamepadEvent::Axis(Axis::RightStickX, 1.0),
+            GamepadEvent::Button(Button::South, true),
+            GamepadEvent::Button(Button::LeftTrigger2, true),
+            GamepadEvent::Button(Button::RightTrigger2, true),
+            GamepadEvent::Button(Button::South, false),
+        ]);
+        assert_eq!(input.poll().len(), 4);
+        input.source.events.push_back(GamepadEvent::Disconnected);
+        assert_eq!(
+            input.poll(),
+            [(Action::Place, false), (Action::Break, false)]
+        );
+        assert_eq!(input.movement(), [0.0, 0.0]);
+        assert_eq!(input.look(Duration::from_secs(1)), (0.0, 0.0));
+
+        input.source.events.push_back(GamepadEvent::Disconnected);
+        assert!(input.poll().is_empty());
+    }
+}
@@ -0,0 +1,81 @@
+use crate::world::Block;
+
+pub const HOTBAR_SLOTS: usize = 9;
+
+/// The blocks the player can place, one of them is selected at a time.
+#[derive(Clone, Debug)]
+pub struct Hotbar {
+    /// `Block::Air` marks an empty slot
+    pub slots: [Block; HOTBAR_SLOTS],
+    pub selected: usize,
+}
+
+impl Default for Hotbar {
+    fn default() -> Self {
+        let mut slots = [Block::Air; HOTBAR_SLOTS];
+        slots[0] = Block::Dirt;
+        slots[1] = Block::Stone;
+        slots[2] = Block::Glowstone;
+        slots[3] = Block::Leaves;
+        slots[4] = Block::Glass;
+        slots[5] = Block::StainedGlass;
+        slots[6] = Block::Water;
+        slots[7] = Block::Lava;
+        slots[8] = Block::Grass;
+        Self { slots, selected: 0 }
+    }
+}
+
+impl Hotbar {
+    pub fn select(&mut self, slot: usize) {
+        if slot < HOTBAR_SLOTS {
+            self.selected = slot;
+        }
+    }
+
+    /// Moves the selection by `steps` slots, wrapping around at both ends.
+    pub fn scroll(&mut self, steps: i32) {
+        self.selected = (self.selected as i32 + steps).rem_euclid(HOTBAR_SLOTS as i32) as usize;
+    }
+
+    /// The block to place, `None` if the selected slot is empty.
+    pub fn selected_block(&self) -> Option<Block> {
+        Some(self.slots[self.selected]).filter(|block| *block != Block::Air)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrolling_wraps_around() {
+        let mut hotbar = Hotbar::default();
+        hotbar.scroll(-1);
+        assert_eq!(hotbar.selected, HOTBAR_SLOTS - 1);
+        hotbar.scroll(1);
+        assert_eq!(hotbar.selected, 0);
+        hotbar.scroll(HOTBAR_SLOTS as i32 + 2);
+        assert_eq!(hotbar.selected, 2);
+        hotbar.scroll(-(HOTBAR_SLOTS as i32) * 3 - 3);
+        assert_eq!(hotbar.selected, HOTBAR_SLOTS - 1);
+    }
+
+    #[test]
+    fn slots_outside_the_hotbar_are_ignored() {
+        let mut hotbar = Hotbar::default();
+        hotbar.select(4);
+        assert_eq!(hotbar.selected, 4);
+        hotbar.select(HOTBAR_SLOTS);
+        assert_eq!(hotbar.selected, 4);
+    }
+
+    #[test]
+    fn empty_slots_have_no_block() {
+        let mut hotbar = Hotbar::default();
+        assert_eq!(hotbar.selected_block(), Some(Block::Dirt));
+        hotbar.slots[3] = Block::Air;
+        hotbar.select(3);
+        assert_eq!(hotbar.selected_block(), None);
+    }
+}
@@ -0,0 +1,483 @@
+//! Maps raw winit input to game actions.
+//!
+//! The bindings are read from a TOML file in which every action lists the inputs that trigger it,
+//! actions that aren't listed keep their default bindings:
+//!
+//! ```toml
+//! [bindings]
+//! MoveForward = ["Z", "Up"]
+//! MoveLeft = ["Q"]
+//! Break = ["MouseLeft"]
+//! Place = ["MouseRight"]
+//! ```
+//!
+//! Keys use the names of winit's `VirtualKeyCode`, mouse buttons are `MouseLeft`, `MouseRight`,
+//! `MouseMiddle` or `Mouse<N>` and the wheel is `WheelUp` / `WheelDown`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::Path,
+};
+
+use serde::{
+    de::{self, IntoDeserializer},
+    Deserialize, Deserializer,
+};
+use winit::event::{MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+
+use crate::{
+    error::{Context, Result},
+    utils::state_is_pressed,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Sneak,
+    Sprint,
+    Slow,
+    Break,
+    Place,
+    Inventory,
+    ToggleDebug,
+    Screenshot,
+    ToggleRecording,
+    ReloadBindings,
+    ToggleNoclip,
+    OpenConsole,
+    OpenCommand,
+    HotbarNext,
+    HotbarPrevious,
+    HotbarSlot1,
+    HotbarSlot2,
+    HotbarSlot3,
+    HotbarSlot4,
+    HotbarSlot5,
+    HotbarSlot6,
+    HotbarSlot7,
+    HotbarSlot8,
+    HotbarSlot9,
+}
+
+impl Action {
+    /// The hotbar slot this action selects, starting at 0.
+    pub fn hotbar_slot(self) -> Option<usize> {
+        let slot = match self {
+            Action::HotbarSlot1 => 0,
+            Action::HotbarSlot2 => 1,
+            Action::HotbarSlot3 => 2,
+            Action::HotbarSlot4 => 3,
+            Action::HotbarSlot5 => 4,
+            Action::HotbarSlot6 => 5,
+            Action::HotbarSlot7 => 6,
+            Action::HotbarSlot8 => 7,
+            Action::HotbarSlot9 => 8,
+            _ => return None,
+        };
+        Some(slot)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Input {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+    WheelUp,
+    WheelDown,
+}
+
+impl Input {
+    pub fn parse(name: &str) -> Option<Self> {
+        let input = match name {
+            "MouseLeft" => Input::Mouse(MouseButton::Left),
+            "MouseRight" => Input::Mouse(MouseButton::Right),
+            "MouseMiddle" => Input::Mouse(MouseButton::Middle),
+            "WheelUp" => Input::WheelUp,
+            "WheelDown" => Input::WheelDown,
+            _ => match name.strip_prefix("Mouse").map(str::parse) {
+                Some(Ok(button)) => Input::Mouse(MouseButton::Other(button)),
+                _ => {
+                    let deserializer: de::value::StrDeserializer<de::value::Error> =
+                        name.into_deserializer();
+                    Input::Key(VirtualKeyCode::deserialize(deserializer).ok()?)
+                }
+            },
+        };
+        Some(input)
+    }
+}
+
+impl<'de> Deserialize<'de> for Input {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Input::parse(&name).ok_or_else(|| de::Error::custom(format!("unknown input {}", name)))
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Input::Key(key_code) => write!(f, "{:?}", key_code),
+            Input::Mouse(MouseButton::Left) => write!(f, "MouseLeft"),
+            Input::Mouse(MouseButton::Right) => write!(f, "MouseRight"),
+            Input::Mouse(MouseButton::Middle) => write!(f, "MouseMiddle"),
+            Input::Mouse(MouseButton::Other(button)) => write!(f, "Mouse{}", button),
+            Input::WheelUp => write!(f, "WheelUp"),
+            Input::WheelDown => write!(f, "WheelDown"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct BindingsFile {
+    /// toml can't deserialize enum keys, the actions are parsed from their names afterwards
+    #[serde(default)]
+    bindings: HashMap<String, Vec<Input>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Bindings {
+    actions: HashMap<Input, Vec<Action>>,
+    /// keys and buttons that are held down, repeated presses of them are ignored
+    held: HashSet<Input>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self::from_actions(default_actions())
+    }
+}
+
+fn default_actions() -> HashMap<Action, Vec<Input>> {
+    use VirtualKeyCode as Key;
+
+    HashMap::from([
+        (Action::MoveForward, vec![Input::Key(Key::W)]),
+        (Action::MoveBackward, vec![Input::Key(Key::S)]),
+        (Action::MoveLeft, vec![Input::Key(Key::A)]),
+        (Action::MoveRight, vec![Input::Key(Key::D)]),
+        (Action::Jump, vec![Input::Key(Key::Space)]),
+        (Action::Sneak, vec![Input::Key(Key::LShift)]),
+        (Action::Sprint, vec![Input::Key(Key::LControl)]),
+        (Action::Slow, vec![Input::Key(Key::LAlt)]),
+        (Action::Break, vec![Input::Mouse(MouseButton::Left)]),
+        (Action::Place, vec![Input::Mouse(MouseButton::Right)]),
+        (Action::Inventory, vec![Input::Key(Key::E)]),
+        (Action::ToggleDebug, vec![Input::Key(Key::F3)]),
+        (Action::Screenshot, vec![Input::Key(Key::F2)]),
+        (Action::ToggleRecording, vec![Input::Key(Key::F9)]),
+        (Action::ReloadBindings, vec![Input::Key(Key::F5)]),
+        (Action::ToggleNoclip, vec![Input::Key(Key::N)]),
+        (Action::OpenConsole, vec![Input::Key(Key::T)]),
+        (Action::OpenCommand, vec![Input::Key(Key::Slash)]),
+        (Action::HotbarNext, vec![Input::WheelDown]),
+        (Action::HotbarPrevious, vec![Input::WheelUp]),
+        (Action::HotbarSlot1, vec![Input::Key(Key::Key1)]),
+        (Action::HotbarSlot2, vec![Input::Key(Key::Key2)]),
+        (Action::HotbarSlot3, vec![Input::Key(Key::Key3)]),
+        (Action::HotbarSlot4, vec![Input::Key(Key::Key4)]),
+        (Action::HotbarSlot5, vec![Input::Key(Key::Key5)]),
+        (Action::HotbarSlot6, vec![Input::Key(Key::Key6)]),
+        (Action::HotbarSlot7, vec![Input::Key(Key::Key7)]),
+        (Action::HotbarSlot8, vec![Input::Key(Key::Key8)]),
+        (Action::HotbarSlot9, vec![Input::Key(Key::Key9)]),
+    ])
+}
+
+impl Bindings {
+    fn from_actions(actions: HashMap<Action, Vec<Input>>) -> Self {
+        let mut by_input: HashMap<Input, Vec<Action>> = HashMap::new();
+        for (action, inputs) in actions {
+            for input in inputs {
+                by_input.entry(input).or_default().push(action);
+            }
+        }
+        Self {
+            actions: by_input,
+            held: HashSet::new(),
+        }
+    }
+
+    /// Parses a bindings file, actions that aren't mentioned keep their default bindings.
+    pub fn from_toml(source: &str) -> Result<Self, toml::de::Error> {
+        let file: BindingsFile = toml::from_str(source)?;
+        let mut actions = default_actions();
+        for (name, inputs) in file.bindings {
+            let deserializer: de::value::StrDeserializer<toml::de::Error> =
+                name.as_str().into_deserializer();
+            let action = Action::deserialize(deserializer).map_err(|_| {
+                <toml::de::Error as de::Error>::custom(format!("unknown action {}", name))
+            })?;
+            actions.insert(action, inputs);
+        }
+        Ok(Self::from_actions(actions))
+    }
+
+    /// Loads the bindings from `path`, or the defaults if the file doesn't exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            log::info!(
+                "{} doesn't exist, using the default bindings",
+                path.display()
+            );
+            return Ok(Self::default());
+        }
+        let source = fs::read_to_string(path).context("read the bindings file")?;
+        Self::from_toml(&source).context("parse the bindings file")
+    }
+
+    pub fn actions(&self, input: Input) -> &[Action] {
+        self.actions.get(&input).map_or(&[], Vec::as_slice)
+    }
+
+    /// Forgets which inputs are held, for when their release won't be seen.
+    pub fn release_all(&mut self) {
+        self.held.clear();
+    }
+
+    /// Returns the actions that the event presses (`true`) or releases (`false`).
+    ///
+    /// The wheel has no released state, so a wheel step presses and immediately releases. Keys
+    /// that are held down repeat their press events, only the first one presses the actions.
+    pub fn resolve(&mut self, event: &WindowEvent) -> Vec<(Action, bool)> {
+        let (input, pressed) = match event {
+            WindowEvent::KeyboardInput { input, .. } => match input.virtual_keycode {
+                Some(key_code) => (Input::Key(key_code), Some(state_is_pressed(input.state))),
+                None => return Vec::new(),
+            },
+            WindowEvent::MouseInput { state, button, .. } => {
+                (Input::Mouse(*button), Some(state_is_pressed(*state)))
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let y = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y as f64,
+                    MouseScrollDelta::PixelDelta(position) => position.y,
+                };
+                if y > 0.0 {
+                    (Input::WheelUp, None)
+                } else if y < 0.0 {
+                    (Input::WheelDown, None)
+                } else {
+                    return Vec::new();
+                }
+            }
+            _ => return Vec::new(),
+        };
+
+        if let Some(pressed) = pressed {
+            let changed = if pressed {
+                self.held.insert(input)
+            } else {
+                self.held.remove(&input)
+            };
+            // a release that wasn't seen pressed still releases, e.g. a key held while the
+            // console was open
+            if pressed && !changed {
+                return Vec::new();
+            }
+        }
+
+        let actions = self.actions(input);
+        match pressed {
+            Some(pressed) => actions.iter().map(|&action| (action, pressed)).collect(),
+            None => actions
+                .iter()
+                .flat_map(|&action| [(action, true), (action, false)])
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::event::{DeviceId, ElementState, KeyboardInput, TouchPhase};
+
+    fn device_id() -> DeviceId {
+        // only used to build events that are never passed to winit
+        unsafe { DeviceId::dummy() }
+    }
+
+    #[allow(deprecated)]
+    fn key(key_code: VirtualKeyCode, state: ElementState) -> WindowEvent<'static> {
+        WindowEvent::KeyboardInput {
+            device_id: device_id(),
+            input: KeyboardInput {
+                scancode: 0,
+                state,
+                virtual_keycode: Some(key_code),
+                modifiers: Default::default(),
+            },
+            is_synthetic: false,
+        }
+    }
+
+    #[allow(deprecated)]
+    fn mouse(button: MouseButton, state: ElementState) -> WindowEvent<'static> {
+        WindowEvent::MouseInput {
+            device_id: device_id(),
+            state,
+            button,
+            modifiers: Default::default(),
+        }
+    }
+
+    #[allow(deprecated)]
+    fn wheel(y: f32) -> WindowEvent<'static> {
+        WindowEvent::MouseWheel {
+            device_id: device_id(),
+            delta: MouseScrollDelta::LineDelta(0.0, y),
+            phase: TouchPhase::Moved,
+            modifiers: Default::default(),
+        }
+    }
+
+    #[test]
+    fn keys_press_and_release_their_actions() {
+        let mut bindings = Bindings::default();
+        assert_eq!(
+            bindings.resolve(&key(VirtualKeyCode::W, ElementState::Pressed)),
+            [(Action::MoveForward, true)]
+        );
+        assert_eq!(
+            bindings.resolve(&key(VirtualKeyCode::W, ElementState::Released)),
+            [(Action::MoveForward, false)]
+        );
+        assert!(bindings
+            .resolve(&key(VirtualKeyCode::F12, ElementState::Pressed))
+            .is_empty());
+    }
+
+    #[test]
+    fn mouse_buttons_press_and_release_their_actions() {
+        let mut bindings = Bindings::default();
+        assert_eq!(
+            bindings.resolve(&mouse(MouseButton::Left, ElementState::Pressed)),
+            [(Action::Break, true)]
+        );
+        assert_eq!(
+            bindings.resolve(&mouse(MouseButton::Right, ElementState::Released)),
+            [(Action::Place, false)]
+        );
+    }
+
+    #[test]
+    fn wheel_steps_press_and_release() {
+        let mut bindings = Bindings::default();
+        assert_eq!(
+            bindings.resolve(&wheel(1.0)),
+            [
+                (Action::HotbarPrevious, true),
+                (Action::HotbarPrevious, false)
+            ]
+        );
+        assert_eq!(
+            bindings.resolve(&wheel(-2.0)),
+            [(Action::HotbarNext, true), (Action::HotbarNext, false)]
+        );
+        assert!(bindings.resolve(&wheel(0.0)).is_empty());
+    }
+
+    #[test]
+    fn repeated_presses_are_ignored() {
+        let mut bindings = Bindings::default();
+        let press = key(VirtualKeyCode::F2, ElementState::Pressed);
+        assert_eq!(bindings.resolve(&press), [(Action::Screenshot, true)]);
+        assert!(bindings.resolve(&press).is_empty());
+        assert!(bindings.resolve(&press).is_empty());
+        assert_eq!(
+            bindings.resolve(&key(VirtualKeyCode::F2, ElementState::Released)),
+            [(Action::Screenshot, false)]
+        );
+        assert_eq!(bindings.resolve(&press), [(Action::Screenshot, true)]);
+
+        bindings.release_all();
+        assert_eq!(bindings.resolve(&press), [(Action::Screenshot, true)]);
+    }
+
+    #[test]
+    fn files_replace_only_the_listed_actions() {
+        let mut bindings = Bindings::from_toml(
+            r#"
+            [bindings]
+            MoveForward = ["Z", "Up"]
+            Break = ["Mouse4"]
+            Place = ["MouseMiddle", "WheelUp"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            bindings.actions(Input::Key(VirtualKeyCode::Z)),
+            [Action::MoveForward]
+        );
+        assert_eq!(
+            bindings.actions(Input::Key(VirtualKeyCode::Up)),
+            [Action::MoveForward]
+        );
+        assert_eq!(
+            bindings.actions(Input::Mouse(MouseButton::Other(4))),
+            [Action::Break]
+        );
+        assert!(bindings.actions(Input::WheelUp).contains(&Action::Place));
+        assert!(bindings
+            .actions(Input::WheelUp)
+            .contains(&Action::HotbarPrevious));
+        // the defaults of the other actions are kept
+        assert_eq!(
+            bindings.actions(Input::Key(VirtualKeyCode::Space)),
+            [Action::Jump]
+        );
+        assert_eq!(
+            bindings.resolve(&key(VirtualKeyCode::Z, ElementState::Pressed)),
+            [(Action::MoveForward, true)]
+        );
+    }
+
+    #[test]
+    fn empty_files_keep_the_defaults() {
+        let bindings = Bindings::from_toml("").unwrap();
+        assert_eq!(
+            bindings.actions(Input::Key(VirtualKeyCode::W)),
+            [Action::MoveForward]
+        );
+    }
+
+    #[test]
+    fn unknown_names_are_errors() {
+        let error = Bindings::from_toml("[bindings]\nMoveForward = [\"NotAKey\"]").unwrap_err();
+        assert!(error.to_string().contains("unknown input NotAKey"));
+        let error = Bindings::from_toml("[bindings]\nFly = [\"F\"]").unwrap_err();
+        assert!(error.to_string().contains("unknown action Fly"));
+    }
+
+    #[test]
+    fn invalid_files_fail_to_load() {
+        let path = std::env::temp_dir().join("poritz_craft_invalid_bindings.toml");
+        fs::write(&path, "[bindings]\nJump = [\"NotAKey\"]").unwrap();
+        let error = Bindings::load(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(error
+            .to_string()
+            .starts_with("Failed to parse the bindings file:"));
+    }
+
+    #[test]
+    fn inputs_are_shown_by_their_names() {
+        for name in [
+            "MouseLeft",
+            "MouseRight",
+            "MouseMiddle",
+            "Mouse7",
+            "WheelUp",
+            "F3",
+            "Key1",
+        ] {
+            assert_eq!(Input::parse(name).unwrap().to_string(), name);
+        }
+    }
+}
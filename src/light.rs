@@ -0,0 +1,339 @@
+//! Flood fill lighting for sky light and light emitted by blocks.
+//!
+//! Every air block stores two levels from 0 to [`MAX_LIGHT`]. Light loses one level per block it
+//! travels, except for full sky light which shines straight down without getting weaker.
+//! Positions in chunks that don't exist are air and count as open sky, so light enters the
+//! stored chunks from there.
+
+use std::collections::{HashSet, VecDeque};
+
+use nalgebra::Point3;
+
+use crate::world::{Face, World, CHUNK_SIZE};
+
+pub const MAX_LIGHT: u8 = 15;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LightKind {
+    Sky,
+    Block,
+}
+
+impl LightKind {
+    pub const ALL: [LightKind; 2] = [LightKind::Sky, LightKind::Block];
+}
+
+/// The level light has after spreading from a block with `level` through `face` of it.
+fn spread(kind: LightKind, level: u8, face: Face) -> u8 {
+    if kind == LightKind::Sky && face == Face::NegY && level == MAX_LIGHT {
+        MAX_LIGHT
+    } else {
+        level.saturating_sub(1)
+    }
+}
+
+/// How much light of `kind` the block at `position` gives off by itself.
+fn emission(world: &World, position: Point3<i32>, kind: LightKind) -> u8 {
+    match kind {
+        LightKind::Sky => 0,
+        LightKind::Block => world.block(position).light_emission(),
+    }
+}
+
+/// Spreads light from all `queue` positions until nothing gets brighter anymore.
+///
+/// Every changed position is added to `changed`.
+fn propagate(
+    world: &mut World,
+    kind: LightKind,
+    mut queue: VecDeque<Point3<i32>>,
+    changed: &mut HashSet<Point3<i32>>,
+) {
+    while let Some(position) = queue.pop_front() {
+        let level = world.light(position, kind);
+        if level <= 1 {
+            continue;
+        }
+        for face in Face::ALL {
+            let neighbor = position + face.normal();
+            if !world.is_loaded(neighbor) || world.block(neighbor).is_opaque() {
+                continue;
+            }
+            let new_level = spread(kind, level, face);
+            if world.light(neighbor, kind) < new_level {
+                world.set_light(neighbor, kind, new_level);
+                changed.insert(neighbor);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+}
+
+/// Computes the light of the whole world from scratch, e.g. after generating it.
+pub fn relight(world: &mut World) {
+    let chunks: Vec<_> = world.chunk_positions().collect();
+    let mut sky_queue = VecDeque::new();
+    let mut block_queue = VecDeque::new();
+    for chunk in &chunks {
+        let origin = chunk * CHUNK_SIZE;
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let position = Point3::new(origin.x + x, origin.y + y, origin.z + z);
+                    world.set_light(position, LightKind::Sky, 0);
+                    let emitted = world.block(position).light_emission();
+                    world.set_light(position, LightKind::Block, emitted);
+                    if emitted > 0 {
+                        block_queue.push_back(position);
+                    }
+                    for face in Face::ALL {
+                        let neighbor = position + face.normal();
+                        if !world.is_loaded(neighbor) {
+                            sky_queue.push_back(neighbor);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let mut changed = HashSet::new();
+    propagate(world, LightKind::Sky, sky_queue, &mut changed);
+    propagate(world, LightKind::Block, block_queue, &mut changed);
+}
+
+/// Fixes the light after the blocks at `positions` were changed.
+///
+/// Light that came through or from the old blocks is removed first, then the remaining light
+/// spreads back into the darkened area. Returns every position whose light changed so the meshes
+/// showing them can be rebuilt.
+pub fn update(world: &mut World, positions: &[Point3<i32>]) -> HashSet<Point3<i32>> {
+    let mut changed: HashSet<_> = positions.iter().copied().collect();
+    for kind in LightKind::ALL {
+        let mut removal = VecDeque::new();
+        let mut removed = Vec::new();
+        for &position in positions {
+            if !world.is_loaded(position) {
+                continue;
+            }
+            removal.push_back((position, world.light(position, kind)));
+            world.set_light(position, kind, 0);
+            removed.push(position);
+        }
+
+        // everything darker than the light it came from was lit by it, brighter neighbors are
+        // lit from somewhere else and have to spread again
+        let mut queue = VecDeque::new();
+        while let Some((position, level)) = removal.pop_front() {
+            for face in Face::ALL {
+                let neighbor = position + face.normal();
+                if !world.is_loaded(neighbor) {
+                    // open sky that never goes dark
+                    queue.push_back(neighbor);
+                    continue;
+                }
+                let neighbor_level = world.light(neighbor, kind);
+                if neighbor_level == 0 {
+                    continue;
+                }
+                if neighbor_level < level || spread(kind, level, face) == neighbor_level {
+                    world.set_light(neighbor, kind, 0);
+                    changed.insert(neighbor);
+                    removed.push(neighbor);
+                    removal.push_back((neighbor, neighbor_level));
+                } else {
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        for position in removed {
+            let emitted = emission(world, position, kind);
+            if emitted > 0 {
+                world.set_light(position, kind, emitted);
+                queue.push_back(position);
+            }
+        }
+        for &position in positions {
+            // a removed block lets the light of its neighbors in
+            if !world.block(position).is_opaque() {
+                queue.extend(Face::ALL.map(|face| position + face.normal()));
+            }
+        }
+        propagate(world, kind, queue, &mut changed);
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Block;
+
+    /// Creates the empty chunk at `chunk`.
+    fn load(world: &mut World, chunk: Point3<i32>) {
+        let origin = chunk * CHUNK_SIZE;
+        world.set_block(origin, Block::Stone);
+        world.set_block(origin, Block::Air);
+    }
+
+    fn sky(world: &World, x: i32, y: i32, z: i32) -> u8 {
+        world.light(Point3::new(x, y, z), LightKind::Sky)
+    }
+
+    fn block_light(world: &World, x: i32, y: i32, z: i32) -> u8 {
+        world.light(Point3::new(x, y, z), LightKind::Block)
+    }
+
+    /// Updates have to end up with the same light as computing it from scratch.
+    fn assert_same_as_relight(world: &mut World) {
+        let positions: Vec<_> = world
+            .chunk_positions()
+            .flat_map(|chunk| {
+                let origin = chunk * CHUNK_SIZE;
+                (0..CHUNK_SIZE.pow(3)).map(move |i| {
+                    origin
+                        + nalgebra::Vector3::new(
+                            i % CHUNK_SIZE,
+                            i / CHUNK_SIZE % CHUNK_SIZE,
+                            i / (CHUNK_SIZE * CHUNK_SIZE),
+                        )
+                })
+            })
+            .collect();
+        let light = |world: &World| -> Vec<_> {
+            positions
+                .iter()
+                .map(|&p| {
+                    (
+                        world.light(p, LightKind::Sky),
+                        world.light(p, LightKind::Block),
+                    )
+                })
+                .collect()
+        };
+        let updated = light(world);
+        relight(world);
+        assert!(
+            updated == light(world),
+            "updated light differs from relight"
+        );
+    }
+
+    #[test]
+    fn block_light_falls_off_with_distance() {
+        let mut world = World::new();
+        world.set_block(Point3::new(8, 8, 8), Block::Glowstone);
+        relight(&mut world);
+        assert_eq!(block_light(&world, 8, 8, 8), MAX_LIGHT);
+        assert_eq!(block_light(&world, 9, 8, 8), 14);
+        assert_eq!(block_light(&world, 8, 3, 8), 10);
+        assert_eq!(block_light(&world, 8, 8, 0), 7);
+        // light goes around corners, so it falls off with the manhattan distance
+        assert_eq!(block_light(&world, 10, 10, 6), 9);
+    }
+
+    #[test]
+    fn removing_a_light_source_darkens_its_surroundings() {
+        let mut world = World::new();
+        let glowstone = Point3::new(8, 8, 8);
+        world.set_block(glowstone, Block::Glowstone);
+        relight(&mut world);
+        world.set_block(glowstone, Block::Air);
+        let changed = update(&mut world, &[glowstone]);
+        for (x, y, z) in [(8, 8, 8), (9, 8, 8), (8, 2, 8), (15, 8, 8)] {
+            assert_eq!(block_light(&world, x, y, z), 0, "at {} {} {}", x, y, z);
+            assert!(changed.contains(&Point3::new(x, y, z)));
+        }
+        // sky light doesn't care about glowstone
+        assert_eq!(sky(&world, 8, 8, 8), MAX_LIGHT);
+        assert_same_as_relight(&mut world);
+    }
+
+    #[test]
+    fn overhangs_cast_a_shadow() {
+        let mut world = World::new();
+        for x in 4..=12 {
+            for z in 4..=12 {
+                world.set_block(Point3::new(x, 10, z), Block::Stone);
+            }
+        }
+        relight(&mut world);
+        assert_eq!(sky(&world, 8, 11, 8), MAX_LIGHT);
+        // the open columns next to the roof are fully lit all the way down
+        assert_eq!(sky(&world, 3, 9, 8), MAX_LIGHT);
+        assert_eq!(sky(&world, 3, 0, 8), MAX_LIGHT);
+        // below it the light comes in from the sides
+        assert_eq!(sky(&world, 4, 9, 8), 14);
+        assert_eq!(sky(&world, 8, 9, 8), 10);
+        assert_eq!(sky(&world, 8, 5, 8), 10);
+        // the open sky below the chunk lights the bottom
+        assert_eq!(sky(&world, 8, 2, 8), 12);
+        assert_eq!(sky(&world, 8, 10, 8), 0);
+    }
+
+    #[test]
+    fn sky_light_shines_straight_down_a_shaft() {
+        let mut world = World::new();
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    if (x, z) != (8, 8) || y == 0 {
+                        world.set_block(Point3::new(x, y, z), Block::Stone);
+                    }
+                }
+            }
+        }
+        // a tunnel going sideways from the bottom of the shaft
+        for x in 9..12 {
+            world.set_block(Point3::new(x, 1, 8), Block::Air);
+        }
+        relight(&mut world);
+        for y in 1..CHUNK_SIZE {
+            assert_eq!(sky(&world, 8, y, 8), MAX_LIGHT, "at height {}", y);
+        }
+        assert_eq!(sky(&world, 9, 1, 8), 14);
+        assert_eq!(sky(&world, 11, 1, 8), 12);
+
+        // closing the shaft darkens everything below
+        let lid = Point3::new(8, 12, 8);
+        world.set_block(lid, Block::Stone);
+        update(&mut world, &[lid]);
+        assert_eq!(sky(&world, 8, 13, 8), MAX_LIGHT);
+        assert_eq!(sky(&world, 8, 11, 8), 0);
+        assert_eq!(sky(&world, 8, 1, 8), 0);
+        assert_eq!(sky(&world, 11, 1, 8), 0);
+        assert_same_as_relight(&mut world);
+
+        world.set_block(lid, Block::Air);
+        update(&mut world, &[lid]);
+        assert_eq!(sky(&world, 8, 1, 8), MAX_LIGHT);
+        assert_eq!(sky(&world, 11, 1, 8), 12);
+        assert_same_as_relight(&mut world);
+    }
+
+    #[test]
+    fn opaque_blocks_on_chunk_borders_block_light() {
+        let mut world = World::new();
+        load(&mut world, Point3::new(1, 0, 0));
+        world.set_block(Point3::new(14, 8, 8), Block::Glowstone);
+        relight(&mut world);
+        assert_eq!(block_light(&world, 17, 8, 8), 12);
+
+        // the wall is the first block of the next chunk, light has to go around it
+        let wall = Point3::new(16, 8, 8);
+        world.set_block(wall, Block::Stone);
+        let changed = update(&mut world, &[wall]);
+        assert_eq!(block_light(&world, 16, 8, 8), 0);
+        assert_eq!(block_light(&world, 15, 8, 8), 14);
+        assert_eq!(block_light(&world, 17, 8, 8), 10);
+        assert!(changed.contains(&Point3::new(17, 8, 8)));
+        assert_same_as_relight(&mut world);
+
+        world.set_block(wall, Block::Air);
+        let changed = update(&mut world, &[wall]);
+        assert_eq!(block_light(&world, 16, 8, 8), 13);
+        assert_eq!(block_light(&world, 17, 8, 8), 12);
+        assert!(changed.contains(&Point3::new(17, 8, 8)));
+        assert_same_as_relight(&mut world);
+    }
+}
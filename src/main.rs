@@ -6,13 +6,66 @@
 // at your option. All files in the project carrying such
 // notice may not be copied, modified, or distributed except
 // according to those terms.
+pub mod animation;
+pub mod biome;
+pub mod camera;
+pub mod clock;
+pub mod command;
+pub mod config;
+pub mod console;
+pub mod debug;
+pub mod error;
+pub mod fluid;
+pub mod fog;
+pub mod gamepad;
+pub mod hotbar;
+pub mod input;
+pub mod light;
 pub mod main_pipeline;
+pub mod mesh;
+pub mod model;
+pub mod overlay;
+pub mod physics;
+pub mod raycast;
+pub mod recording;
 pub mod renderer;
+pub mod screenshot;
+pub mod shadow;
+pub mod sky;
+pub mod text;
 pub mod utils;
 pub mod window;
+pub mod world;
 
-use crate::window::PoritzCraftWindow;
+use crate::{
+    config::{ArgsError, Config, USAGE},
+    renderer::list_devices,
+    window::PoritzCraftWindow,
+};
 
 pub fn main() {
-    PoritzCraftWindow::new().run();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let config = match Config::from_args() {
+        Ok(config) => config,
+        Err(ArgsError::Help) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(ArgsError::Invalid(message)) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    };
+
+    let result = if config.list_devices {
+        list_devices(&config)
+    } else {
+        PoritzCraftWindow::new(config).run()
+    };
+
+    if let Err(e) = result {
+        log::error!("{}", e);
+        std::process::exit(1);
+    }
 }
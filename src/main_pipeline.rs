@@ -1,14 +1,21 @@
-use std::{io::Cursor, sync::Arc, time::Instant};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::Instant,
+};
 
 use nalgebra::{
     Affine3, Isometry3, IsometryMatrix3, Matrix4, Point3, Quaternion, Rotation3, Translation,
-    Translation3, UnitQuaternion, Vector3,
+    UnitQuaternion, Vector3,
 };
-use rand::Rng;
 use vulkano::{
-    buffer::{BufferUsage, CpuBufferPool, ImmutableBuffer, TypedBufferAccess},
+    buffer::{
+        cpu_pool::CpuBufferPoolChunk, BufferUsage, CpuAccessibleBuffer, CpuBufferPool,
+        DeviceLocalBuffer, TypedBufferAccess,
+    },
     command_buffer::{
-        AutoCommandBufferBuilder, CommandBufferUsage, RenderPassBeginInfo, SubpassContents,
+        AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo, CopyImageToBufferInfo,
+        PrimaryAutoCommandBuffer, RenderPassBeginInfo, SubpassContents,
     },
     descriptor_set::{
         layout::{
@@ -19,14 +26,15 @@ use vulkano::{
     device::{Device, Queue},
     format::Format,
     image::{
-        view::ImageView, AttachmentImage, ImageAccess, ImageDimensions, ImageViewAbstract,
-        ImmutableImage, MipmapsCount, SwapchainImage,
+        view::ImageView, AttachmentImage, ImageAccess, ImageDimensions, ImageUsage,
+        ImageViewAbstract, ImmutableImage, MipmapsCount, SwapchainImage,
     },
     pipeline::{
         graphics::{
+            color_blend::ColorBlendState,
             depth_stencil::DepthStencilState,
-            input_assembly::InputAssemblyState,
-            rasterization::{CullMode, FrontFace, RasterizationState},
+            input_assembly::{InputAssemblyState, PrimitiveTopology},
+            rasterization::{CullMode, DepthBias, DepthBiasState, FrontFace, RasterizationState},
             vertex_input::BuffersDefinition,
             viewport::{Viewport, ViewportState},
         },
@@ -34,41 +42,200 @@ use vulkano::{
         GraphicsPipeline, Pipeline, PipelineBindPoint, PipelineLayout, StateMode,
     },
     render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass},
-    sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo},
+    sampler::{BorderColor, Filter, Sampler, SamplerAddressMode, SamplerCreateInfo},
     shader::ShaderModule,
     swapchain::{
         acquire_next_image, AcquireError, Surface, Swapchain, SwapchainCreateInfo,
         SwapchainCreationError,
     },
     sync::{self, FlushError, GpuFuture},
+    DeviceSize,
 };
 use winit::window::Window;
 
-use crate::utils::{repeat_element, InstanceData, Normal, TexCoord, Vertex, SIZE};
+use crate::{
+    animation::TextureAnimation,
+    biome::{BiomeColors, Colormap},
+    camera::Camera,
+    debug::set_object_name,
+    error::{Context, PoritzCraftError, Result},
+    fluid::Fluid,
+    fog::{Fog, FogSettings},
+    hotbar::Hotbar,
+    mesh::{build_chunk_mesh, outline_vertices},
+    overlay::{self, OverlayBuilder},
+    physics::Aabb,
+    recording::Frame,
+    screenshot::{self, PendingReadback},
+    shadow::{self, Cascade, Frustum, ShadowSettings, MAX_CASCADES, SHADOW_DISTANCE},
+    sky::Sky,
+    utils::{decode_png_rgba8, ChunkVertex, OutlineVertex, OverlayVertex},
+    world::{
+        block_render_position, chunk_position, render_direction_to_world, render_to_world,
+        world_to_render, World, BLOCK_SIZE, BLOCK_TEXTURES, CHUNK_SIZE,
+    },
+};
+
+/// Length of the texture array, block textures are indexed by [`Block::texture`](crate::world::Block::texture),
+/// the font atlas comes last at [`FONT_TEXTURE`](crate::text::FONT_TEXTURE).
+const TEXTURE_COUNT: u32 = BLOCK_TEXTURES.len() as u32 + 1;
+/// The PNG of every block texture and the contents of its `.png.mcmeta` file if it is animated,
+/// in the order of [`BLOCK_TEXTURES`].
+const BLOCK_TEXTURE_FILES: [(&str, &[u8], Option<&str>); BLOCK_TEXTURES.len()] = [
+    ("block/dirt", include_bytes!("block/dirt.png"), None),
+    ("block/stone", include_bytes!("block/stone.png"), None),
+    (
+        "block/glowstone",
+        include_bytes!("block/glowstone.png"),
+        None,
+    ),
+    (
+        "block/oak_leaves",
+        include_bytes!("block/oak_leaves.png"),
+        None,
+    ),
+    ("block/glass", include_bytes!("block/glass.png"), None),
+    (
+        "block/light_blue_stained_glass",
+        include_bytes!("block/light_blue_stained_glass.png"),
+        None,
+    ),
+    (
+        "block/water_still",
+        include_bytes!("block/water_still.png"),
+        Some(include_str!("block/water_still.png.mcmeta")),
+    ),
+    (
+        "block/lava_still",
+        include_bytes!("block/lava_still.png"),
+        Some(include_str!("block/lava_still.png.mcmeta")),
+    ),
+    (
+        "block/grass_block_top",
+        include_bytes!("block/grass_block_top.png"),
+        None,
+    ),
+    (
+        "block/grass_block_side",
+        include_bytes!("block/grass_block_side.png"),
+        None,
+    ),
+    ("block/poppy", include_bytes!("block/poppy.png"), None),
+    ("block/grass", include_bytes!("block/grass.png"), None),
+];
+/// Length of the animation array in the fragment shader.
+const MAX_TEXTURES: usize = 16;
+const _: () = assert!(TEXTURE_COUNT as usize <= MAX_TEXTURES);
+
+/// Vertical field of view in radians.
+const FOV: f32 = 70.0 * std::f32::consts::PI / 180.0; // this value is exciting
+const NEAR: f32 = 0.1;
+
+/// Cutout pixels with less alpha than this are discarded.
+const CUTOUT_ALPHA: f32 = 0.5;
+
+/// In chunks, can be changed with `/render_distance`.
+pub const DEFAULT_RENDER_DISTANCE: u32 = 12;
+
+type VertexBuffer = Arc<DeviceLocalBuffer<[ChunkVertex]>>;
+
+/// A chunk mesh waiting to be copied to the GPU at the start of the next frame.
+struct PendingUpload {
+    source: Arc<CpuBufferPoolChunk<ChunkVertex>>,
+    destination: VertexBuffer,
+}
+
+/// The uploaded [`ChunkMesh`], empty parts have no buffer because empty buffers aren't allowed.
+struct ChunkBuffers {
+    opaque: Option<VertexBuffer>,
+    translucent: Option<VertexBuffer>,
+    water: Option<VertexBuffer>,
+}
 
 pub struct MainPipeline {
-    vertex_buffer: Arc<ImmutableBuffer<[Vertex]>>,
-    normals_buffer: Arc<ImmutableBuffer<[Normal]>>,
-    texture_coordinate_buffer: Arc<ImmutableBuffer<[TexCoord]>>,
-    instance_buffer: Arc<ImmutableBuffer<[InstanceData]>>,
+    /// chunks without any visible faces have no entry
+    chunk_meshes: HashMap<Point3<i32>, ChunkBuffers>,
+    /// staging memory for chunk meshes, reused once the copies out of it are done
+    upload_pool: CpuBufferPool<ChunkVertex>,
+    pending_uploads: Vec<PendingUpload>,
     pipeline: Arc<GraphicsPipeline>,
+    /// blends translucent blocks over the rest, shares the layout with `pipeline`
+    translucent_pipeline: Arc<GraphicsPipeline>,
+    /// like `translucent_pipeline` with the animated water shader
+    water_pipeline: Arc<GraphicsPipeline>,
+    outline_pipeline: Arc<GraphicsPipeline>,
+    outline_vs: Arc<ShaderModule>,
+    outline_fs: Arc<ShaderModule>,
+    outline_buffer_pool: CpuBufferPool<OutlineVertex>,
+    overlay_pipeline: Arc<GraphicsPipeline>,
+    overlay_vs: Arc<ShaderModule>,
+    overlay_fs: Arc<ShaderModule>,
+    overlay_buffer_pool: CpuBufferPool<OverlayVertex>,
+    sky_pipeline: Arc<GraphicsPipeline>,
+    sky_vs: Arc<ShaderModule>,
+    sky_fs: Arc<ShaderModule>,
+    shadow_pipeline: Arc<GraphicsPipeline>,
+    shadow_framebuffer: Arc<Framebuffer>,
+    /// all cascades side by side, sampled by the main pipeline
+    shadow_map: Arc<ImageView<AttachmentImage>>,
+    shadow_sampler: Arc<Sampler>,
+    shadow_buffer: CpuBufferPool<fs::ty::Shadows>,
+    shadow_settings: ShadowSettings,
     device: Arc<Device>,
     vs: Arc<ShaderModule>,
     fs: Arc<ShaderModule>,
+    water_fs: Arc<ShaderModule>,
     render_pass: Arc<RenderPass>,
     sampler: Arc<Sampler>,
     textures: Vec<(Arc<dyn ImageViewAbstract>, Arc<Sampler>)>,
+    /// how each of `textures` is animated
+    animations: Vec<TextureAnimation>,
+    animation_buffer: CpuBufferPool<fs::ty::Animations>,
+    /// grass, leaves and water are tinted with these when their meshes are built
+    biome_colors: BiomeColors,
     framebuffers: Vec<Arc<Framebuffer>>,
     uniform_buffer: CpuBufferPool<vs::ty::Data>,
     previous_frame_end: Option<Box<dyn GpuFuture>>,
     pub recreate_swapchain: bool,
     swapchain: Arc<Swapchain<Window>>,
+    images: Vec<Arc<SwapchainImage<Window>>>,
     surface: Arc<Surface<Window>>,
     queue: Arc<Queue>,
+    pending_screenshots: Vec<PendingReadback>,
+    /// readback buffers of the size of the swapchain images, reused once nothing else holds them
+    readback_buffers: Vec<Arc<CpuAccessibleBuffer<[u8]>>>,
+    /// recorded frames that are still being copied, oldest first
+    pending_frames: VecDeque<PendingReadback>,
+    /// how many draw commands the last frame recorded
+    pub draw_calls: u32,
+}
 
-    pub view_rotation_pitch: f64,
-    pub view_rotation_yaw: f64,
-    pub view_translation: Translation3<f32>,
+/// What a frame shows, built by the game for every call to [`MainPipeline::render`].
+///
+/// The pipeline doesn't keep any of it, so it can be thrown away and recreated at any time.
+pub struct FrameView<'a> {
+    pub camera: &'a Camera,
+    pub hotbar: &'a Hotbar,
+    /// boxes in world coordinates that get a wireframe drawn around them, the shape of the targeted
+    /// block
+    pub outline: Vec<Aabb>,
+    /// shown in the top left corner if set
+    pub debug_lines: Option<Vec<String>>,
+    /// shown above the hotbar while the console is open
+    pub console_lines: Option<Vec<String>>,
+    /// chunks further away from the camera than this aren't drawn
+    pub render_distance: u32,
+    /// the sky and the sun light on the terrain
+    pub sky: Sky,
+    pub fog: FogSettings,
+    /// the fluid the camera is inside of, it replaces the sky with dense fog
+    pub camera_fluid: Option<Fluid>,
+    /// seconds that animate the water surface and the block textures
+    pub animation_time: f32,
+    /// saves the frame as a screenshot
+    pub screenshot: bool,
+    /// reads the frame back for [`MainPipeline::finished_frames`]
+    pub record: bool,
 }
 
 impl MainPipeline {
@@ -78,362 +245,17 @@ impl MainPipeline {
         surface: Arc<Surface<Window>>,
         queue: Arc<Queue>,
         images: Vec<Arc<SwapchainImage<Window>>>,
-    ) -> Self {
-        // https://www.saschawillems.de/blog/2019/03/29/flipping-the-vulkan-viewport/
-        // https://matthewwellings.com/blog/the-new-vulkan-coordinate-system/
-        // x right
-        // y down
-        // z back
-
-        // counter clockwise around the whole face (for back-face culling)
-
-        const N_TOP: Normal = Normal {
-            normal: [0.0, -SIZE, 0.0],
-        };
-        const N_BOTTOM: Normal = Normal {
-            normal: [0.0, SIZE, 0.0],
-        };
-        const N_LEFT: Normal = Normal {
-            normal: [-SIZE, 0.0, 0.0],
-        };
-        const N_RIGHT: Normal = Normal {
-            normal: [SIZE, 0.0, 0.0],
-        };
-        const N_FRONT: Normal = Normal {
-            normal: [0.0, 0.0, -SIZE],
-        };
-        const N_BACK: Normal = Normal {
-            normal: [0.0, 0.0, SIZE],
-        };
-
-        let texs = vec![
-            TexCoord {
-                tex_coord: [0.0, 0.0],
-            },
-            TexCoord {
-                tex_coord: [0.0, 1.0],
-            },
-            TexCoord {
-                tex_coord: [1.0, 1.0],
-            },
-            TexCoord {
-                tex_coord: [1.0, 1.0],
-            },
-            TexCoord {
-                tex_coord: [1.0, 0.0],
-            },
-            TexCoord {
-                tex_coord: [0.0, 0.0],
-            },
-        ];
-
-        let vertices: Vec<(Vertex, Normal, TexCoord)> = vec![
-            (
-                Vertex {
-                    position: [-SIZE, SIZE, -SIZE],
-                },
-                N_FRONT,
-                texs[0],
-            ),
-            (
-                Vertex {
-                    position: [-SIZE, -SIZE, -SIZE],
-                },
-                N_FRONT,
-                texs[1],
-            ),
-            (
-                Vertex {
-                    position: [SIZE, -SIZE, -SIZE],
-                },
-                N_FRONT,
-                texs[2],
-            ),
-            (
-                Vertex {
-                    position: [SIZE, -SIZE, -SIZE],
-                },
-                N_FRONT,
-                texs[3],
-            ),
-            (
-                Vertex {
-                    position: [SIZE, SIZE, -SIZE],
-                },
-                N_FRONT,
-                texs[4],
-            ),
-            (
-                Vertex {
-                    position: [-SIZE, SIZE, -SIZE],
-                },
-                N_FRONT,
-                texs[5],
-            ),
-            (
-                Vertex {
-                    position: [SIZE, SIZE, SIZE],
-                },
-                N_BACK,
-                texs[0],
-            ),
-            (
-                Vertex {
-                    position: [SIZE, -SIZE, SIZE],
-                },
-                N_BACK,
-                texs[1],
-            ),
-            (
-                Vertex {
-                    position: [-SIZE, -SIZE, SIZE],
-                },
-                N_BACK,
-                texs[2],
-            ),
-            (
-                Vertex {
-                    position: [-SIZE, -SIZE, SIZE],
-                },
-                N_BACK,
-                texs[3],
-            ),
-            (
-                Vertex {
-                    position: [-SIZE, SIZE, SIZE],
-                },
-                N_BACK,
-                texs[4],
-            ),
-            (
-                Vertex {
-                    position: [SIZE, SIZE, SIZE],
-                },
-                N_BACK,
-                texs[5],
-            ),
-            (
-                Vertex {
-                    position: [SIZE, SIZE, -SIZE],
-                },
-                N_RIGHT,
-                texs[0],
-            ),
-            (
-                Vertex {
-                    position: [SIZE, -SIZE, -SIZE],
-                },
-                N_RIGHT,
-                texs[1],
-            ),
-            (
-                Vertex {
-                    position: [SIZE, -SIZE, SIZE],
-                },
-                N_RIGHT,
-                texs[2],
-            ),
-            (
-                Vertex {
-                    position: [SIZE, -SIZE, SIZE],
-                },
-                N_RIGHT,
-                texs[3],
-            ),
-            (
-                Vertex {
-                    position: [SIZE, SIZE, SIZE],
-                },
-                N_RIGHT,
-                texs[4],
-            ),
-            (
-                Vertex {
-                    position: [SIZE, SIZE, -SIZE],
-                },
-                N_RIGHT,
-                texs[5],
-            ),
-            (
-                Vertex {
-                    position: [-SIZE, SIZE, SIZE],
-                },
-                N_LEFT,
-                texs[0],
-            ),
-            (
-                Vertex {
-                    position: [-SIZE, -SIZE, SIZE],
-                },
-                N_LEFT,
-                texs[1],
-            ),
-            (
-                Vertex {
-                    position: [-SIZE, -SIZE, -SIZE],
-                },
-                N_LEFT,
-                texs[2],
-            ),
-            (
-                Vertex {
-                    position: [-SIZE, -SIZE, -SIZE],
-                },
-                N_LEFT,
-                texs[3],
-            ),
-            (
-                Vertex {
-                    position: [-SIZE, SIZE, -SIZE],
-                },
-                N_LEFT,
-                texs[4],
-            ),
-            (
-                Vertex {
-                    position: [-SIZE, SIZE, SIZE],
-                },
-                N_LEFT,
-                texs[5],
-            ),
-            (
-                Vertex {
-                    position: [-SIZE, SIZE, SIZE],
-                },
-                N_TOP,
-                texs[0],
-            ),
-            (
-                Vertex {
-                    position: [-SIZE, SIZE, -SIZE],
-                },
-                N_TOP,
-                texs[1],
-            ),
-            (
-                Vertex {
-                    position: [SIZE, SIZE, -SIZE],
-                },
-                N_TOP,
-                texs[2],
-            ),
-            (
-                Vertex {
-                    position: [SIZE, SIZE, -SIZE],
-                },
-                N_TOP,
-                texs[3],
-            ),
-            (
-                Vertex {
-                    position: [SIZE, SIZE, SIZE],
-                },
-                N_TOP,
-                texs[4],
-            ),
-            (
-                Vertex {
-                    position: [-SIZE, SIZE, SIZE],
-                },
-                N_TOP,
-                texs[5],
-            ),
-            (
-                Vertex {
-                    position: [-SIZE, -SIZE, -SIZE],
-                },
-                N_BOTTOM,
-                texs[0],
-            ),
-            (
-                Vertex {
-                    position: [-SIZE, -SIZE, SIZE],
-                },
-                N_BOTTOM,
-                texs[1],
-            ),
-            (
-                Vertex {
-                    position: [SIZE, -SIZE, SIZE],
-                },
-                N_BOTTOM,
-                texs[2],
-            ),
-            (
-                Vertex {
-                    position: [SIZE, -SIZE, SIZE],
-                },
-                N_BOTTOM,
-                texs[3],
-            ),
-            (
-                Vertex {
-                    position: [SIZE, -SIZE, -SIZE],
-                },
-                N_BOTTOM,
-                texs[4],
-            ),
-            (
-                Vertex {
-                    position: [-SIZE, -SIZE, -SIZE],
-                },
-                N_BOTTOM,
-                texs[5],
-            ),
-        ];
-
-        let (vertex_buffer, vertex_buffer_future) = ImmutableBuffer::from_iter(
-            vertices.iter().map(|e| e.0),
-            BufferUsage::all(),
-            queue.clone(),
-        )
-        .unwrap();
-        let (normals_buffer, normals_buffer_future) = ImmutableBuffer::from_iter(
-            vertices.iter().map(|e| e.1),
-            BufferUsage::all(),
-            queue.clone(),
-        )
-        .unwrap();
-        let (texture_coordinate_buffer, texture_coordinate_buffer_future) =
-            ImmutableBuffer::from_iter(
-                vertices.iter().map(|e| e.2),
-                BufferUsage::all(),
-                queue.clone(),
-            )
-            .unwrap();
-
-        let mut rng = rand::thread_rng();
-
-        // https://www.khronos.org/registry/vulkan/specs/1.3-extensions/man/html/vkCmdCopyBuffer.html
-
-        let instances = {
-            let mut data = Vec::new();
-            for x in 0..100 {
-                for y in 0..1 {
-                    for z in 0..100 {
-                        /*data.push(InstanceData {
-                            position_offset: [
-                                rng.gen_range(0..1000) as f32 * 20.0,
-                                rng.gen_range(0..100) as f32 * 20.0,
-                                rng.gen_range(0..1000) as f32 * 20.0,
-                            ],
-                        });*/
-                        data.push(InstanceData {
-                            position_offset: [x as f32 * 20.0, y as f32 * 20.0, z as f32 * 20.0],
-                            block_type: rng.gen_range(0..=1),
-                        });
-                    }
-                }
-            }
-            data
-        };
-        let (instance_buffer, instance_buffer_future) =
-            ImmutableBuffer::from_iter(instances, BufferUsage::all(), queue.clone()).unwrap();
-
+        world: &World,
+        shadow_settings: ShadowSettings,
+    ) -> Result<Self> {
+        // all cascades are side by side in one image
+        let shadow_settings =
+            shadow_settings.fit_width(device.physical_device().properties().max_image_dimension2d);
         let uniform_buffer = CpuBufferPool::<vs::ty::Data>::new(device.clone(), BufferUsage::all());
 
-        let vs = vs::load(device.clone()).unwrap();
-        let fs = fs::load(device.clone()).unwrap();
+        let vs = vs::load(device.clone()).context("load the vertex shader")?;
+        let fs = fs::load(device.clone()).context("load the fragment shader")?;
+        let water_fs = water_fs::load(device.clone()).context("load the water fragment shader")?;
 
         let render_pass = vulkano::single_pass_renderpass!(device.clone(),
             attachments: {
@@ -455,62 +277,30 @@ impl MainPipeline {
                 depth_stencil: {depth}
             }
         )
-        .unwrap();
-
-        let dirt_texture = {
-            let png_bytes = include_bytes!("block/dirt.png").to_vec();
-            let cursor = Cursor::new(png_bytes);
-            let decoder = png::Decoder::new(cursor);
-            let mut reader = decoder.read_info().unwrap();
-            let info = reader.info();
-            let dimensions = ImageDimensions::Dim2d {
-                width: info.width,
-                height: info.height,
-                array_layers: 1,
-            };
-            let mut image_data = Vec::new();
-            image_data.resize((info.width * info.height * 4) as usize, 0);
-            let output = reader.next_frame(&mut image_data).unwrap();
-
-            let image = ImmutableImage::from_iter(
-                image_data,
-                dimensions,
-                MipmapsCount::One,
-                Format::R8G8B8A8_SRGB,
-                queue.clone(),
-            )
-            .unwrap()
-            .0;
+        .context("create the render pass")?;
+        set_object_name(&device, &*render_pass, "main render pass");
 
-            ImageView::new_default(image).unwrap()
-        };
-
-        let stone_texture = {
-            let png_bytes = include_bytes!("block/stone.png").to_vec();
-            let cursor = Cursor::new(png_bytes);
-            let decoder = png::Decoder::new(cursor);
-            let mut reader = decoder.read_info().unwrap();
-            let info = reader.info();
-            let dimensions = ImageDimensions::Dim2d {
-                width: info.width,
-                height: info.height,
-                array_layers: 1,
-            };
-            let mut image_data = Vec::new();
-            image_data.resize((info.width * info.height * 4) as usize, 0);
-            let output = reader.next_frame(&mut image_data).unwrap();
-
-            let image = ImmutableImage::from_iter(
-                image_data,
-                dimensions,
-                MipmapsCount::One,
-                Format::R8G8B8A8_SRGB,
-                queue.clone(),
-            )
-            .unwrap()
-            .0;
-
-            ImageView::new_default(image).unwrap()
+        // the block textures with their animations, the font atlas isn't animated
+        let mut textures = BLOCK_TEXTURE_FILES
+            .iter()
+            .zip(BLOCK_TEXTURES)
+            .map(|(&(name, png, mcmeta), expected)| {
+                assert_eq!(name, expected, "the block textures are out of order");
+                let texture = load_texture(png, queue.clone())?;
+                let animation = match mcmeta {
+                    Some(mcmeta) => texture_animation(&texture, mcmeta)?,
+                    None => TextureAnimation::still(),
+                };
+                Ok((texture, animation))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        textures.push((
+            load_texture(include_bytes!("font/ascii.png"), queue.clone())?,
+            TextureAnimation::still(),
+        ));
+        let biome_colors = BiomeColors {
+            grass: Colormap::from_png(include_bytes!("colormap/grass.png"))?,
+            foliage: Colormap::from_png(include_bytes!("colormap/foliage.png"))?,
         };
 
         // https://docs.rs/vulkano/latest/vulkano/sampler/struct.SamplerCreateInfo.html
@@ -527,54 +317,268 @@ impl MainPipeline {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .context("create the sampler")?;
+        let (textures, animations): (Vec<_>, Vec<_>) = textures
+            .into_iter()
+            .map(|(texture, animation)| {
+                (
+                    (texture as Arc<dyn ImageViewAbstract>, sampler.clone()),
+                    animation,
+                )
+            })
+            .unzip();
+
+        let outline_vs =
+            outline_vs::load(device.clone()).context("load the outline vertex shader")?;
+        let outline_fs =
+            outline_fs::load(device.clone()).context("load the outline fragment shader")?;
+
+        let overlay_vs =
+            overlay_vs::load(device.clone()).context("load the overlay vertex shader")?;
+        let overlay_fs =
+            overlay_fs::load(device.clone()).context("load the overlay fragment shader")?;
 
-        let (pipeline, framebuffers) =
-            window_size_dependent_setup(device.clone(), &vs, &fs, &images, render_pass.clone());
+        let shadow_render_pass = vulkano::single_pass_renderpass!(device.clone(),
+            attachments: {
+                depth: {
+                    load: Clear,
+                    store: Store,
+                    format: Format::D32_SFLOAT,
+                    samples: 1,
+                }
+            },
+            pass: {
+                color: [],
+                depth_stencil: {depth}
+            }
+        )
+        .context("create the shadow render pass")?;
+        set_object_name(&device, &*shadow_render_pass, "shadow render pass");
+
+        // even without shadows the main pipeline needs something to sample
+        let shadow_map = ImageView::new_default(
+            AttachmentImage::with_usage(
+                device.clone(),
+                [
+                    shadow_settings.resolution * shadow_settings.cascades.max(1),
+                    shadow_settings.resolution,
+                ],
+                Format::D32_SFLOAT,
+                ImageUsage {
+                    depth_stencil_attachment: true,
+                    sampled: true,
+                    ..ImageUsage::none()
+                },
+            )
+            .context("create the shadow map")?,
+        )
+        .context("create the shadow map view")?;
+        let shadow_framebuffer = Framebuffer::new(
+            shadow_render_pass.clone(),
+            FramebufferCreateInfo {
+                attachments: vec![shadow_map.clone()],
+                ..Default::default()
+            },
+        )
+        .context("create the shadow framebuffer")?;
+        // outside of the cascades everything is lit
+        let shadow_sampler = Sampler::new(
+            device.clone(),
+            SamplerCreateInfo {
+                mag_filter: Filter::Nearest,
+                min_filter: Filter::Nearest,
+                address_mode: [SamplerAddressMode::ClampToBorder; 3],
+                border_color: BorderColor::FloatOpaqueWhite,
+                ..Default::default()
+            },
+        )
+        .context("create the shadow sampler")?;
+        let shadow_vs = shadow_vs::load(device.clone()).context("load the shadow vertex shader")?;
+        let shadow_fs =
+            shadow_fs::load(device.clone()).context("load the shadow fragment shader")?;
+        let shadow_pipeline =
+            create_shadow_pipeline(device.clone(), &shadow_vs, &shadow_fs, shadow_render_pass)?;
 
-        Self {
-            normals_buffer,
-            texture_coordinate_buffer,
-            vertex_buffer,
-            instance_buffer,
+        let sky_vs = sky_vs::load(device.clone()).context("load the sky vertex shader")?;
+        let sky_fs = sky_fs::load(device.clone()).context("load the sky fragment shader")?;
+
+        let (pipeline, translucent_pipeline, water_pipeline, framebuffers) =
+            window_size_dependent_setup(
+                device.clone(),
+                &vs,
+                &fs,
+                &water_fs,
+                &images,
+                render_pass.clone(),
+            )?;
+        let outline_pipeline = create_outline_pipeline(
+            device.clone(),
+            &outline_vs,
+            &outline_fs,
+            &images,
+            render_pass.clone(),
+        )?;
+        let overlay_pipeline = create_overlay_pipeline(
+            device.clone(),
+            &overlay_vs,
+            &overlay_fs,
+            &images,
+            render_pass.clone(),
+        )?;
+        let sky_pipeline = create_sky_pipeline(
+            device.clone(),
+            &sky_vs,
+            &sky_fs,
+            &images,
+            render_pass.clone(),
+        )?;
+
+        let mut main_pipeline = Self {
+            chunk_meshes: HashMap::new(),
+            upload_pool: CpuBufferPool::upload(device.clone()),
+            pending_uploads: Vec::new(),
             pipeline,
+            translucent_pipeline,
+            water_pipeline,
+            outline_pipeline,
+            outline_vs,
+            outline_fs,
+            outline_buffer_pool: CpuBufferPool::vertex_buffer(device.clone()),
+            overlay_pipeline,
+            overlay_vs,
+            overlay_fs,
+            overlay_buffer_pool: CpuBufferPool::vertex_buffer(device.clone()),
+            sky_pipeline,
+            sky_vs,
+            sky_fs,
+            shadow_pipeline,
+            shadow_framebuffer,
+            shadow_map,
+            shadow_sampler,
+            shadow_buffer: CpuBufferPool::new(device.clone(), BufferUsage::uniform_buffer()),
+            shadow_settings,
             uniform_buffer,
-            textures: vec![
-                (dirt_texture.clone() as _, sampler.clone()),
-                (stone_texture.clone() as _, sampler.clone()),
-            ],
+            textures,
+            animations,
+            biome_colors,
+            animation_buffer: CpuBufferPool::new(device.clone(), BufferUsage::uniform_buffer()),
             sampler,
             framebuffers,
             fs,
+            water_fs,
             vs,
             render_pass,
+            previous_frame_end: Some(sync::now(device.clone()).boxed()),
             device,
-            previous_frame_end: Some(
-                vertex_buffer_future
-                    .join(normals_buffer_future)
-                    .join(texture_coordinate_buffer_future)
-                    .join(instance_buffer_future)
-                    .boxed(),
-            ),
             recreate_swapchain: false,
             surface,
             swapchain,
+            images,
             queue,
-            view_rotation_pitch: 0.0,
-            view_rotation_yaw: 0.0,
-            view_translation: Translation3::new(-250.0, -250.0, -250.0),
+            pending_screenshots: Vec::new(),
+            readback_buffers: Vec::new(),
+            pending_frames: VecDeque::new(),
+            draw_calls: 0,
+        };
+        for chunk in world.chunk_positions() {
+            main_pipeline.update_chunk(world, chunk)?;
         }
+        Ok(main_pipeline)
     }
 
-    pub fn build_rotation(&self) -> Rotation3<f32> {
-        let rot1 = Rotation3::new(Vector3::new(self.view_rotation_pitch as f32, 0.0, 0.0));
+    pub fn device_name(&self) -> &str {
+        &self.device.physical_device().properties().device_name
+    }
 
-        let rot2 = Rotation3::new(Vector3::new(0.0, self.view_rotation_yaw as f32, 0.0));
-        return rot1 * rot2;
+    /// Number of chunks that have something to draw.
+    pub fn chunk_mesh_count(&self) -> usize {
+        self.chunk_meshes.len()
     }
 
-    pub fn render(&mut self) {
-        self.previous_frame_end.as_mut().unwrap().cleanup_finished();
+    /// Rebuilds the mesh of a single chunk after its blocks changed.
+    pub fn update_chunk(&mut self, world: &World, chunk: Point3<i32>) -> Result<()> {
+        let mesh = build_chunk_mesh(world, chunk, &self.biome_colors);
+        let buffers = ChunkBuffers {
+            opaque: self.upload_vertices(mesh.opaque)?,
+            translucent: self.upload_vertices(mesh.translucent)?,
+            water: self.upload_vertices(mesh.water)?,
+        };
+        if buffers.opaque.is_none() && buffers.translucent.is_none() && buffers.water.is_none() {
+            self.chunk_meshes.remove(&chunk);
+        } else {
+            self.chunk_meshes.insert(chunk, buffers);
+        }
+        Ok(())
+    }
+
+    /// Creates a buffer in device memory that is filled by the next frame.
+    fn upload_vertices(&mut self, vertices: Vec<ChunkVertex>) -> Result<Option<VertexBuffer>> {
+        if vertices.is_empty() {
+            return Ok(None);
+        }
+        let source = self
+            .upload_pool
+            .chunk(vertices)
+            .context("stage a chunk mesh")?;
+        // frames in flight keep the old buffer alive until they are done with it
+        let destination = DeviceLocalBuffer::array(
+            self.device.clone(),
+            source.len(),
+            BufferUsage {
+                transfer_destination: true,
+                ..BufferUsage::vertex_buffer()
+            },
+            [self.queue.family()],
+        )
+        .context("allocate a chunk mesh")?;
+        self.pending_uploads.push(PendingUpload {
+            source,
+            destination: destination.clone(),
+        });
+        Ok(Some(destination))
+    }
+
+    /// The recorded frames that have been copied back from the GPU, in the order they were
+    /// rendered.
+    pub fn finished_frames(&mut self) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        while let Some(rgba) = self
+            .pending_frames
+            .front()
+            .and_then(|pending| pending.try_read_rgba())
+        {
+            let pending = self.pending_frames.pop_front().unwrap();
+            frames.push(Frame {
+                extent: pending.extent,
+                rgba,
+            });
+        }
+        frames
+    }
+
+    /// Whether recorded frames are still on their way back from the GPU.
+    pub fn has_pending_frames(&self) -> bool {
+        !self.pending_frames.is_empty()
+    }
+
+    /// Forgets the recorded frames that are still being copied.
+    pub fn discard_recorded_frames(&mut self) {
+        self.pending_frames.clear();
+    }
+
+    pub fn render(&mut self, view: &FrameView) -> Result<()> {
+        if let Some(previous_frame_end) = self.previous_frame_end.as_mut() {
+            previous_frame_end.cleanup_finished();
+        }
+
+        self.pending_screenshots
+            .retain(|pending| match pending.try_read_rgba() {
+                Some(rgba) => {
+                    screenshot::save_screenshot(pending.extent, rgba);
+                    false
+                }
+                None => true,
+            });
 
         if self.recreate_swapchain {
             let (new_swapchain, new_images) = match self.swapchain.recreate(SwapchainCreateInfo {
@@ -582,22 +586,53 @@ impl MainPipeline {
                 ..self.swapchain.create_info()
             }) {
                 Ok(r) => r,
-                Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
-                Err(e) => panic!("Failed to recreate swapchain: {:?}", e),
+                Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return Ok(()),
+                Err(SwapchainCreationError::DeviceLost) => {
+                    return Err(PoritzCraftError::DeviceLost)
+                }
+                Err(e) => return Err(e).context("recreate the swapchain"),
             };
 
             self.swapchain = new_swapchain;
+            // pending readbacks keep the buffers they still need
+            self.readback_buffers.clear();
 
             // this part here is pipeline specific - the part above not
-            let (new_pipeline, new_framebuffers) = window_size_dependent_setup(
+            let (new_pipeline, new_translucent_pipeline, new_water_pipeline, new_framebuffers) =
+                window_size_dependent_setup(
+                    self.device.clone(),
+                    &self.vs,
+                    &self.fs,
+                    &self.water_fs,
+                    &new_images,
+                    self.render_pass.clone(),
+                )?;
+            self.pipeline = new_pipeline;
+            self.translucent_pipeline = new_translucent_pipeline;
+            self.water_pipeline = new_water_pipeline;
+            self.outline_pipeline = create_outline_pipeline(
                 self.device.clone(),
-                &self.vs,
-                &self.fs,
+                &self.outline_vs,
+                &self.outline_fs,
                 &new_images,
                 self.render_pass.clone(),
-            );
-            self.pipeline = new_pipeline;
+            )?;
+            self.overlay_pipeline = create_overlay_pipeline(
+                self.device.clone(),
+                &self.overlay_vs,
+                &self.overlay_fs,
+                &new_images,
+                self.render_pass.clone(),
+            )?;
+            self.sky_pipeline = create_sky_pipeline(
+                self.device.clone(),
+                &self.sky_vs,
+                &self.sky_fs,
+                &new_images,
+                self.render_pass.clone(),
+            )?;
             self.framebuffers = new_framebuffers;
+            self.images = new_images;
             self.recreate_swapchain = false;
         }
 
@@ -606,9 +641,10 @@ impl MainPipeline {
                 Ok(r) => r,
                 Err(AcquireError::OutOfDate) => {
                     self.recreate_swapchain = true;
-                    return;
+                    return Ok(());
                 }
-                Err(e) => panic!("Failed to acquire next image: {:?}", e),
+                Err(AcquireError::DeviceLost) => return Err(PoritzCraftError::DeviceLost),
+                Err(e) => return Err(e).context("acquire the next image"),
             };
 
         if suboptimal {
@@ -616,68 +652,164 @@ impl MainPipeline {
         }
 
         // this part here is pipeline-specific
-        let uniform_buffer_subbuffer = {
-            let aspect_ratio =
-                self.swapchain.image_extent()[0] as f32 / self.swapchain.image_extent()[1] as f32;
-            let proj = Matrix4::new_perspective(
+        let aspect_ratio =
+            self.swapchain.image_extent()[0] as f32 / self.swapchain.image_extent()[1] as f32;
+        // far enough for the corners of the drawn area, everything beyond is hidden by fog
+        let far = ((view.render_distance + 1) * CHUNK_SIZE as u32) as f32 * BLOCK_SIZE * 2.0;
+        let proj = Matrix4::new_perspective(aspect_ratio, FOV, NEAR, far);
+        let fog = match view.camera_fluid {
+            Some(fluid) => Fog::in_fluid(fluid),
+            None => Fog::new(&view.fog, view.render_distance, view.sky.horizon_color),
+        };
+        // both are symmetric in y, so this goes from world to render coordinates as well
+        let light_direction = render_direction_to_world(view.sky.light_direction());
+        let camera_chunk =
+            chunk_position(render_to_world(view.camera.position()).map(|c| c.floor() as i32));
+        let cascades = if self.shadow_settings.cascades > 0 {
+            let frustum = Frustum {
+                view: view.camera.view(),
+                fov: FOV,
                 aspect_ratio,
-                70.0 * std::f32::consts::PI / 180.0, // this value is exciting
-                0.1,
-                10000.0,
-            );
+                near: NEAR,
+            };
+            let distance =
+                SHADOW_DISTANCE.min((view.render_distance * CHUNK_SIZE as u32) as f32) * BLOCK_SIZE;
+            shadow::cascades(&frustum, light_direction, distance, &self.shadow_settings)
+        } else {
+            Vec::new()
+        };
+
+        let uniform_buffer_subbuffer = {
             /*let view = Matrix4::look_at_rh(
                 &Point3::new(0.3, 0.3, 1.0),
                 &Point3::new(0.0, 0.0, 0.0),
                 &Vector3::new(0.0, -1.0, 0.0),
             );*/
 
-            let view = self.build_rotation() * self.view_translation;
+            let camera_view = view.camera.view();
 
             let uniform_data = vs::ty::Data {
                 world: Matrix4::identity().into(), //self.view_matrix.into(),
-                view: view.to_matrix().into(),
+                view: camera_view.to_matrix().into(),
                 proj: proj.into(),
+                sun: [
+                    light_direction.x,
+                    light_direction.y,
+                    light_direction.z,
+                    view.sky.sky_light,
+                ],
             };
 
             // TODO FIXMe check if this is ever dropped
-            self.uniform_buffer.next(uniform_data).unwrap()
+            self.uniform_buffer
+                .next(uniform_data)
+                .context("allocate the uniform buffer")?
+        };
+
+        let shadow_subbuffer = self
+            .shadow_buffer
+            .next(shadow_uniforms(&cascades, &self.shadow_settings))
+            .context("allocate the shadow uniform buffer")?;
+        let mut animations = fs::ty::Animations {
+            frames: [[0.0, 0.0, 0.0, 1.0]; MAX_TEXTURES],
         };
+        for (frame, animation) in animations.frames.iter_mut().zip(&self.animations) {
+            *frame = animation.shader_data(view.animation_time);
+        }
+        let animation_subbuffer = self
+            .animation_buffer
+            .next(animations)
+            .context("allocate the animation uniform buffer")?;
+        let shadow_set = PersistentDescriptorSet::new(
+            self.pipeline.layout().set_layouts()[2].clone(),
+            [
+                WriteDescriptorSet::image_view_sampler(
+                    0,
+                    self.shadow_map.clone(),
+                    self.shadow_sampler.clone(),
+                ),
+                WriteDescriptorSet::buffer(1, shadow_subbuffer),
+                WriteDescriptorSet::buffer(2, animation_subbuffer),
+            ],
+        )
+        .context("create the shadow descriptor set")?;
 
         let layout = self.pipeline.layout().set_layouts().get(0).unwrap();
         let set = PersistentDescriptorSet::new(
             layout.clone(),
-            [WriteDescriptorSet::buffer(0, uniform_buffer_subbuffer)],
+            [WriteDescriptorSet::buffer(
+                0,
+                uniform_buffer_subbuffer.clone(),
+            )],
         )
-        .unwrap();
+        .context("create the uniform descriptor set")?;
 
         let layout2 = self.pipeline.layout().set_layouts().get(1).unwrap();
         let set2 = PersistentDescriptorSet::new_variable(
             layout2.clone(),
-            2,
+            TEXTURE_COUNT,
             [WriteDescriptorSet::image_view_sampler_array(
                 0,
                 0,
                 self.textures.clone(),
             )],
         )
-        .unwrap();
+        .context("create the texture descriptor set")?;
 
         let mut builder = AutoCommandBufferBuilder::primary(
             self.device.clone(),
             self.queue.family(),
             CommandBufferUsage::OneTimeSubmit,
         )
-        .unwrap();
+        .context("begin the command buffer")?;
+        // the copies finish before anything is drawn from the buffers
+        for upload in self.pending_uploads.drain(..) {
+            builder
+                .copy_buffer(CopyBufferInfo::buffers(upload.source, upload.destination))
+                .context("copy a chunk mesh")?;
+        }
+        let mut draw_calls = self.draw_shadows(&mut builder, &cascades, camera_chunk)?;
+
+        let [r, g, b] = fog.color;
         builder
             .begin_render_pass(
                 RenderPassBeginInfo {
-                    clear_values: vec![Some([0.0, 0.0, 1.0, 1.0].into()), Some(1.0f32.into())],
+                    clear_values: vec![Some([r, g, b, 1.0].into()), Some(1.0f32.into())],
                     ..RenderPassBeginInfo::framebuffer(self.framebuffers[image_num].clone())
                 },
                 SubpassContents::Inline,
             )
-            .unwrap()
+            .context("begin the render pass")?;
+        if view.camera_fluid.is_none() {
+            self.draw_sky(&mut builder, view, &proj)?;
+            draw_calls += 1;
+        }
+        let (fog_color, fog_parameters) = fog.shader_parameters();
+        let camera_position = view.camera.position();
+        let [zr, zg, zb] = view.sky.zenith_color;
+        let [hr, hg, hb] = view.sky.horizon_color;
+        let push_constants = fs::ty::PushConstants {
+            fog_color,
+            fog: fog_parameters,
+            alpha_cutoff: CUTOUT_ALPHA,
+            sun: [
+                light_direction.x,
+                light_direction.y,
+                light_direction.z,
+                view.sky.sky_light,
+            ],
+            camera: [
+                camera_position.x,
+                camera_position.y,
+                camera_position.z,
+                view.animation_time,
+            ],
+            zenith_color: [zr, zg, zb, 1.0],
+            horizon_color: [hr, hg, hb, 1.0],
+        };
+        builder
             .bind_pipeline_graphics(self.pipeline.clone())
+            .push_constants(self.pipeline.layout().clone(), 0, push_constants)
             .bind_descriptor_sets(
                 PipelineBindPoint::Graphics,
                 self.pipeline.layout().clone(),
@@ -690,33 +822,121 @@ impl MainPipeline {
                 1,
                 set2,
             )
-            .bind_vertex_buffers(
-                0,
-                (
-                    self.vertex_buffer.clone(),
-                    self.normals_buffer.clone(),
-                    self.texture_coordinate_buffer.clone(),
-                    self.instance_buffer.clone(),
-                ),
-            )
-            .draw(
-                self.vertex_buffer.len() as u32,
-                self.instance_buffer.len() as u32,
-                0,
-                0,
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.pipeline.layout().clone(),
+                2,
+                shadow_set,
+            );
+        let visible_chunks: Vec<_> = self
+            .chunk_meshes
+            .iter()
+            .filter(|(chunk, _)| (*chunk - camera_chunk).abs().max() <= view.render_distance as i32)
+            .collect();
+        for mesh in visible_chunks
+            .iter()
+            .filter_map(|(_, buffers)| buffers.opaque.as_ref())
+        {
+            draw_calls += 1;
+            builder
+                .bind_vertex_buffers(0, mesh.clone())
+                .draw(mesh.len() as u32, 1, 0, 0)
+                .context("draw a chunk")?;
+        }
+
+        // blending only looks right if whatever is further away was drawn first
+        let mut translucent: Vec<_> = visible_chunks
+            .iter()
+            .filter(|(_, buffers)| buffers.translucent.is_some() || buffers.water.is_some())
+            .map(|(chunk, buffers)| {
+                let center = chunk_render_center(**chunk);
+                ((center - camera_position).norm_squared(), buffers)
+            })
+            .collect();
+        translucent.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        let translucent_constants = fs::ty::PushConstants {
+            alpha_cutoff: 0.0,
+            ..push_constants
+        };
+        // the descriptor sets stay bound because all block pipelines have the same layout
+        let mut bound: Option<&Arc<GraphicsPipeline>> = None;
+        for (_, buffers) in translucent {
+            let parts = [
+                (&self.translucent_pipeline, &buffers.translucent),
+                (&self.water_pipeline, &buffers.water),
+            ];
+            for (pipeline, mesh) in parts {
+                let mesh = match mesh {
+                    Some(mesh) => mesh,
+                    None => continue,
+                };
+                if bound.map_or(true, |bound| !Arc::ptr_eq(bound, pipeline)) {
+                    builder
+                        .bind_pipeline_graphics(pipeline.clone())
+                        .push_constants(pipeline.layout().clone(), 0, translucent_constants);
+                    bound = Some(pipeline);
+                }
+                draw_calls += 1;
+                builder
+                    .bind_vertex_buffers(0, mesh.clone())
+                    .draw(mesh.len() as u32, 1, 0, 0)
+                    .context("draw a translucent chunk")?;
+            }
+        }
+
+        if !view.outline.is_empty() {
+            draw_calls += 1;
+            // the outline shares the view and projection with the blocks
+            let outline_set = PersistentDescriptorSet::new(
+                self.outline_pipeline.layout().set_layouts()[0].clone(),
+                [WriteDescriptorSet::buffer(0, uniform_buffer_subbuffer)],
             )
-            .unwrap()
-            .end_render_pass()
-            .unwrap();
-        let command_buffer = builder.build().unwrap();
+            .context("create the outline descriptor set")?;
+            let vertices = self
+                .outline_buffer_pool
+                .chunk(outline_vertices(&view.outline))
+                .context("allocate the outline vertices")?;
+            builder
+                .bind_pipeline_graphics(self.outline_pipeline.clone())
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Graphics,
+                    self.outline_pipeline.layout().clone(),
+                    0,
+                    outline_set,
+                )
+                .bind_vertex_buffers(0, vertices.clone())
+                .draw(vertices.len() as u32, 1, 0, 0)
+                .context("draw the outline")?;
+        }
+
+        self.draw_overlay(&mut builder, view)?;
+        draw_calls += 1;
+        self.draw_calls = draw_calls;
+
+        builder.end_render_pass().context("end the render pass")?;
+
+        if view.screenshot {
+            match self.record_readback(&mut builder, image_num)? {
+                Some(pending) => self.pending_screenshots.push(pending),
+                None => log::warn!("Screenshots are not supported by the swapchain"),
+            }
+        }
+
+        if view.record {
+            if let Some(pending) = self.record_readback(&mut builder, image_num)? {
+                self.pending_frames.push_back(pending);
+            }
+        }
+
+        let command_buffer = builder.build().context("build the command buffer")?;
 
         let future = self
             .previous_frame_end
             .take()
-            .unwrap()
+            .unwrap_or_else(|| sync::now(self.device.clone()).boxed())
             .join(acquire_future)
             .then_execute(self.queue.clone(), command_buffer)
-            .unwrap()
+            .context("submit the command buffer")?
             .then_swapchain_present(self.queue.clone(), self.swapchain.clone(), image_num)
             .then_signal_fence_and_flush();
 
@@ -728,33 +948,267 @@ impl MainPipeline {
                 self.recreate_swapchain = true;
                 self.previous_frame_end = Some(sync::now(self.device.clone()).boxed());
             }
+            Err(FlushError::DeviceLost) => return Err(PoritzCraftError::DeviceLost),
+            Err(FlushError::OomError(e)) => return Err(e).context("submit the frame"),
             Err(e) => {
-                println!("Failed to flush future: {:?}", e);
+                log::error!("Failed to flush future: {:?}", e);
                 self.previous_frame_end = Some(sync::now(self.device.clone()).boxed());
             }
         }
+
+        Ok(())
+    }
+
+    /// Renders the depth of the terrain as seen from the sun into every cascade.
+    ///
+    /// The render pass runs even without cascades so the shadow map is always initialized.
+    fn draw_shadows(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        cascades: &[Cascade],
+        camera_chunk: Point3<i32>,
+    ) -> Result<u32> {
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some(1.0f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(self.shadow_framebuffer.clone())
+                },
+                SubpassContents::Inline,
+            )
+            .context("begin the shadow render pass")?
+            .bind_pipeline_graphics(self.shadow_pipeline.clone());
+
+        let resolution = self.shadow_settings.resolution as f32;
+        // chunks outside of the shadow distance can still cast shadows into it
+        let chunk_distance = (SHADOW_DISTANCE / CHUNK_SIZE as f32).ceil() as i32 + 1;
+        let mut draw_calls = 0;
+        for (index, cascade) in cascades.iter().enumerate() {
+            builder
+                .set_viewport(
+                    0,
+                    [Viewport {
+                        origin: [index as f32 * resolution, 0.0],
+                        dimensions: [resolution, resolution],
+                        depth_range: 0.0..1.0,
+                    }],
+                )
+                .push_constants(
+                    self.shadow_pipeline.layout().clone(),
+                    0,
+                    shadow_vs::ty::PushConstants {
+                        view_projection: cascade.view_projection.into(),
+                    },
+                );
+            // translucent blocks let the light through
+            for (chunk, buffers) in &self.chunk_meshes {
+                let mesh = match &buffers.opaque {
+                    Some(mesh) => mesh,
+                    None => continue,
+                };
+                if (chunk - camera_chunk).abs().max() > chunk_distance {
+                    continue;
+                }
+                let (min, max) = chunk_render_bounds(*chunk);
+                if !cascade.intersects(min, max) {
+                    continue;
+                }
+                draw_calls += 1;
+                builder
+                    .bind_vertex_buffers(0, mesh.clone())
+                    .draw(mesh.len() as u32, 1, 0, 0)
+                    .context("draw a chunk into the shadow map")?;
+            }
+        }
+
+        builder
+            .end_render_pass()
+            .context("end the shadow render pass")?;
+        Ok(draw_calls)
+    }
+
+    /// Fills the background with the sky gradient, the sun, the moon and stars.
+    fn draw_sky(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        view: &FrameView,
+        proj: &Matrix4<f32>,
+    ) -> Result<()> {
+        // only the rotation, the sky is infinitely far away
+        let view_projection = proj * view.camera.view().rotation.to_homogeneous();
+        let inverse_view_projection = view_projection
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity);
+        let sun = view.sky.sun_direction;
+        let [zr, zg, zb] = view.sky.zenith_color;
+        let [hr, hg, hb] = view.sky.horizon_color;
+        builder
+            .bind_pipeline_graphics(self.sky_pipeline.clone())
+            .push_constants(
+                self.sky_pipeline.layout().clone(),
+                0,
+                sky_fs::ty::PushConstants {
+                    inverse_view_projection: inverse_view_projection.into(),
+                    sun_direction: [sun.x, sun.y, sun.z, view.sky.star_brightness],
+                    zenith_color: [zr, zg, zb, 1.0],
+                    horizon_color: [hr, hg, hb, 1.0],
+                },
+            )
+            .draw(3, 1, 0, 0)
+            .context("draw the sky")?;
+        Ok(())
+    }
+
+    fn draw_overlay(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        view: &FrameView,
+    ) -> Result<()> {
+        let extent = self.swapchain.image_extent();
+        let mut overlay = OverlayBuilder::new();
+        overlay::crosshair(&mut overlay, extent);
+        let frame_counts: Vec<_> = self.animations.iter().map(|a| a.frame_count).collect();
+        overlay::hotbar(&mut overlay, extent, view.hotbar, &frame_counts);
+        if let Some(lines) = &view.debug_lines {
+            overlay::debug_screen(&mut overlay, extent, lines);
+        }
+        if let Some(lines) = &view.console_lines {
+            overlay::console(&mut overlay, extent, lines);
+        }
+
+        let vertices = self
+            .overlay_buffer_pool
+            .chunk(overlay.vertices)
+            .context("allocate the overlay vertices")?;
+        let texture_set = PersistentDescriptorSet::new_variable(
+            self.overlay_pipeline.layout().set_layouts()[0].clone(),
+            TEXTURE_COUNT,
+            [WriteDescriptorSet::image_view_sampler_array(
+                0,
+                0,
+                self.textures.clone(),
+            )],
+        )
+        .context("create the overlay texture descriptor set")?;
+
+        builder
+            .bind_pipeline_graphics(self.overlay_pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.overlay_pipeline.layout().clone(),
+                0,
+                texture_set,
+            )
+            .push_constants(
+                self.overlay_pipeline.layout().clone(),
+                0,
+                overlay_vs::ty::PushConstants {
+                    screen_size: [extent[0] as f32, extent[1] as f32],
+                },
+            )
+            .bind_vertex_buffers(0, vertices.clone())
+            .draw(vertices.len() as u32, 1, 0, 0)
+            .context("draw the overlay")?;
+        Ok(())
+    }
+
+    /// Whether swapchain images can be copied and converted for screenshots and recording.
+    pub fn can_read_back(&self) -> bool {
+        self.swapchain.image_usage().transfer_source
+            && screenshot::is_supported(self.swapchain.image_format())
+    }
+
+    /// Records a copy of the swapchain image into a host visible buffer after the frame has been drawn.
+    fn record_readback(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image_num: usize,
+    ) -> Result<Option<PendingReadback>> {
+        if !self.can_read_back() {
+            return Ok(None);
+        }
+        let format = self.swapchain.image_format();
+
+        let extent = self.swapchain.image_extent();
+        // pending readbacks and the frames in flight hold on to the buffers they use
+        let free = self
+            .readback_buffers
+            .iter()
+            .find(|buffer| Arc::strong_count(buffer) == 1)
+            .cloned();
+        let buffer = match free {
+            Some(buffer) => buffer,
+            None => {
+                // safe because the copy overwrites the whole buffer before it is read
+                let buffer = unsafe {
+                    CpuAccessibleBuffer::uninitialized_array(
+                        self.device.clone(),
+                        extent[0] as DeviceSize * extent[1] as DeviceSize * 4,
+                        BufferUsage {
+                            transfer_destination: true,
+                            ..BufferUsage::none()
+                        },
+                        false,
+                    )
+                }
+                .context("allocate the readback buffer")?;
+                self.readback_buffers.push(buffer.clone());
+                buffer
+            }
+        };
+
+        builder
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                self.images[image_num].clone(),
+                buffer.clone(),
+            ))
+            .context("copy the swapchain image")?;
+
+        Ok(Some(PendingReadback {
+            buffer,
+            extent,
+            format,
+        }))
     }
 }
 
+/// The middle of a chunk in render coordinates.
+fn chunk_render_center(chunk: Point3<i32>) -> Point3<f32> {
+    let [x, y, z] = block_render_position(chunk * CHUNK_SIZE);
+    // blocks go down in render coordinates
+    let half = (CHUNK_SIZE - 1) as f32 / 2.0 * BLOCK_SIZE;
+    Point3::new(x + half, y - half, z + half)
+}
+
 /// This method is called once during initialization, then again whenever the window is resized
+///
+/// Returns the pipelines for opaque blocks, translucent blocks and water, which share their layout.
 fn window_size_dependent_setup(
     device: Arc<Device>,
     vs: &ShaderModule,
     fs: &ShaderModule,
+    water_fs: &ShaderModule,
     images: &[Arc<SwapchainImage<Window>>],
     render_pass: Arc<RenderPass>,
-) -> (Arc<GraphicsPipeline>, Vec<Arc<Framebuffer>>) {
+) -> Result<(
+    Arc<GraphicsPipeline>,
+    Arc<GraphicsPipeline>,
+    Arc<GraphicsPipeline>,
+    Vec<Arc<Framebuffer>>,
+)> {
     let dimensions = images[0].dimensions().width_height();
 
     let depth_buffer = ImageView::new_default(
-        AttachmentImage::transient(device.clone(), dimensions, Format::D16_UNORM).unwrap(),
+        AttachmentImage::transient(device.clone(), dimensions, Format::D16_UNORM)
+            .context("create the depth buffer")?,
     )
-    .unwrap();
+    .context("create the depth buffer view")?;
 
     let framebuffers = images
         .iter()
         .map(|image| {
-            let view = ImageView::new_default(image.clone()).unwrap();
+            let view =
+                ImageView::new_default(image.clone()).context("create the swapchain image view")?;
             Framebuffer::new(
                 render_pass.clone(),
                 FramebufferCreateInfo {
@@ -762,54 +1216,14 @@ fn window_size_dependent_setup(
                     ..Default::default()
                 },
             )
-            .unwrap()
+            .context("create the framebuffer")
         })
-        .collect::<Vec<_>>();
-
-    let pipeline_layout = {
-        let mut layout_create_infos: Vec<_> = DescriptorSetLayoutCreateInfo::from_requirements(
-            fs.entry_point("main")
-                .unwrap()
-                .descriptor_requirements()
-                .chain(vs.entry_point("main").unwrap().descriptor_requirements()),
-        );
+        .collect::<Result<Vec<_>>>()?;
 
-        //println!("{:#?}", layout_create_infos);
-        let binding = layout_create_infos[1].bindings.get_mut(&0).unwrap();
-        binding.variable_descriptor_count = true;
-        binding.descriptor_count = 2;
-
-        let set_layouts = layout_create_infos
-            .into_iter()
-            .map(|desc| Ok(DescriptorSetLayout::new(device.clone(), desc.clone())?))
-            .collect::<Result<Vec<_>, DescriptorSetLayoutCreationError>>()
-            .unwrap();
-
-        PipelineLayout::new(
-            device.clone(),
-            PipelineLayoutCreateInfo {
-                set_layouts,
-                push_constant_ranges: fs
-                    .entry_point("main")
-                    .unwrap()
-                    .push_constant_requirements()
-                    .cloned()
-                    .into_iter()
-                    .collect(),
-                ..Default::default()
-            },
-        )
-        .unwrap()
-    };
+    let pipeline_layout = create_pipeline_layout(device.clone(), vs, fs, 1)?;
 
     let pipeline = GraphicsPipeline::start()
-        .vertex_input_state(
-            BuffersDefinition::new()
-                .vertex::<Vertex>()
-                .vertex::<Normal>()
-                .vertex::<TexCoord>()
-                .instance::<InstanceData>(),
-        )
+        .vertex_input_state(BuffersDefinition::new().vertex::<ChunkVertex>())
         .vertex_shader(vs.entry_point("main").unwrap(), ())
         .input_assembly_state(InputAssemblyState {
             // primitive_restart_enable: StateMode::Fixed(true),
@@ -825,16 +1239,281 @@ fn window_size_dependent_setup(
         ]))
         .fragment_shader(fs.entry_point("main").unwrap(), ())
         .depth_stencil_state(DepthStencilState::simple_depth_test())
-        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
         .rasterization_state(RasterizationState {
             cull_mode: StateMode::Fixed(CullMode::Back),
             front_face: StateMode::Fixed(FrontFace::CounterClockwise),
             ..Default::default()
         })
-        .with_pipeline_layout(device.clone(), pipeline_layout)
+        .with_pipeline_layout(device.clone(), pipeline_layout.clone())
+        .context("create the graphics pipeline")?;
+    set_object_name(&device, &*pipeline, "main pipeline");
+
+    // translucent faces are tested against the depth of the opaque blocks, but they don't hide
+    // the translucent faces behind them
+    let mut translucent_depth = DepthStencilState::simple_depth_test();
+    if let Some(depth) = translucent_depth.depth.as_mut() {
+        depth.write_enable = StateMode::Fixed(false);
+    }
+    let blended_pipeline = |fs: &ShaderModule| {
+        GraphicsPipeline::start()
+            .vertex_input_state(BuffersDefinition::new().vertex::<ChunkVertex>())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([
+                Viewport {
+                    origin: [0.0, 0.0],
+                    dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+                    depth_range: 0.0..1.0,
+                },
+            ]))
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .depth_stencil_state(translucent_depth.clone())
+            .color_blend_state(ColorBlendState::new(1).blend_alpha())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .rasterization_state(RasterizationState {
+                cull_mode: StateMode::Fixed(CullMode::Back),
+                front_face: StateMode::Fixed(FrontFace::CounterClockwise),
+                ..Default::default()
+            })
+            .with_pipeline_layout(device.clone(), pipeline_layout.clone())
+    };
+    let translucent_pipeline = blended_pipeline(fs).context("create the translucent pipeline")?;
+    set_object_name(&device, &*translucent_pipeline, "translucent pipeline");
+    // the water shader declares the same push constants and descriptor sets as `fs`, so the
+    // layout fits it as well
+    let water_pipeline = blended_pipeline(water_fs).context("create the water pipeline")?;
+    set_object_name(&device, &*water_pipeline, "water pipeline");
+
+    Ok((pipeline, translucent_pipeline, water_pipeline, framebuffers))
+}
+
+/// Creates a pipeline layout from the requirements of both shaders, the sampler array at binding 0
+/// of `texture_set` gets a variable descriptor count so it can hold all block textures.
+fn create_pipeline_layout(
+    device: Arc<Device>,
+    vs: &ShaderModule,
+    fs: &ShaderModule,
+    texture_set: usize,
+) -> Result<Arc<PipelineLayout>> {
+    let mut layout_create_infos: Vec<_> = DescriptorSetLayoutCreateInfo::from_requirements(
+        fs.entry_point("main")
+            .unwrap()
+            .descriptor_requirements()
+            .chain(vs.entry_point("main").unwrap().descriptor_requirements()),
+    );
+
+    let binding = layout_create_infos[texture_set]
+        .bindings
+        .get_mut(&0)
         .unwrap();
+    binding.variable_descriptor_count = true;
+    binding.descriptor_count = TEXTURE_COUNT;
+
+    let set_layouts = layout_create_infos
+        .into_iter()
+        .map(|desc| Ok(DescriptorSetLayout::new(device.clone(), desc.clone())?))
+        .collect::<Result<Vec<_>, DescriptorSetLayoutCreationError>>()
+        .context("create the descriptor set layouts")?;
+
+    PipelineLayout::new(
+        device.clone(),
+        PipelineLayoutCreateInfo {
+            set_layouts,
+            push_constant_ranges: vs
+                .entry_point("main")
+                .unwrap()
+                .push_constant_requirements()
+                .into_iter()
+                .chain(fs.entry_point("main").unwrap().push_constant_requirements())
+                .cloned()
+                .collect(),
+            ..Default::default()
+        },
+    )
+    .context("create the pipeline layout")
+}
+
+/// A line list pipeline for the box around the targeted block, drawn on top of the blocks
+/// in the same render pass.
+fn create_outline_pipeline(
+    device: Arc<Device>,
+    vs: &ShaderModule,
+    fs: &ShaderModule,
+    images: &[Arc<SwapchainImage<Window>>],
+    render_pass: Arc<RenderPass>,
+) -> Result<Arc<GraphicsPipeline>> {
+    let dimensions = images[0].dimensions().width_height();
+    let pipeline = GraphicsPipeline::start()
+        .vertex_input_state(BuffersDefinition::new().vertex::<OutlineVertex>())
+        .vertex_shader(vs.entry_point("main").unwrap(), ())
+        .input_assembly_state(InputAssemblyState::new().topology(PrimitiveTopology::LineList))
+        .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([
+            Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+                depth_range: 0.0..1.0,
+            },
+        ]))
+        .fragment_shader(fs.entry_point("main").unwrap(), ())
+        .depth_stencil_state(DepthStencilState::simple_depth_test())
+        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .build(device.clone())
+        .context("create the outline pipeline")?;
+    set_object_name(&device, &*pipeline, "outline pipeline");
+
+    Ok(pipeline)
+}
+
+/// The HUD, drawn last without depth testing and blended over the world.
+fn create_overlay_pipeline(
+    device: Arc<Device>,
+    vs: &ShaderModule,
+    fs: &ShaderModule,
+    images: &[Arc<SwapchainImage<Window>>],
+    render_pass: Arc<RenderPass>,
+) -> Result<Arc<GraphicsPipeline>> {
+    let dimensions = images[0].dimensions().width_height();
+    let pipeline = GraphicsPipeline::start()
+        .vertex_input_state(BuffersDefinition::new().vertex::<OverlayVertex>())
+        .vertex_shader(vs.entry_point("main").unwrap(), ())
+        .input_assembly_state(InputAssemblyState::new())
+        .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([
+            Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+                depth_range: 0.0..1.0,
+            },
+        ]))
+        .fragment_shader(fs.entry_point("main").unwrap(), ())
+        .color_blend_state(ColorBlendState::new(1).blend_alpha())
+        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .with_pipeline_layout(
+            device.clone(),
+            create_pipeline_layout(device.clone(), vs, fs, 0)?,
+        )
+        .context("create the overlay pipeline")?;
+    set_object_name(&device, &*pipeline, "overlay pipeline");
+
+    Ok(pipeline)
+}
+
+/// The corners of the box around all blocks of the chunk in render coordinates.
+fn chunk_render_bounds(chunk: Point3<i32>) -> (Point3<f32>, Point3<f32>) {
+    let first = world_to_render((chunk * CHUNK_SIZE).cast());
+    let last = world_to_render(((chunk + Vector3::repeat(1)) * CHUNK_SIZE).cast());
+    (first.inf(&last), first.sup(&last))
+}
+
+/// Renders chunk meshes into the shadow map, the viewport selects the cascade.
+fn create_shadow_pipeline(
+    device: Arc<Device>,
+    vs: &ShaderModule,
+    fs: &ShaderModule,
+    render_pass: Arc<RenderPass>,
+) -> Result<Arc<GraphicsPipeline>> {
+    let pipeline = GraphicsPipeline::start()
+        .vertex_input_state(BuffersDefinition::new().vertex::<ChunkVertex>())
+        .vertex_shader(vs.entry_point("main").unwrap(), ())
+        .input_assembly_state(InputAssemblyState::new())
+        .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+        .fragment_shader(fs.entry_point("main").unwrap(), ())
+        .depth_stencil_state(DepthStencilState::simple_depth_test())
+        .rasterization_state(RasterizationState {
+            // both sides so thin walls facing away from the sun still cast shadows
+            cull_mode: StateMode::Fixed(CullMode::None),
+            depth_bias: Some(DepthBiasState {
+                enable_dynamic: false,
+                bias: StateMode::Fixed(DepthBias {
+                    constant_factor: 1.25,
+                    clamp: 0.0,
+                    slope_factor: 1.75,
+                }),
+            }),
+            ..Default::default()
+        })
+        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .build(device.clone())
+        .context("create the shadow pipeline")?;
+    set_object_name(&device, &*pipeline, "shadow pipeline");
+
+    Ok(pipeline)
+}
+
+/// The cascades as the fragment shader expects them, unused cascades are left empty.
+fn shadow_uniforms(cascades: &[Cascade], settings: &ShadowSettings) -> fs::ty::Shadows {
+    let mut uniforms = fs::ty::Shadows {
+        cascades: [Matrix4::identity().into(); MAX_CASCADES as usize],
+        splits: [0.0; 4],
+        texel_sizes: [0.0; 4],
+        settings: [cascades.len() as f32, settings.resolution as f32, 0.0, 0.0],
+    };
+    for (index, cascade) in cascades.iter().enumerate() {
+        uniforms.cascades[index] = cascade.view_projection.into();
+        uniforms.splits[index] = cascade.split;
+        uniforms.texel_sizes[index] = cascade.texel_size;
+    }
+    uniforms
+}
+
+/// A full screen triangle drawn before everything else, without depth testing so the terrain
+/// covers it.
+fn create_sky_pipeline(
+    device: Arc<Device>,
+    vs: &ShaderModule,
+    fs: &ShaderModule,
+    images: &[Arc<SwapchainImage<Window>>],
+    render_pass: Arc<RenderPass>,
+) -> Result<Arc<GraphicsPipeline>> {
+    let dimensions = images[0].dimensions().width_height();
+    let pipeline = GraphicsPipeline::start()
+        .vertex_input_state(BuffersDefinition::new())
+        .vertex_shader(vs.entry_point("main").unwrap(), ())
+        .input_assembly_state(InputAssemblyState::new())
+        .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([
+            Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+                depth_range: 0.0..1.0,
+            },
+        ]))
+        .fragment_shader(fs.entry_point("main").unwrap(), ())
+        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .build(device.clone())
+        .context("create the sky pipeline")?;
+    set_object_name(&device, &*pipeline, "sky pipeline");
+
+    Ok(pipeline)
+}
 
-    (pipeline, framebuffers)
+/// Reads the animation of `texture` from the contents of its `.png.mcmeta` file.
+fn texture_animation(
+    texture: &ImageView<ImmutableImage>,
+    mcmeta: &str,
+) -> Result<TextureAnimation> {
+    let dimensions = texture.image().dimensions();
+    TextureAnimation::parse(mcmeta, dimensions.width(), dimensions.height())
+}
+
+fn load_texture(png_bytes: &[u8], queue: Arc<Queue>) -> Result<Arc<ImageView<ImmutableImage>>> {
+    let (width, height, image_data) = decode_png_rgba8(png_bytes).context("decode the texture")?;
+    let dimensions = ImageDimensions::Dim2d {
+        width,
+        height,
+        array_layers: 1,
+    };
+
+    let image = ImmutableImage::from_iter(
+        image_data,
+        dimensions,
+        MipmapsCount::One,
+        Format::R8G8B8A8_SRGB,
+        queue,
+    )
+    .context("upload the texture")?
+    .0;
+
+    ImageView::new_default(image).context("create the texture view")
 }
 
 mod vs {
@@ -856,5 +1535,92 @@ mod fs {
         path: "src/frag.glsl",
         vulkan_version: "1.2",
         spirv_version: "1.5",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+mod water_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/water_frag.glsl",
+        vulkan_version: "1.2",
+        spirv_version: "1.5",
+    }
+}
+
+mod outline_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/outline_vert.glsl",
+    }
+}
+
+mod outline_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/outline_frag.glsl",
+    }
+}
+
+mod overlay_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/overlay_vert.glsl",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+mod overlay_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/overlay_frag.glsl",
+        vulkan_version: "1.2",
+        spirv_version: "1.5",
+    }
+}
+
+mod sky_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/sky_vert.glsl",
+    }
+}
+
+mod sky_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/sky_frag.glsl",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+mod shadow_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shadow_vert.glsl",
+        types_meta: {
+            use bytemuck::{Pod, Zeroable};
+
+            #[derive(Clone, Copy, Zeroable, Pod)]
+        },
+    }
+}
+
+mod shadow_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shadow_frag.glsl",
     }
 }
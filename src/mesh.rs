@@ -0,0 +1,418 @@
+use nalgebra::{Point3, Vector3};
+
+use crate::{
+    biome::{BiomeColors, Climate, Tint},
+    fluid::{Fluid, SOURCE_LEVEL},
+    light::{LightKind, MAX_LIGHT},
+    model::BlockModel,
+    physics::Aabb,
+    utils::{ChunkVertex, OutlineVertex, SIZE},
+    world::{
+        block_render_position, chunk_position, world_to_render, Block, Face, RenderLayer, World,
+        CHUNK_SIZE,
+    },
+};
+
+// https://www.saschawillems.de/blog/2019/03/29/flipping-the-vulkan-viewport/
+// https://matthewwellings.com/blog/the-new-vulkan-coordinate-system/
+// render coordinates:
+// x right
+// y down
+// z back
+
+/// The tint of faces without a biome color. Untinted textures are drawn at twice their brightness,
+/// the colormaps are bright enough by themselves.
+pub const UNTINTED: [f32; 3] = [2.0; 3];
+
+// counter clockwise around the whole face (for back-face culling)
+const TEX_COORDS: [[f32; 2]; 6] = [
+    [0.0, 0.0],
+    [0.0, 1.0],
+    [1.0, 1.0],
+    [1.0, 1.0],
+    [1.0, 0.0],
+    [0.0, 0.0],
+];
+
+/// The corners of the two triangles of a face relative to the block center in units of `SIZE`,
+/// and the unit normal in render coordinates.
+fn face_geometry(face: Face) -> ([[f32; 3]; 6], [f32; 3]) {
+    match face {
+        Face::NegZ => (
+            [
+                [-1.0, 1.0, -1.0],
+                [-1.0, -1.0, -1.0],
+                [1.0, -1.0, -1.0],
+                [1.0, -1.0, -1.0],
+                [1.0, 1.0, -1.0],
+                [-1.0, 1.0, -1.0],
+            ],
+            [0.0, 0.0, -1.0],
+        ),
+        Face::PosZ => (
+            [
+                [1.0, 1.0, 1.0],
+                [1.0, -1.0, 1.0],
+                [-1.0, -1.0, 1.0],
+                [-1.0, -1.0, 1.0],
+                [-1.0, 1.0, 1.0],
+                [1.0, 1.0, 1.0],
+            ],
+            [0.0, 0.0, 1.0],
+        ),
+        Face::PosX => (
+            [
+                [1.0, 1.0, -1.0],
+                [1.0, -1.0, -1.0],
+                [1.0, -1.0, 1.0],
+                [1.0, -1.0, 1.0],
+                [1.0, 1.0, 1.0],
+                [1.0, 1.0, -1.0],
+            ],
+            [1.0, 0.0, 0.0],
+        ),
+        Face::NegX => (
+            [
+                [-1.0, 1.0, 1.0],
+                [-1.0, -1.0, 1.0],
+                [-1.0, -1.0, -1.0],
+                [-1.0, -1.0, -1.0],
+                [-1.0, 1.0, -1.0],
+                [-1.0, 1.0, 1.0],
+            ],
+            [-1.0, 0.0, 0.0],
+        ),
+        // y points down in render coordinates, so the bottom of the block is at +SIZE
+        Face::NegY => (
+            [
+                [-1.0, 1.0, 1.0],
+                [-1.0, 1.0, -1.0],
+                [1.0, 1.0, -1.0],
+                [1.0, 1.0, -1.0],
+                [1.0, 1.0, 1.0],
+                [-1.0, 1.0, 1.0],
+            ],
+            [0.0, 1.0, 0.0],
+        ),
+        Face::PosY => (
+            [
+                [-1.0, -1.0, -1.0],
+                [-1.0, -1.0, 1.0],
+                [1.0, -1.0, 1.0],
+                [1.0, -1.0, 1.0],
+                [1.0, -1.0, -1.0],
+                [-1.0, -1.0, -1.0],
+            ],
+            [0.0, -1.0, 0.0],
+        ),
+    }
+}
+
+/// The vertices of one chunk, split by the pass they are drawn in.
+#[derive(Clone, Debug, Default)]
+pub struct ChunkMesh {
+    /// opaque and cutout blocks
+    pub opaque: Vec<ChunkVertex>,
+    pub translucent: Vec<ChunkVertex>,
+    /// translucent as well, but drawn with the water shader
+    pub water: Vec<ChunkVertex>,
+}
+
+/// Whether the face of `block` towards `neighbor` can't be seen.
+fn is_face_hidden(block: Block, face: Face, neighbor: Block) -> bool {
+    if neighbor == block && block.blockstate().is_none() {
+        // glass next to glass looks like one large pane
+        return true;
+    }
+    // the surface of a fluid is lower than the block above it
+    neighbor.is_opaque() && !(block.fluid().is_some() && face == Face::PosY)
+}
+
+/// How high the fluid at `position` reaches, from 0 to 1.
+fn fluid_height(world: &World, position: Point3<i32>, fluid: Fluid) -> f32 {
+    if world.block(position + Vector3::y()).fluid() == Some(fluid) {
+        return 1.0;
+    }
+    // a little below the top so the surface doesn't touch the block above
+    world.fluid_level(position) as f32 / (SOURCE_LEVEL + 1) as f32
+}
+
+/// The height of the fluid surface at the corner between the four blocks around `corner`, which
+/// are at `corner - 1` and `corner` on x and z.
+///
+/// Neighbors with the same fluid slope the surface towards their own height, empty ones pull it
+/// down and solid blocks are left out.
+fn fluid_corner_height(world: &World, corner: Point3<i32>, fluid: Fluid) -> f32 {
+    let mut sum = 0.0;
+    let mut count = 0;
+    for (dx, dz) in [(-1, -1), (-1, 0), (0, -1), (0, 0)] {
+        let position = corner + Vector3::new(dx, 0, dz);
+        let block = world.block(position);
+        if block.fluid() == Some(fluid) {
+            let height = fluid_height(world, position, fluid);
+            if height >= 1.0 {
+                // keeps falling fluid connected to the fluid above
+                return height;
+            }
+            sum += height;
+            count += 1;
+        } else if !block.is_solid() {
+            count += 1;
+        }
+    }
+    sum / count as f32
+}
+
+/// The climate of the columns of a chunk and the columns around it.
+struct ChunkClimate {
+    origin: Point3<i32>,
+    /// from `origin - 1` to `origin + CHUNK_SIZE` on x and z
+    columns: Vec<Climate>,
+}
+
+impl ChunkClimate {
+    const WIDTH: i32 = CHUNK_SIZE + 2;
+
+    fn new(world: &World, origin: Point3<i32>) -> Self {
+        let mut columns = Vec::with_capacity((Self::WIDTH * Self::WIDTH) as usize);
+        for z in -1..=CHUNK_SIZE {
+            for x in -1..=CHUNK_SIZE {
+                columns.push(world.climate(origin.x + x, origin.z + z));
+            }
+        }
+        Self { origin, columns }
+    }
+
+    /// The tint at the vertical edge between blocks at `x - 1` to `x` and `z - 1` to `z`,
+    /// blended from the four columns touching it so colors fade between biomes.
+    fn tint(&self, colors: &BiomeColors, tint: Tint, x: i32, z: i32) -> [f32; 3] {
+        let mut sum = [0.0; 3];
+        for (dx, dz) in [(-1, -1), (-1, 0), (0, -1), (0, 0)] {
+            let column_x = x + dx - self.origin.x + 1;
+            let column_z = z + dz - self.origin.z + 1;
+            let climate = self.columns[(column_z * Self::WIDTH + column_x) as usize];
+            let color = colors.tint(tint, climate);
+            for channel in 0..3 {
+                sum[channel] += color[channel] / 4.0;
+            }
+        }
+        sum
+    }
+}
+
+/// Builds the faces of all blocks in the chunk that aren't hidden behind an opaque neighbor.
+///
+/// Neighbors in other chunks are looked up too, so the chunks around a changed block on the border
+/// have to be rebuilt as well, see [`affected_chunks`]. Grass, leaves and water get the colors of
+/// their biome from `colors`, blocks that aren't full cubes are built from their model in
+/// [`World::models`].
+pub fn build_chunk_mesh(world: &World, chunk: Point3<i32>, colors: &BiomeColors) -> ChunkMesh {
+    let origin = chunk * CHUNK_SIZE;
+    let climate = ChunkClimate::new(world, origin);
+    let mut mesh = ChunkMesh::default();
+    for x in 0..CHUNK_SIZE {
+        for y in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let position = origin + Vector3::new(x, y, z);
+                let block = world.block(position);
+                if block.texture().is_none() {
+                    continue;
+                }
+                let vertices = match block.render_layer() {
+                    RenderLayer::Opaque | RenderLayer::Cutout => &mut mesh.opaque,
+                    RenderLayer::Translucent if block == Block::Water => &mut mesh.water,
+                    RenderLayer::Translucent => &mut mesh.translucent,
+                };
+                if let Some(model) = world.models.get(block, world.block_state(position)) {
+                    add_model(world, position, block, model, &climate, colors, vertices);
+                    continue;
+                }
+                let center = block_render_position(position);
+                // the surface heights at the corners, indexed by the sign of x and z
+                let fluid_corners = block.fluid().map(|fluid| {
+                    [[0, 0], [0, 1], [1, 0], [1, 1]].map(|[x, z]| {
+                        fluid_corner_height(world, position + Vector3::new(x, 0, z), fluid)
+                    })
+                });
+                for face in Face::ALL {
+                    let neighbor = position + face.normal();
+                    if is_face_hidden(block, face, world.block(neighbor)) {
+                        continue;
+                    }
+                    let block_type = match block.face_texture(face) {
+                        Some(texture) => texture,
+                        None => continue,
+                    };
+                    // a face is as bright as the air in front of it
+                    let light = [
+                        world.light(neighbor, LightKind::Sky) as f32 / MAX_LIGHT as f32,
+                        world.light(neighbor, LightKind::Block) as f32 / MAX_LIGHT as f32,
+                    ];
+                    let (corners, normal) = face_geometry(face);
+                    for (mut corner, tex_coord) in corners.into_iter().zip(TEX_COORDS) {
+                        // y points down, so the top corners are at -1
+                        if let (Some(heights), true) = (fluid_corners, corner[1] < 0.0) {
+                            let index = (corner[0] > 0.0) as usize * 2 + (corner[2] > 0.0) as usize;
+                            corner[1] = 1.0 - 2.0 * heights[index];
+                        }
+                        let tint = match block.tint(face) {
+                            Some(tint) => climate.tint(
+                                colors,
+                                tint,
+                                position.x + (corner[0] > 0.0) as i32,
+                                position.z + (corner[2] > 0.0) as i32,
+                            ),
+                            None => UNTINTED,
+                        };
+                        vertices.push(ChunkVertex {
+                            position: [
+                                center[0] + corner[0] * SIZE,
+                                center[1] + corner[1] * SIZE,
+                                center[2] + corner[2] * SIZE,
+                            ],
+                            normal,
+                            tex_coord,
+                            block_type,
+                            light,
+                            tint,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    mesh
+}
+
+/// Adds the quads of the model of `block` at `position` that aren't hidden by a neighbor.
+fn add_model(
+    world: &World,
+    position: Point3<i32>,
+    block: Block,
+    model: &BlockModel,
+    climate: &ChunkClimate,
+    colors: &BiomeColors,
+    vertices: &mut Vec<ChunkVertex>,
+) {
+    let center = block_render_position(position);
+    for quad in &model.quads {
+        // quads inside the block are lit by the block itself, the ones on its sides by the
+        // neighbor they face
+        let light_position = match quad.cullface {
+            Some(cullface) => {
+                let neighbor = position + cullface.normal();
+                if is_face_hidden(block, cullface, world.block(neighbor)) {
+                    continue;
+                }
+                neighbor
+            }
+            None => position,
+        };
+        let light = [
+            world.light(light_position, LightKind::Sky) as f32 / MAX_LIGHT as f32,
+            world.light(light_position, LightKind::Block) as f32 / MAX_LIGHT as f32,
+        ];
+        let tint = if quad.tinted {
+            block.tint(quad.face)
+        } else {
+            None
+        };
+        // y points down in render coordinates
+        let normal = [quad.normal[0], -quad.normal[1], quad.normal[2]];
+        // the same two triangles as `TEX_COORDS`
+        for index in [0, 1, 2, 2, 3, 0] {
+            let corner = quad.corners[index];
+            vertices.push(ChunkVertex {
+                position: [
+                    center[0] + (corner[0] * 2.0 - 1.0) * SIZE,
+                    center[1] + (1.0 - corner[1] * 2.0) * SIZE,
+                    center[2] + (corner[2] * 2.0 - 1.0) * SIZE,
+                ],
+                normal,
+                tex_coord: quad.tex_coords[index],
+                block_type: quad.texture,
+                light,
+                tint: match tint {
+                    Some(tint) => climate.tint(
+                        colors,
+                        tint,
+                        position.x + corner[0].round() as i32,
+                        position.z + corner[2].round() as i32,
+                    ),
+                    None => UNTINTED,
+                },
+            });
+        }
+    }
+}
+
+/// The chunks whose meshes can change when the block or the light at `position` changes.
+pub fn affected_chunks(position: Point3<i32>) -> Vec<Point3<i32>> {
+    let chunk = chunk_position(position);
+    let mut chunks = vec![chunk];
+    for face in Face::ALL {
+        let neighbor = chunk_position(position + face.normal());
+        if neighbor != chunk {
+            chunks.push(neighbor);
+        }
+    }
+    chunks
+}
+
+/// The outline is a little larger than the block so it isn't hidden by the block's own faces.
+const OUTLINE_SCALE: f32 = 1.005;
+
+/// The 12 edges of every box as a line list, `boxes` are in world coordinates.
+pub fn outline_vertices(boxes: &[Aabb]) -> Vec<OutlineVertex> {
+    let mut vertices = Vec::with_capacity(24 * boxes.len());
+    for aabb in boxes {
+        let min = world_to_render(aabb.min);
+        let max = world_to_render(aabb.max);
+        let center = nalgebra::center(&min, &max);
+        let half_size = (max - min) / 2.0 * OUTLINE_SCALE;
+        let corner = |x: f32, y: f32, z: f32| OutlineVertex {
+            position: (center + half_size.component_mul(&Vector3::new(x, y, z))).into(),
+        };
+        for a in [-1.0, 1.0] {
+            for b in [-1.0, 1.0] {
+                // one edge along each axis
+                vertices.extend([corner(-1.0, a, b), corner(1.0, a, b)]);
+                vertices.extend([corner(a, -1.0, b), corner(a, 1.0, b)]);
+                vertices.extend([corner(a, b, -1.0), corner(a, b, 1.0)]);
+            }
+        }
+    }
+    vertices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::render_direction_to_world;
+
+    #[test]
+    fn face_normals_are_unit_length_and_point_out_of_the_block() {
+        for face in Face::ALL {
+            let (corners, normal) = face_geometry(face);
+            let normal = Vector3::from(normal);
+            assert_eq!(normal.norm(), 1.0, "{:?}", face);
+            // render coordinates have y pointing down
+            assert_eq!(
+                render_direction_to_world(normal),
+                face.normal().cast::<f32>(),
+                "{:?}",
+                face
+            );
+            for corner in corners {
+                assert_eq!(Vector3::from(corner).dot(&normal), 1.0, "{:?}", face);
+            }
+        }
+    }
+
+    #[test]
+    fn top_faces_point_up() {
+        let (corners, normal) = face_geometry(Face::PosY);
+        assert_eq!(normal, [0.0, -1.0, 0.0]);
+        assert!(corners.iter().all(|corner| corner[1] == -1.0));
+    }
+}
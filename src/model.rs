@@ -0,0 +1,681 @@
+//! Blocks that aren't full cubes, drawn from Minecraft's block model JSON.
+//!
+//! A model is a list of elements, boxes from `from` to `to` in sixteenths of a block that can be
+//! rotated around one axis. Every face of an element shows its own part of a texture, can be left
+//! out when a neighbor covers it (`cullface`) and can be tinted with the biome color
+//! (`tintindex`). Models inherit textures and elements from their `parent` and refer to textures
+//! through `#variables`. All of this is resolved once when the models are loaded, so the mesher
+//! only copies the finished quads.
+//!
+//! Which model a block is drawn with depends on its [`BlockState`], the blockstate file of the
+//! block lists a model for each of them that can be turned around the x and y axes. `uvlock` is
+//! ignored, textures turn with the model. The boxes of the elements are the block's shape that
+//! the player collides with and that the crosshair outlines.
+
+use std::collections::{BTreeMap, HashMap};
+
+use nalgebra::{Matrix3, Point3, Rotation3, Unit, Vector3};
+use serde::Deserialize;
+
+use crate::{
+    error::{Context, PoritzCraftError, Result},
+    physics::Aabb,
+    world::{Block, BlockState, Face, Half, BLOCK_TEXTURES},
+};
+
+/// How long a chain of parents or texture variables can be, longer ones are probably a loop.
+const MAX_DEPTH: usize = 16;
+
+#[derive(Deserialize)]
+struct BlockStateFile {
+    variants: HashMap<String, Variants>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Variants {
+    One(VariantFile),
+    /// picked at random for every block, only the first one is used
+    Random(Vec<VariantFile>),
+}
+
+#[derive(Deserialize)]
+struct VariantFile {
+    model: String,
+    /// degrees around the x axis, in steps of 90
+    #[serde(default)]
+    x: i32,
+    /// degrees around the y axis after the x rotation, in steps of 90
+    #[serde(default)]
+    y: i32,
+}
+
+#[derive(Deserialize)]
+struct ModelFile {
+    parent: Option<String>,
+    #[serde(default)]
+    textures: HashMap<String, String>,
+    elements: Option<Vec<ElementFile>>,
+}
+
+#[derive(Deserialize)]
+struct ElementFile {
+    from: [f32; 3],
+    to: [f32; 3],
+    rotation: Option<RotationFile>,
+    faces: BTreeMap<String, FaceFile>,
+}
+
+#[derive(Deserialize)]
+struct RotationFile {
+    origin: [f32; 3],
+    axis: String,
+    angle: f32,
+    /// stretches the element so it still spans the whole block after being rotated
+    #[serde(default)]
+    rescale: bool,
+}
+
+#[derive(Deserialize)]
+struct FaceFile {
+    /// taken from the position of the face if missing
+    uv: Option<[f32; 4]>,
+    texture: String,
+    cullface: Option<String>,
+    /// turns the texture clockwise in steps of 90 degrees
+    #[serde(default)]
+    rotation: u32,
+    tintindex: Option<i32>,
+}
+
+/// The model files in the jar that are used by a block or are a parent of one.
+fn model_source(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "block/block" => include_str!("models/block/block.json"),
+        "block/cross" => include_str!("models/block/cross.json"),
+        "block/tinted_cross" => include_str!("models/block/tinted_cross.json"),
+        "block/slab" => include_str!("models/block/slab.json"),
+        "block/stairs" => include_str!("models/block/stairs.json"),
+        "block/poppy" => include_str!("models/block/poppy.json"),
+        "block/grass" => include_str!("models/block/grass.json"),
+        "block/stone_slab" => include_str!("models/block/stone_slab.json"),
+        "block/stone_stairs" => include_str!("models/block/stone_stairs.json"),
+        "block/slab_top" => include_str!("models/block/slab_top.json"),
+        "block/stone_slab_top" => include_str!("models/block/stone_slab_top.json"),
+        _ => return None,
+    })
+}
+
+/// The blockstate files in the jar of every block with a [`Block::blockstate`].
+fn blockstate_source(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "poppy" => include_str!("blockstates/poppy.json"),
+        "grass" => include_str!("blockstates/grass.json"),
+        "stone_slab" => include_str!("blockstates/stone_slab.json"),
+        "stone_stairs" => include_str!("blockstates/stone_stairs.json"),
+        _ => return None,
+    })
+}
+
+/// `minecraft:block/stone` and `block/stone` are the same thing.
+fn resource_name(name: &str) -> &str {
+    name.strip_prefix("minecraft:").unwrap_or(name)
+}
+
+fn model_error(message: String) -> PoritzCraftError {
+    PoritzCraftError::Failed {
+        context: "load the block models",
+        source: message.into(),
+    }
+}
+
+fn face_from_name(name: &str) -> Result<Face> {
+    match name {
+        // "bottom" is what older models call it
+        "down" | "bottom" => Ok(Face::NegY),
+        "up" => Ok(Face::PosY),
+        "north" => Ok(Face::NegZ),
+        "south" => Ok(Face::PosZ),
+        "west" => Ok(Face::NegX),
+        "east" => Ok(Face::PosX),
+        _ => Err(model_error(format!("unknown face {}", name))),
+    }
+}
+
+/// The properties blockstate files select the variant of a block in `state` by.
+fn state_properties(block: Block, state: BlockState) -> Vec<(&'static str, &'static str)> {
+    let mut properties = Vec::new();
+    if block.has_facing() {
+        let facing = match state.facing {
+            Face::NegZ => "north",
+            Face::PosX => "east",
+            Face::PosZ => "south",
+            _ => "west",
+        };
+        properties.push(("facing", facing));
+    }
+    if block.has_half() {
+        let half = match state.half {
+            Half::Bottom => "bottom",
+            Half::Top => "top",
+        };
+        // slabs call it type, and stairs are only ever straight here
+        if block.has_facing() {
+            properties.extend([("half", half), ("shape", "straight")]);
+        } else {
+            properties.push(("type", half));
+        }
+    }
+    properties
+}
+
+/// Whether a variant like `facing=east,half=bottom` is the one for `properties`.
+fn variant_matches(variant: &str, properties: &[(&str, &str)]) -> bool {
+    variant
+        .split(',')
+        .filter(|property| !property.is_empty())
+        .all(|property| match property.split_once('=') {
+            Some(pair) => properties.contains(&pair),
+            None => false,
+        })
+}
+
+/// A rotation in steps of 90 degrees, first by `x` around the x axis and then by `y` around the y
+/// axis. Positive angles turn clockwise when looking along the axis towards the origin.
+fn variant_rotation(x: i32, y: i32) -> Result<Matrix3<f32>> {
+    if x % 90 != 0 || y % 90 != 0 {
+        return Err(model_error(format!(
+            "blockstates can only turn models in steps of 90 degrees, not {} {}",
+            x, y
+        )));
+    }
+    let rotation = Rotation3::from_axis_angle(&Vector3::y_axis(), -(y as f32).to_radians())
+        * Rotation3::from_axis_angle(&Vector3::x_axis(), -(x as f32).to_radians());
+    // exact, so faces stay on the block's sides
+    Ok(rotation.into_inner().map(f32::round))
+}
+
+/// The face `face` ends up as after the model is turned by `rotation`.
+fn rotate_face(face: Face, rotation: &Matrix3<f32>) -> Face {
+    let normal = rotation * face.normal().cast::<f32>();
+    let axis = normal.iamax();
+    Face::from_axis(axis, normal[axis] > 0.0)
+}
+
+/// One side of an element.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelQuad {
+    /// in blocks from the lowest corner of the block in world coordinates, counter clockwise as
+    /// seen from the front
+    pub corners: [[f32; 3]; 4],
+    pub tex_coords: [[f32; 2]; 4],
+    /// in world coordinates
+    pub normal: [f32; 3],
+    /// the side of the element, rotated elements keep the side they had before
+    pub face: Face,
+    /// index into the texture array
+    pub texture: u32,
+    /// the quad is left out when the neighbor on this side hides it
+    pub cullface: Option<Face>,
+    /// whether the texture is multiplied with the block's [`Block::tint`]
+    pub tinted: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BlockModel {
+    pub quads: Vec<ModelQuad>,
+    /// one per element in blocks from the lowest corner of the block
+    pub boxes: Vec<Aabb>,
+}
+
+impl BlockModel {
+    /// Reads the model `name`, e.g. `block/poppy`, and everything it inherits.
+    ///
+    /// `source` returns the JSON of a model by name and `texture_index` the index of a texture
+    /// like `block/stone` in the texture array.
+    pub fn load(
+        name: &str,
+        source: impl Fn(&str) -> Option<&'static str>,
+        texture_index: impl Fn(&str) -> Option<u32>,
+    ) -> Result<Self> {
+        let mut textures = HashMap::new();
+        let mut elements = None;
+        let mut next = Some(resource_name(name).to_owned());
+        for _ in 0..MAX_DEPTH {
+            let name = match next.take() {
+                Some(name) => name,
+                None => break,
+            };
+            let json = source(&name)
+                .ok_or_else(|| model_error(format!("there is no block model {}", name)))?;
+            let file: ModelFile = serde_json::from_str(json).context("parse a block model")?;
+            // the child's textures and elements replace the ones of its parent
+            for (variable, texture) in file.textures {
+                textures.entry(variable).or_insert(texture);
+            }
+            if elements.is_none() {
+                elements = file.elements;
+            }
+            next = file.parent.map(|parent| resource_name(&parent).to_owned());
+        }
+        if let Some(parent) = next {
+            return Err(model_error(format!("{} has too many parents", parent)));
+        }
+
+        let elements = elements.unwrap_or_default();
+        let mut quads = Vec::new();
+        let mut boxes = Vec::with_capacity(elements.len());
+        for element in elements {
+            boxes.push(element_box(&element)?);
+            for (face_name, face) in &element.faces {
+                let texture_name = resolve_texture(&textures, &face.texture)?;
+                let texture = texture_index(texture_name).ok_or_else(|| {
+                    model_error(format!("the texture {} isn't loaded", texture_name))
+                })?;
+                let cullface = face.cullface.as_deref().map(face_from_name).transpose()?;
+                quads.push(element_quad(
+                    &element,
+                    face_from_name(face_name)?,
+                    face,
+                    texture,
+                    cullface,
+                )?);
+            }
+        }
+        Ok(Self { quads, boxes })
+    }
+
+    /// The model turned around the center of the block, `rotation` has to keep the sides of the
+    /// block on its sides.
+    fn rotated(&self, rotation: &Matrix3<f32>) -> Self {
+        let center = Vector3::repeat(0.5);
+        let turn = |point: [f32; 3]| -> Vector3<f32> {
+            rotation * (Vector3::from(point) - center) + center
+        };
+        let quads = self
+            .quads
+            .iter()
+            .map(|quad| ModelQuad {
+                corners: quad.corners.map(|corner| turn(corner).into()),
+                normal: (rotation * Vector3::from(quad.normal)).into(),
+                face: rotate_face(quad.face, rotation),
+                cullface: quad.cullface.map(|face| rotate_face(face, rotation)),
+                ..quad.clone()
+            })
+            .collect();
+        let boxes = self
+            .boxes
+            .iter()
+            .map(|aabb| {
+                let a = turn(aabb.min.into());
+                let b = turn(aabb.max.into());
+                Aabb::new(a.inf(&b).into(), a.sup(&b).into())
+            })
+            .collect();
+        Self { quads, boxes }
+    }
+}
+
+/// Follows `#variables` until it ends up at the name of a texture.
+fn resolve_texture<'a>(
+    textures: &'a HashMap<String, String>,
+    reference: &'a str,
+) -> Result<&'a str> {
+    let mut reference = reference;
+    for _ in 0..MAX_DEPTH {
+        match reference.strip_prefix('#') {
+            Some(variable) => {
+                reference = textures.get(variable).ok_or_else(|| {
+                    model_error(format!("the texture variable {} isn't set", variable))
+                })?;
+            }
+            None => return Ok(resource_name(reference)),
+        }
+    }
+    Err(model_error(format!("{} refers to itself", reference)))
+}
+
+/// The rotation of an element, in sixteenths of a block.
+struct ElementRotation {
+    origin: Point3<f32>,
+    turn: Rotation3<f32>,
+    scale: Vector3<f32>,
+}
+
+impl ElementRotation {
+    fn new(element: &ElementFile) -> Result<Self> {
+        let rotation = match &element.rotation {
+            Some(rotation) => rotation,
+            None => {
+                return Ok(Self {
+                    origin: Point3::origin(),
+                    turn: Rotation3::identity(),
+                    scale: Vector3::repeat(1.0),
+                })
+            }
+        };
+        let axis = match rotation.axis.as_str() {
+            "x" => 0,
+            "y" => 1,
+            "z" => 2,
+            axis => return Err(model_error(format!("unknown rotation axis {}", axis))),
+        };
+        let angle = rotation.angle.to_radians();
+        // the other two axes are stretched by as much as the rotation shortened them
+        let mut scale = Vector3::repeat(1.0);
+        if rotation.rescale {
+            scale = Vector3::repeat(1.0 / angle.cos());
+            scale[axis] = 1.0;
+        }
+        Ok(Self {
+            origin: Point3::from(rotation.origin),
+            turn: Rotation3::from_axis_angle(&Unit::new_unchecked(Vector3::ith(axis, 1.0)), angle),
+            scale,
+        })
+    }
+
+    fn apply(&self, point: Point3<f32>) -> Point3<f32> {
+        self.origin + (self.turn * (point - self.origin)).component_mul(&self.scale)
+    }
+}
+
+/// The box around the element in blocks.
+fn element_box(element: &ElementFile) -> Result<Aabb> {
+    let rotation = ElementRotation::new(element)?;
+    let mut min = Point3::from(Vector3::repeat(f32::INFINITY));
+    let mut max = Point3::from(Vector3::repeat(f32::NEG_INFINITY));
+    for corner in 0..8 {
+        let point = Point3::from(Vector3::from_fn(|axis, _| {
+            if corner & (1 << axis) == 0 {
+                element.from[axis]
+            } else {
+                element.to[axis]
+            }
+        }));
+        let point = rotation.apply(point) / 16.0;
+        min = min.inf(&point);
+        max = max.sup(&point);
+    }
+    Ok(Aabb::new(min, max))
+}
+
+/// The quad of one face of an element.
+fn element_quad(
+    element: &ElementFile,
+    face: Face,
+    face_file: &FaceFile,
+    texture: u32,
+    cullface: Option<Face>,
+) -> Result<ModelQuad> {
+    let [x0, y0, z0] = element.from;
+    let [x1, y1, z1] = element.to;
+    // the corners go top left, bottom left, bottom right, top right seen from the front, the
+    // default texture coordinates are where the face is on the block
+    let (corners, uv) = match face {
+        Face::PosZ => (
+            [[x0, y1, z1], [x0, y0, z1], [x1, y0, z1], [x1, y1, z1]],
+            [x0, 16.0 - y1, x1, 16.0 - y0],
+        ),
+        Face::NegZ => (
+            [[x1, y1, z0], [x1, y0, z0], [x0, y0, z0], [x0, y1, z0]],
+            [16.0 - x1, 16.0 - y1, 16.0 - x0, 16.0 - y0],
+        ),
+        Face::PosX => (
+            [[x1, y1, z1], [x1, y0, z1], [x1, y0, z0], [x1, y1, z0]],
+            [16.0 - z1, 16.0 - y1, 16.0 - z0, 16.0 - y0],
+        ),
+        Face::NegX => (
+            [[x0, y1, z0], [x0, y0, z0], [x0, y0, z1], [x0, y1, z1]],
+            [z0, 16.0 - y1, z1, 16.0 - y0],
+        ),
+        // seen from above with north at the top
+        Face::PosY => (
+            [[x0, y1, z0], [x0, y1, z1], [x1, y1, z1], [x1, y1, z0]],
+            [x0, z0, x1, z1],
+        ),
+        // seen from below with south at the top
+        Face::NegY => (
+            [[x0, y0, z1], [x0, y0, z0], [x1, y0, z0], [x1, y0, z1]],
+            [x0, 16.0 - z1, x1, 16.0 - z0],
+        ),
+    };
+    let [u0, v0, u1, v1] = face_file.uv.unwrap_or(uv);
+    let uv_corners = [[u0, v0], [u0, v1], [u1, v1], [u1, v0]];
+    let turns = match face_file.rotation {
+        0 => 0,
+        90 => 1,
+        180 => 2,
+        270 => 3,
+        rotation => {
+            return Err(model_error(format!(
+                "faces can only be turned in steps of 90 degrees, not {}",
+                rotation
+            )))
+        }
+    };
+    let tex_coords = [0, 1, 2, 3].map(|corner| uv_corners[(corner + turns) % 4].map(|c| c / 16.0));
+
+    let rotation = ElementRotation::new(element)?;
+    let corners = corners.map(|corner| rotation.apply(Point3::from(corner)));
+    let normal = rotation.turn * face.normal().map(|c| c as f32);
+
+    Ok(ModelQuad {
+        corners: corners.map(|corner| (corner / 16.0).coords.into()),
+        tex_coords,
+        normal: normal.into(),
+        face,
+        texture,
+        cullface,
+        tinted: face_file.tintindex.is_some(),
+    })
+}
+
+/// The models of all blocks that aren't full cubes in every state they can be in.
+#[derive(Clone, Debug, Default)]
+pub struct BlockModels {
+    models: HashMap<(Block, BlockState), BlockModel>,
+}
+
+impl BlockModels {
+    /// Loads the models of every block with a [`Block::blockstate`].
+    pub fn load() -> Result<Self> {
+        let texture_index = |name: &str| {
+            BLOCK_TEXTURES
+                .iter()
+                .position(|texture| *texture == name)
+                .map(|index| index as u32)
+        };
+        let mut loaded: HashMap<String, BlockModel> = HashMap::new();
+        let mut models = HashMap::new();
+        for block in Block::ALL {
+            let name = match block.blockstate() {
+                Some(name) => name,
+                None => continue,
+            };
+            let json = blockstate_source(name)
+                .ok_or_else(|| model_error(format!("there is no blockstate file {}", name)))?;
+            let file: BlockStateFile =
+                serde_json::from_str(json).context("parse a blockstate file")?;
+            for state in block.states() {
+                let properties = state_properties(block, state);
+                let variant = file
+                    .variants
+                    .iter()
+                    .find(|(variant, _)| variant_matches(variant, &properties))
+                    .and_then(|(_, variants)| match variants {
+                        Variants::One(variant) => Some(variant),
+                        Variants::Random(variants) => variants.first(),
+                    })
+                    .ok_or_else(|| model_error(format!("{} has no model for {:?}", name, state)))?;
+                let model_name = resource_name(&variant.model).to_owned();
+                if !loaded.contains_key(&model_name) {
+                    let model = BlockModel::load(&model_name, model_source, texture_index)?;
+                    loaded.insert(model_name.clone(), model);
+                }
+                let rotation = variant_rotation(variant.x, variant.y)?;
+                models.insert((block, state), loaded[&model_name].rotated(&rotation));
+            }
+        }
+        Ok(Self { models })
+    }
+
+    pub fn get(&self, block: Block, state: BlockState) -> Option<&BlockModel> {
+        self.models.get(&(block, state))
+    }
+
+    /// The boxes of every model, for the [`World`](crate::world::World) to collide with.
+    pub fn shapes(&self) -> BlockShapes {
+        BlockShapes {
+            boxes: self
+                .models
+                .iter()
+                .map(|(key, model)| (*key, model.boxes.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// The boxes of blocks that aren't full cubes, without the rest of their model.
+#[derive(Clone, Debug, Default)]
+pub struct BlockShapes {
+    boxes: HashMap<(Block, BlockState), Vec<Aabb>>,
+}
+
+impl BlockShapes {
+    /// In blocks from the lowest corner of the block, `None` for full cubes.
+    pub fn get(&self, block: Block, state: BlockState) -> Option<&[Aabb]> {
+        self.boxes.get(&(block, state)).map(Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boxes(models: &BlockModels, block: Block, facing: Face, half: Half) -> Vec<Aabb> {
+        models
+            .get(block, block.state(facing, half))
+            .unwrap()
+            .boxes
+            .clone()
+    }
+
+    fn aabb(min: [f32; 3], max: [f32; 3]) -> Aabb {
+        Aabb::new(Point3::from(min), Point3::from(max))
+    }
+
+    #[test]
+    fn every_state_has_a_model() {
+        let models = BlockModels::load().unwrap();
+        for block in Block::ALL {
+            for state in block.states() {
+                assert_eq!(
+                    models.get(block, state).is_some(),
+                    block.blockstate().is_some(),
+                    "{:?} {:?}",
+                    block,
+                    state
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn slabs_fill_their_half() {
+        let models = BlockModels::load().unwrap();
+        let bottom = boxes(&models, Block::StoneSlab, Face::NegZ, Half::Bottom);
+        assert_eq!(bottom, [aabb([0.0; 3], [1.0, 0.5, 1.0])]);
+        let top = boxes(&models, Block::StoneSlab, Face::NegZ, Half::Top);
+        assert_eq!(top, [aabb([0.0, 0.5, 0.0], [1.0; 3])]);
+        // slabs don't turn, placing them in any direction gives the same state
+        assert_eq!(
+            Block::StoneSlab.state(Face::PosX, Half::Top),
+            Block::StoneSlab.state(Face::NegZ, Half::Top)
+        );
+    }
+
+    #[test]
+    fn stairs_step_up_towards_their_facing() {
+        let models = BlockModels::load().unwrap();
+        for (facing, step) in [
+            (Face::PosX, aabb([0.5, 0.5, 0.0], [1.0, 1.0, 1.0])),
+            (Face::PosZ, aabb([0.0, 0.5, 0.5], [1.0, 1.0, 1.0])),
+            (Face::NegX, aabb([0.0, 0.5, 0.0], [0.5, 1.0, 1.0])),
+            (Face::NegZ, aabb([0.0, 0.5, 0.0], [1.0, 1.0, 0.5])),
+        ] {
+            let boxes = boxes(&models, Block::StoneStairs, facing, Half::Bottom);
+            assert_eq!(boxes[0], aabb([0.0; 3], [1.0, 0.5, 1.0]), "{:?}", facing);
+            assert_eq!(boxes[1], step, "{:?}", facing);
+        }
+    }
+
+    #[test]
+    fn upside_down_stairs_hang_from_the_top() {
+        let models = BlockModels::load().unwrap();
+        let boxes = boxes(&models, Block::StoneStairs, Face::PosZ, Half::Top);
+        assert_eq!(boxes[0], aabb([0.0, 0.5, 0.0], [1.0; 3]));
+        assert_eq!(boxes[1], aabb([0.0, 0.0, 0.5], [1.0, 0.5, 1.0]));
+    }
+
+    #[test]
+    fn turned_quads_keep_facing_out_of_their_side() {
+        let models = BlockModels::load().unwrap();
+        for state in Block::StoneStairs.states() {
+            let model = models.get(Block::StoneStairs, state).unwrap();
+            for quad in &model.quads {
+                let normal = Vector3::from(quad.normal);
+                assert_eq!(normal, quad.face.normal().cast(), "{:?}", state);
+                // the corners lie on the side of their element that the face names
+                let axis = normal.iamax();
+                let side = quad.corners[0][axis];
+                assert!(quad.corners.iter().all(|corner| corner[axis] == side));
+                let element = model
+                    .boxes
+                    .iter()
+                    .any(|aabb| if normal[axis] > 0.0 { aabb.max } else { aabb.min }[axis] == side);
+                assert!(element, "{:?} {:?}", state, quad.face);
+                if let Some(cullface) = quad.cullface {
+                    assert_eq!(cullface, quad.face, "{:?}", state);
+                }
+            }
+        }
+        // the bottom of upside down stairs is their step and is culled by nothing
+        let model = models
+            .get(
+                Block::StoneStairs,
+                Block::StoneStairs.state(Face::PosX, Half::Top),
+            )
+            .unwrap();
+        let culled_down = model
+            .quads
+            .iter()
+            .filter(|quad| quad.cullface == Some(Face::NegY))
+            .count();
+        assert_eq!(culled_down, 1);
+    }
+
+    #[test]
+    fn variants_match_all_of_their_properties() {
+        let properties = state_properties(
+            Block::StoneStairs,
+            Block::StoneStairs.state(Face::PosZ, Half::Top),
+        );
+        assert!(variant_matches(
+            "facing=south,half=top,shape=straight",
+            &properties
+        ));
+        assert!(!variant_matches(
+            "facing=south,half=top,shape=inner_left",
+            &properties
+        ));
+        assert!(!variant_matches(
+            "facing=north,half=top,shape=straight",
+            &properties
+        ));
+        assert!(variant_matches(
+            "",
+            &state_properties(Block::Poppy, BlockState::default())
+        ));
+        assert!(variant_rotation(45, 0).is_err());
+    }
+}
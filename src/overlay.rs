@@ -0,0 +1,213 @@
+//! The 2D HUD drawn on top of the world.
+
+use crate::{
+    hotbar::{Hotbar, HOTBAR_SLOTS},
+    text::{self, GLYPH_HEIGHT},
+    utils::OverlayVertex,
+};
+
+const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 0.9];
+const SLOT_BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+const SLOT_BORDER: [f32; 4] = [0.3, 0.3, 0.3, 0.8];
+
+// sizes in unscaled pixels
+const SLOT_SIZE: f32 = 20.0;
+const ICON_SIZE: f32 = 14.0;
+const BORDER: f32 = 1.0;
+const CROSSHAIR_LENGTH: f32 = 9.0;
+const CROSSHAIR_WIDTH: f32 = 1.0;
+const HOTBAR_MARGIN: f32 = 2.0;
+const DEBUG_MARGIN: f32 = 2.0;
+const DEBUG_BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.4];
+const CONSOLE_BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+
+/// Collects the quads of the overlay as triangle lists.
+#[derive(Default)]
+pub struct OverlayBuilder {
+    pub vertices: Vec<OverlayVertex>,
+}
+
+impl OverlayBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a quad showing the whole texture, `texture` is `None` for a plain colored one.
+    pub fn quad(&mut self, min: [f32; 2], max: [f32; 2], color: [f32; 4], texture: Option<u32>) {
+        self.push_quad(
+            min,
+            max,
+            [0.0, 0.0],
+            [1.0, 1.0],
+            color,
+            texture.map_or(-1, |t| t as i32),
+        );
+    }
+
+    /// Adds a quad showing the part of the texture between `tex_min` and `tex_max`.
+    pub fn textured_quad(
+        &mut self,
+        min: [f32; 2],
+        max: [f32; 2],
+        tex_min: [f32; 2],
+        tex_max: [f32; 2],
+        color: [f32; 4],
+        texture: u32,
+    ) {
+        self.push_quad(min, max, tex_min, tex_max, color, texture as i32);
+    }
+
+    fn push_quad(
+        &mut self,
+        min: [f32; 2],
+        max: [f32; 2],
+        tex_min: [f32; 2],
+        tex_max: [f32; 2],
+        color: [f32; 4],
+        texture: i32,
+    ) {
+        let vertex = |x: f32, y: f32, u: f32, v: f32| OverlayVertex {
+            position: [x, y],
+            tex_coord: [u, v],
+            color,
+            texture,
+        };
+        self.vertices.extend([
+            vertex(min[0], min[1], tex_min[0], tex_min[1]),
+            vertex(min[0], max[1], tex_min[0], tex_max[1]),
+            vertex(max[0], max[1], tex_max[0], tex_max[1]),
+            vertex(max[0], max[1], tex_max[0], tex_max[1]),
+            vertex(max[0], min[1], tex_max[0], tex_min[1]),
+            vertex(min[0], min[1], tex_min[0], tex_min[1]),
+        ]);
+    }
+
+    /// A frame of `width` pixels along the inside of the rectangle.
+    pub fn frame(&mut self, min: [f32; 2], max: [f32; 2], width: f32, color: [f32; 4]) {
+        self.quad(min, [max[0], min[1] + width], color, None);
+        self.quad([min[0], max[1] - width], max, color, None);
+        self.quad(
+            [min[0], min[1] + width],
+            [min[0] + width, max[1] - width],
+            color,
+            None,
+        );
+        self.quad(
+            [max[0] - width, min[1] + width],
+            [max[0], max[1] - width],
+            color,
+            None,
+        );
+    }
+}
+
+/// How much the HUD is enlarged so it stays readable on large windows.
+pub fn gui_scale(extent: [u32; 2]) -> f32 {
+    (extent[0] / 320).min(extent[1] / 240).max(1) as f32
+}
+
+pub fn crosshair(builder: &mut OverlayBuilder, extent: [u32; 2]) {
+    let scale = gui_scale(extent);
+    let center = [(extent[0] / 2) as f32, (extent[1] / 2) as f32];
+    let half_length = CROSSHAIR_LENGTH * scale / 2.0;
+    let half_width = CROSSHAIR_WIDTH * scale / 2.0;
+    builder.quad(
+        [center[0] - half_length, center[1] - half_width],
+        [center[0] + half_length, center[1] + half_width],
+        WHITE,
+        None,
+    );
+    builder.quad(
+        [center[0] - half_width, center[1] - half_length],
+        [center[0] + half_width, center[1] + half_length],
+        WHITE,
+        None,
+    );
+}
+
+/// The slots centered at the bottom of the screen with the icon of every block in it.
+///
+/// `frame_counts` are the number of animation frames in each texture, icons show the first one.
+pub fn hotbar(
+    builder: &mut OverlayBuilder,
+    extent: [u32; 2],
+    hotbar: &Hotbar,
+    frame_counts: &[u32],
+) {
+    let scale = gui_scale(extent);
+    let slot_size = SLOT_SIZE * scale;
+    let left = ((extent[0] as f32 - slot_size * HOTBAR_SLOTS as f32) / 2.0).floor();
+    let top = extent[1] as f32 - slot_size - HOTBAR_MARGIN * scale;
+
+    for (index, block) in hotbar.slots.iter().enumerate() {
+        let min = [left + index as f32 * slot_size, top];
+        let max = [min[0] + slot_size, min[1] + slot_size];
+        builder.quad(min, max, SLOT_BACKGROUND, None);
+        builder.frame(min, max, BORDER * scale, SLOT_BORDER);
+
+        if let Some(texture) = block.texture() {
+            let inset = (slot_size - ICON_SIZE * scale) / 2.0;
+            let frames = frame_counts.get(texture as usize).copied().unwrap_or(1);
+            builder.textured_quad(
+                [min[0] + inset, min[1] + inset],
+                [max[0] - inset, max[1] - inset],
+                [0.0, 0.0],
+                [1.0, 1.0 / frames as f32],
+                [1.0, 1.0, 1.0, 1.0],
+                texture,
+            );
+        }
+    }
+
+    let selected_min = [left + hotbar.selected as f32 * slot_size, top];
+    let selected_max = [selected_min[0] + slot_size, selected_min[1] + slot_size];
+    builder.frame(
+        [selected_min[0] - scale, selected_min[1] - scale],
+        [selected_max[0] + scale, selected_max[1] + scale],
+        2.0 * scale,
+        WHITE,
+    );
+}
+
+/// Lines of text in the top left corner, each on a dark background.
+pub fn debug_screen(builder: &mut OverlayBuilder, extent: [u32; 2], lines: &[String]) {
+    // text is half as large as the rest of the HUD because the font has twice the resolution
+    let scale = (gui_scale(extent) / 2.0).ceil();
+    let margin = DEBUG_MARGIN * scale;
+    for (index, line) in lines.iter().enumerate() {
+        let position = [
+            margin,
+            margin + index as f32 * (GLYPH_HEIGHT * scale + margin),
+        ];
+        let size = text::text_size(line, scale);
+        if size[0] > 0.0 {
+            builder.quad(
+                [position[0] - margin / 2.0, position[1]],
+                [position[0] + size[0] + margin / 2.0, position[1] + size[1]],
+                DEBUG_BACKGROUND,
+                None,
+            );
+        }
+        text::draw_text_with_shadow(builder, position, line, scale, [1.0, 1.0, 1.0, 1.0]);
+    }
+}
+
+/// The console output with the input line at the bottom, just above the hotbar.
+pub fn console(builder: &mut OverlayBuilder, extent: [u32; 2], lines: &[String]) {
+    let scale = (gui_scale(extent) / 2.0).ceil();
+    let line_height = GLYPH_HEIGHT * scale;
+    let margin = DEBUG_MARGIN * scale;
+    let bottom = extent[1] as f32 - (SLOT_SIZE + 2.0 * HOTBAR_MARGIN) * gui_scale(extent);
+    let top = bottom - lines.len() as f32 * line_height - margin;
+
+    builder.quad(
+        [0.0, top],
+        [extent[0] as f32 / 2.0, bottom],
+        CONSOLE_BACKGROUND,
+        None,
+    );
+    for (index, line) in lines.iter().enumerate() {
+        let position = [margin, top + margin / 2.0 + index as f32 * line_height];
+        text::draw_text_with_shadow(builder, position, line, scale, [1.0, 1.0, 1.0, 1.0]);
+    }
+}
@@ -0,0 +1,19 @@
+#version 450
+
+#extension GL_EXT_nonuniform_qualifier : enable
+
+layout(location = 0) in vec2 v_tex_coord;
+layout(location = 1) in vec4 v_color;
+layout(location = 2) in flat int v_texture;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D tex[];
+
+void main() {
+    if (v_texture < 0) {
+        f_color = v_color;
+    } else {
+        f_color = texture(tex[nonuniformEXT(v_texture)], v_tex_coord) * v_color;
+    }
+}
@@ -0,0 +1,22 @@
+#version 450
+
+// in pixels from the top left corner
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec2 tex_coord;
+layout(location = 2) in vec4 color;
+layout(location = 3) in int texture;
+
+layout(location = 0) out vec2 v_tex_coord;
+layout(location = 1) out vec4 v_color;
+layout(location = 2) out flat int v_texture;
+
+layout(push_constant) uniform PushConstants {
+    vec2 screen_size;
+} push_constants;
+
+void main() {
+    gl_Position = vec4(position / push_constants.screen_size * 2.0 - 1.0, 0.0, 1.0);
+    v_tex_coord = tex_coord;
+    v_color = color;
+    v_texture = texture;
+}
@@ -0,0 +1,498 @@
+use std::time::Duration;
+
+use nalgebra::{Point3, Vector3};
+
+use crate::world::World;
+
+/// Physics always advances in steps of this length so the result doesn't depend on the frame rate.
+pub const TICK: f32 = 1.0 / 60.0;
+
+pub const PLAYER_WIDTH: f32 = 0.6;
+pub const PLAYER_HEIGHT: f32 = 1.8;
+pub const EYE_HEIGHT: f32 = 1.62;
+
+// everything is measured in blocks and seconds
+pub const GRAVITY: f32 = 32.0;
+pub const TERMINAL_VELOCITY: f32 = 78.0;
+pub const JUMP_VELOCITY: f32 = 9.0;
+pub const WALK_SPEED: f32 = 4.3;
+pub const SPRINT_MULTIPLIER: f32 = 1.3;
+pub const SNEAK_MULTIPLIER: f32 = 0.3;
+/// Ledges up to this height are climbed without jumping, a slab but not a full block.
+pub const STEP_HEIGHT: f32 = 0.6;
+
+/// Keeps boxes from ending up exactly on a block boundary where rounding could let them sink in.
+const EPSILON: f32 = 1e-4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Point3<f32>, max: Point3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    pub fn block(position: Point3<i32>) -> Self {
+        let min = position.cast::<f32>();
+        Self::new(min, min + Vector3::repeat(1.0))
+    }
+
+    pub fn translated(&self, offset: Vector3<f32>) -> Self {
+        Self::new(self.min + offset, self.max + offset)
+    }
+
+    /// Grows the box in the direction of `offset`, covering everything it passes while moving.
+    pub fn expanded(&self, offset: Vector3<f32>) -> Self {
+        Self::new(
+            self.min + offset.inf(&Vector3::zeros()),
+            self.max + offset.sup(&Vector3::zeros()),
+        )
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.min[axis] < other.max[axis] && self.max[axis] > other.min[axis])
+    }
+
+    /// Shortens the movement of `self` along `axis` so it stops at `other`.
+    fn clip_axis(&self, other: &Aabb, axis: usize, offset: f32) -> f32 {
+        let overlaps_other_axes = (0..3)
+            .filter(|&a| a != axis)
+            .all(|a| self.min[a] < other.max[a] && self.max[a] > other.min[a]);
+        if !overlaps_other_axes {
+            return offset;
+        }
+        if offset > 0.0 && self.max[axis] <= other.min[axis] {
+            offset
+                .min(other.min[axis] - self.max[axis] - EPSILON)
+                .max(0.0)
+        } else if offset < 0.0 && self.min[axis] >= other.max[axis] {
+            offset
+                .max(other.max[axis] - self.min[axis] + EPSILON)
+                .min(0.0)
+        } else {
+            offset
+        }
+    }
+}
+
+/// The boxes of the solid blocks that touch `aabb`.
+pub fn colliding_blocks(world: &World, aabb: &Aabb) -> Vec<Aabb> {
+    let min = aabb.min.map(|c| c.floor() as i32);
+    let max = aabb.max.map(|c| c.ceil() as i32);
+    let mut blocks = Vec::new();
+    for x in min.x..max.x {
+        for y in min.y..max.y {
+            for z in min.z..max.z {
+                let position = Point3::new(x, y, z);
+                if world.is_solid(position) {
+                    let boxes = world.block_boxes(position);
+                    blocks.extend(boxes.into_iter().filter(|block| block.intersects(aabb)));
+                }
+            }
+        }
+    }
+    blocks
+}
+
+/// Moves the box by as much of `offset` as possible without entering a solid block,
+/// resolving one axis after the other. Returns the movement that actually happened.
+pub fn sweep(world: &World, aabb: &Aabb, offset: Vector3<f32>) -> Vector3<f32> {
+    let obstacles = colliding_blocks(world, &aabb.expanded(offset));
+    let mut moved = *aabb;
+    let mut result = Vector3::zeros();
+    // vertical first so walking off a ledge and landing resolve the same way every time
+    for axis in [1, 0, 2] {
+        let mut axis_offset = offset[axis];
+        for obstacle in &obstacles {
+            axis_offset = moved.clip_axis(obstacle, axis, axis_offset);
+        }
+        let mut axis_vector = Vector3::zeros();
+        axis_vector[axis] = axis_offset;
+        moved = moved.translated(axis_vector);
+        result[axis] = axis_offset;
+    }
+    result
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovementMode {
+    Walking,
+    /// flies through blocks without gravity, this is the old camera behavior
+    Noclip,
+}
+
+/// What the player wants to do during a tick.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PlayerInput {
+    /// desired horizontal movement in world space, at most 1 long
+    pub movement: Vector3<f32>,
+    pub jump: bool,
+    pub sneak: bool,
+    pub sprint: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Player {
+    /// center of the bottom of the bounding box in world coordinates
+    pub position: Point3<f32>,
+    pub velocity: Vector3<f32>,
+    pub on_ground: bool,
+    pub mode: MovementMode,
+    accumulated: f32,
+}
+
+impl Player {
+    pub fn new(position: Point3<f32>) -> Self {
+        Self {
+            position,
+            velocity: Vector3::zeros(),
+            on_ground: false,
+            mode: MovementMode::Walking,
+            accumulated: 0.0,
+        }
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        let half_width = PLAYER_WIDTH / 2.0;
+        Aabb::new(
+            self.position + Vector3::new(-half_width, 0.0, -half_width),
+            self.position + Vector3::new(half_width, PLAYER_HEIGHT, half_width),
+        )
+    }
+
+    pub fn eye_position(&self) -> Point3<f32> {
+        self.position + Vector3::new(0.0, EYE_HEIGHT, 0.0)
+    }
+
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            MovementMode::Walking => MovementMode::Noclip,
+            MovementMode::Noclip => MovementMode::Walking,
+        };
+        self.velocity = Vector3::zeros();
+        self.on_ground = false;
+    }
+
+    /// Runs as many fixed ticks as fit into the elapsed time, the rest carries over to the next call.
+    pub fn update(&mut self, world: &World, input: &PlayerInput, delta: Duration) {
+        self.accumulated += delta.as_secs_f32();
+        // don't try to catch up after a long stall, e.g. while the window was dragged
+        self.accumulated = self.accumulated.min(TICK * 10.0);
+        while self.accumulated >= TICK {
+            self.tick(world, input);
+            self.accumulated -= TICK;
+        }
+    }
+
+    /// Advances the simulation by exactly one [`TICK`].
+    pub fn tick(&mut self, world: &World, input: &PlayerInput) {
+        if self.mode == MovementMode::Noclip {
+            return;
+        }
+
+        let mut speed = WALK_SPEED;
+        if input.sprint {
+            speed *= SPRINT_MULTIPLIER;
+        }
+        if input.sneak {
+            speed *= SNEAK_MULTIPLIER;
+        }
+        self.velocity.x = input.movement.x * speed;
+        self.velocity.z = input.movement.z * speed;
+
+        if input.jump && self.on_ground {
+            self.velocity.y = JUMP_VELOCITY;
+        }
+        self.velocity.y = (self.velocity.y - GRAVITY * TICK).max(-TERMINAL_VELOCITY);
+
+        let offset = self.velocity * TICK;
+        let aabb = self.aabb();
+        let mut moved = sweep(world, &aabb, offset);
+
+        let blocked_horizontally = moved.x != offset.x || moved.z != offset.z;
+        if blocked_horizontally && (self.on_ground || moved.y != offset.y && offset.y < 0.0) {
+            if let Some(stepped) = self.try_step_up(world, &aabb, offset) {
+                moved = stepped;
+            }
+        }
+
+        self.on_ground = offset.y < 0.0 && moved.y > offset.y;
+        if moved.y != offset.y {
+            self.velocity.y = 0.0;
+        }
+        if moved.x != offset.x {
+            self.velocity.x = 0.0;
+        }
+        if moved.z != offset.z {
+            self.velocity.z = 0.0;
+        }
+        self.position += moved;
+    }
+
+    /// Moves up by at most [`STEP_HEIGHT`], then horizontally and back down again.
+    /// Only used if that gets the player further than walking into the obstacle.
+    fn try_step_up(
+        &self,
+        world: &World,
+        aabb: &Aabb,
+        offset: Vector3<f32>,
+    ) -> Option<Vector3<f32>> {
+        let up = sweep(world, aabb, Vector3::new(0.0, STEP_HEIGHT, 0.0));
+        let raised = aabb.translated(up);
+        let horizontal = sweep(world, &raised, Vector3::new(offset.x, 0.0, offset.z));
+        let moved_horizontal = raised.translated(horizontal);
+        let down = sweep(
+            world,
+            &moved_horizontal,
+            Vector3::new(0.0, -up.y + offset.y.min(0.0), 0.0),
+        );
+
+        let direct = sweep(world, aabb, offset);
+        let stepped = up + horizontal + down;
+        let distance = |v: Vector3<f32>| v.x * v.x + v.z * v.z;
+        if distance(stepped) > distance(direct) + EPSILON {
+            Some(stepped)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        model::BlockModels,
+        world::{Block, Face, Half},
+    };
+
+    /// A stone floor whose top is at y = 1, around the origin.
+    fn floor() -> World {
+        let mut world = World::new();
+        for x in -8..8 {
+            for z in -8..8 {
+                world.set_block(Point3::new(x, 0, z), Block::Stone);
+            }
+        }
+        world
+    }
+
+    fn standing_at(x: f32, z: f32) -> Player {
+        let mut player = Player::new(Point3::new(x, 1.0, z));
+        player.on_ground = true;
+        player
+    }
+
+    fn walk(world: &World, player: &mut Player, movement: Vector3<f32>, ticks: u32) {
+        let input = PlayerInput {
+            movement,
+            ..Default::default()
+        };
+        for _ in 0..ticks {
+            player.tick(world, &input);
+            assert!(
+                colliding_blocks(world, &player.aabb()).is_empty(),
+                "the player ended up inside a block at {:?}",
+                player.position
+            );
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 0.01,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn falling_players_land_on_the_floor() {
+        let world = floor();
+        let mut player = Player::new(Point3::new(0.5, 4.0, 0.5));
+        walk(&world, &mut player, Vector3::zeros(), 60);
+        assert_close(player.position.y, 1.0);
+        assert!(player.on_ground);
+        assert_eq!(player.velocity.y, 0.0);
+    }
+
+    #[test]
+    fn walls_stop_the_player_on_every_axis() {
+        let mut world = floor();
+        for (x, z) in [(3, 0), (-3, 0), (0, 3), (0, -3)] {
+            // two blocks high, too high to step onto
+            world.set_block(Point3::new(x, 1, z), Block::Stone);
+            world.set_block(Point3::new(x, 2, z), Block::Stone);
+        }
+        let half_width = PLAYER_WIDTH / 2.0;
+        for (direction, axis, stop) in [
+            (Vector3::x(), 0, 3.0 - half_width),
+            (-Vector3::x(), 0, -2.0 + half_width),
+            (Vector3::z(), 2, 3.0 - half_width),
+            (-Vector3::z(), 2, -2.0 + half_width),
+        ] {
+            let mut player = standing_at(0.5, 0.5);
+            walk(&world, &mut player, direction, 120);
+            assert_close(player.position[axis], stop);
+            assert_eq!(player.velocity[axis], 0.0);
+            assert_close(player.position.y, 1.0);
+            assert!(player.on_ground);
+        }
+    }
+
+    #[test]
+    fn full_blocks_need_a_jump() {
+        let mut world = floor();
+        world.set_block(Point3::new(2, 1, 0), Block::Stone);
+        let mut player = standing_at(0.5, 0.5);
+        walk(&world, &mut player, Vector3::x(), 30);
+        assert_close(player.position.x, 2.0 - PLAYER_WIDTH / 2.0);
+        assert_close(player.position.y, 1.0);
+        assert!(player.on_ground);
+    }
+
+    #[test]
+    fn jumping_gets_onto_a_block() {
+        let mut world = floor();
+        for x in 2..6 {
+            world.set_block(Point3::new(x, 1, 0), Block::Stone);
+        }
+        let mut player = standing_at(0.5, 0.5);
+        let input = PlayerInput {
+            movement: Vector3::x(),
+            jump: true,
+            ..Default::default()
+        };
+        player.tick(&world, &input);
+        walk(&world, &mut player, Vector3::x(), 40);
+        assert_close(player.position.y, 2.0);
+        assert!(player.position.x > 2.0);
+        assert!(player.on_ground);
+    }
+
+    #[test]
+    fn players_slide_past_corners() {
+        let mut world = floor();
+        world.set_block(Point3::new(2, 1, 2), Block::Stone);
+        world.set_block(Point3::new(2, 2, 2), Block::Stone);
+        // heading straight for the corner of the pillar
+        let mut player = standing_at(1.0, 1.0);
+        let direction = Vector3::new(1.0, 0.0, 1.0).normalize();
+        walk(&world, &mut player, direction, 60);
+        // one axis is stopped by the pillar while the other one slides along it and past it
+        let past = 3.0 + PLAYER_WIDTH / 2.0;
+        assert!(
+            player.position.x > past || player.position.z > past,
+            "the player should get around the pillar, ended at {:?}",
+            player.position
+        );
+        assert_close(player.position.y, 1.0);
+    }
+
+    #[test]
+    fn players_stand_on_the_top_of_slabs() {
+        let mut world = floor();
+        world.set_models(BlockModels::load().unwrap());
+        let bottom = Block::StoneSlab.state(Face::NegZ, Half::Bottom);
+        let top = Block::StoneSlab.state(Face::NegZ, Half::Top);
+        world.set_block_with_state(Point3::new(0, 1, 0), Block::StoneSlab, top);
+        world.set_block_with_state(Point3::new(2, 1, 3), Block::StoneSlab, bottom);
+
+        // the upper half of the block
+        let mut player = Player::new(Point3::new(0.5, 4.0, 0.5));
+        walk(&world, &mut player, Vector3::zeros(), 60);
+        assert_close(player.position.y, 2.0);
+        assert!(player.on_ground);
+
+        // the lower half is stepped onto and walked off again
+        let mut player = standing_at(0.5, 3.5);
+        walk(&world, &mut player, Vector3::x(), 28);
+        assert!((2.0..3.0).contains(&player.position.x));
+        assert_close(player.position.y, 1.5);
+        walk(&world, &mut player, Vector3::x(), 30);
+        assert_close(player.position.y, 1.0);
+    }
+
+    #[test]
+    fn ceilings_stop_jumps() {
+        let mut world = floor();
+        // the player's head is at 2.8, so there's 0.2 to go
+        world.set_block(Point3::new(0, 3, 0), Block::Stone);
+        let mut player = standing_at(0.5, 0.5);
+        let jump = PlayerInput {
+            jump: true,
+            ..Default::default()
+        };
+        player.tick(&world, &jump);
+        let mut highest = player.position.y;
+        for _ in 0..60 {
+            player.tick(&world, &PlayerInput::default());
+            highest = highest.max(player.position.y);
+            assert!(player.aabb().max.y <= 3.0);
+        }
+        assert!(highest <= 3.0 - PLAYER_HEIGHT);
+        assert!(highest > 1.1);
+        // and then falls back down
+        assert_close(player.position.y, 1.0);
+        assert!(player.on_ground);
+    }
+
+    #[test]
+    fn jumps_without_a_ceiling_clear_a_block() {
+        let world = floor();
+        let mut player = standing_at(0.5, 0.5);
+        let jump = PlayerInput {
+            jump: true,
+            ..Default::default()
+        };
+        let mut highest = player.position.y;
+        for _ in 0..60 {
+            player.tick(&world, &jump);
+            highest = highest.max(player.position.y);
+        }
+        assert!(highest > 2.0, "jumped to {}", highest);
+    }
+
+    #[test]
+    fn noclip_passes_through_blocks() {
+        let mut world = floor();
+        world.set_block(Point3::new(0, 1, 0), Block::Stone);
+        world.set_block(Point3::new(0, 2, 0), Block::Stone);
+        let mut player = standing_at(0.5, 0.5);
+        player.toggle_mode();
+        assert_eq!(player.mode, MovementMode::Noclip);
+        let input = PlayerInput {
+            movement: Vector3::x(),
+            jump: true,
+            ..Default::default()
+        };
+        for _ in 0..60 {
+            player.tick(&world, &input);
+        }
+        // the camera flies on its own, the stuck player isn't pushed out or pulled down
+        assert_eq!(player.position, Point3::new(0.5, 1.0, 0.5));
+        assert_eq!(player.velocity, Vector3::zeros());
+    }
+
+    #[test]
+    fn updates_run_whole_ticks() {
+        let world = floor();
+        let mut player = Player::new(Point3::new(0.5, 4.0, 0.5));
+        player.update(
+            &world,
+            &PlayerInput::default(),
+            Duration::from_secs_f32(TICK * 0.5),
+        );
+        assert_eq!(player.position.y, 4.0);
+        player.update(
+            &world,
+            &PlayerInput::default(),
+            Duration::from_secs_f32(TICK * 0.6),
+        );
+        assert!(player.position.y < 4.0);
+    }
+}
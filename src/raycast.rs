@@ -0,0 +1,293 @@
+use nalgebra::{Point3, Vector3};
+
+use crate::{
+    physics::Aabb,
+    world::{Face, World},
+};
+
+/// How far away blocks can be picked, in blocks.
+pub const REACH: f32 = 5.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RaycastHit {
+    pub block: Point3<i32>,
+    /// the face the ray entered the block through, `None` if the ray started inside of it
+    pub face: Option<Face>,
+    /// distance from the origin to where the ray enters the block
+    pub distance: f32,
+    /// where the ray enters the block
+    pub point: Point3<f32>,
+}
+
+impl RaycastHit {
+    /// The block in front of the hit face, which is where a new block would be placed.
+    pub fn adjacent(&self) -> Option<Point3<i32>> {
+        Some(self.block + self.face?.normal())
+    }
+}
+
+/// Finds the first block along the ray that can be targeted, visiting every block it passes
+/// through (Amanatides & Woo, "A Fast Voxel Traversal Algorithm for Ray Tracing").
+///
+/// Blocks that aren't full cubes are only hit where the ray goes through one of their boxes.
+/// All coordinates are world coordinates, `direction` doesn't have to be normalized.
+pub fn raycast(
+    world: &World,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    max_distance: f32,
+) -> Option<RaycastHit> {
+    let direction = direction.try_normalize(f32::EPSILON)?;
+    let mut block = origin.map(|c| c.floor() as i32);
+    let hit = |block, face, distance| {
+        hit_block(world, block, face, distance, origin, direction)
+            .filter(|hit| hit.distance <= max_distance)
+    };
+    if let Some(hit) = hit(block, None, 0.0) {
+        return Some(hit);
+    }
+
+    let mut step = Vector3::zeros();
+    // distance along the ray between two block boundaries on each axis
+    let mut t_delta = Vector3::repeat(f32::INFINITY);
+    // distance along the ray to the next block boundary on each axis
+    let mut t_max = Vector3::repeat(f32::INFINITY);
+    for axis in 0..3 {
+        if direction[axis] > 0.0 {
+            step[axis] = 1;
+            t_delta[axis] = 1.0 / direction[axis];
+            t_max[axis] = (block[axis] as f32 + 1.0 - origin[axis]) / direction[axis];
+        } else if direction[axis] < 0.0 {
+            step[axis] = -1;
+            t_delta[axis] = -1.0 / direction[axis];
+            t_max[axis] = (block[axis] as f32 - origin[axis]) / direction[axis];
+        }
+    }
+
+    loop {
+        let axis = t_max.imin();
+        let distance = t_max[axis];
+        if distance > max_distance {
+            return None;
+        }
+        block[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+        // the face looks back towards where the ray came from
+        let face = Face::from_axis(axis, step[axis] < 0);
+        if let Some(hit) = hit(block, Some(face), distance) {
+            return Some(hit);
+        }
+    }
+}
+
+/// Where the ray hits the block it enters at `distance` through `face`, if it does.
+fn hit_block(
+    world: &World,
+    block: Point3<i32>,
+    face: Option<Face>,
+    distance: f32,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+) -> Option<RaycastHit> {
+    let kind = world.block(block);
+    if !kind.is_targetable() {
+        return None;
+    }
+    let (distance, face) = match world.shapes.get(kind, world.block_state(block)) {
+        None => (distance, face),
+        Some(_) => world
+            .block_boxes(block)
+            .iter()
+            .filter_map(|aabb| intersect_box(aabb, origin, direction))
+            .min_by(|a, b| a.0.total_cmp(&b.0))?,
+    };
+    Some(RaycastHit {
+        block,
+        face,
+        distance,
+        point: origin + direction * distance,
+    })
+}
+
+/// The distance to where the ray enters `aabb` and the face it enters through, `None` for the
+/// face if it starts inside of it.
+fn intersect_box(
+    aabb: &Aabb,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+) -> Option<(f32, Option<Face>)> {
+    let mut enter = (f32::NEG_INFINITY, None);
+    let mut exit = f32::INFINITY;
+    for axis in 0..3 {
+        if direction[axis] == 0.0 {
+            if origin[axis] < aabb.min[axis] || origin[axis] > aabb.max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let a = (aabb.min[axis] - origin[axis]) / direction[axis];
+        let b = (aabb.max[axis] - origin[axis]) / direction[axis];
+        let near = a.min(b);
+        if near > enter.0 {
+            enter = (near, Some(Face::from_axis(axis, direction[axis] < 0.0)));
+        }
+        exit = exit.min(a.max(b));
+    }
+    if exit < enter.0.max(0.0) {
+        None
+    } else if enter.0 <= 0.0 {
+        Some((0.0, None))
+    } else {
+        Some(enter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        model::BlockModels,
+        world::{Block, Half, CHUNK_SIZE},
+    };
+
+    /// The middle of the block at the origin.
+    fn center() -> Point3<f32> {
+        Point3::new(0.5, 0.5, 0.5)
+    }
+
+    fn world_with(blocks: &[[i32; 3]]) -> World {
+        let mut world = World::new();
+        for &block in blocks {
+            world.set_block(Point3::from(block), Block::Stone);
+        }
+        world
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-5,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn rays_along_the_axes_hit_the_facing_side() {
+        for face in Face::ALL {
+            let normal = face.normal();
+            let block = Point3::from(normal * 3);
+            let world = world_with(&[block.into()]);
+            let hit = raycast(&world, center(), normal.cast(), REACH).unwrap();
+            assert_eq!(hit.block, block);
+            assert_eq!(hit.face.map(|face| face.normal()), Some(-normal));
+            assert_close(hit.distance, 2.5);
+            assert_eq!(hit.adjacent(), Some(Point3::from(normal * 2)));
+        }
+    }
+
+    #[test]
+    fn rays_starting_inside_a_block_hit_it_without_a_face() {
+        let world = world_with(&[[0, 0, 0], [1, 0, 0]]);
+        let hit = raycast(&world, center(), Vector3::x(), REACH).unwrap();
+        assert_eq!(hit.block, Point3::new(0, 0, 0));
+        assert_eq!(hit.face, None);
+        assert_eq!(hit.distance, 0.0);
+        // there's nowhere to place a block
+        assert_eq!(hit.adjacent(), None);
+    }
+
+    #[test]
+    fn rays_through_an_edge_hit_the_diagonal_block() {
+        let world = world_with(&[[1, 1, 0]]);
+        let direction = Vector3::new(1.0, 1.0, 0.0);
+        let hit = raycast(&world, center(), direction, REACH).unwrap();
+        assert_eq!(hit.block, Point3::new(1, 1, 0));
+        assert_close(hit.distance, 0.5f32.sqrt());
+        // on a tie the x boundary is crossed first, so the ray enters from below
+        assert_eq!(hit.face, Some(Face::NegY));
+    }
+
+    #[test]
+    fn rays_grazing_an_edge_hit_the_block_they_touch() {
+        let world = world_with(&[[1, 0, 0], [1, 1, 0]]);
+        let hit = raycast(&world, center(), Vector3::new(1.0, 1.0, 0.0), REACH).unwrap();
+        assert_eq!(hit.block, Point3::new(1, 0, 0));
+        assert_eq!(hit.face, Some(Face::NegX));
+    }
+
+    #[test]
+    fn rays_through_a_corner_hit_the_diagonal_block() {
+        let world = world_with(&[[1, 1, 1]]);
+        let hit = raycast(&world, center(), Vector3::repeat(1.0), REACH).unwrap();
+        assert_eq!(hit.block, Point3::new(1, 1, 1));
+        assert_close(hit.distance, 0.75f32.sqrt());
+    }
+
+    #[test]
+    fn blocks_beyond_the_distance_are_missed() {
+        // the near side of the block is 4.5 away
+        let world = world_with(&[[5, 0, 0]]);
+        assert_eq!(raycast(&world, center(), Vector3::x(), 4.4), None);
+        let hit = raycast(&world, center(), Vector3::x(), 4.5).unwrap();
+        assert_eq!(hit.block, Point3::new(5, 0, 0));
+        assert_eq!(raycast(&world, center(), -Vector3::x(), REACH), None);
+    }
+
+    #[test]
+    fn negative_coordinates_across_chunk_borders() {
+        let edge = -CHUNK_SIZE;
+        let world = world_with(&[[edge - 2, -1, -1]]);
+        // starts in the chunk at -1 and ends in the one at -2
+        let origin = Point3::new(edge as f32 + 1.5, -0.5, -0.5);
+        let hit = raycast(&world, origin, -Vector3::x(), REACH).unwrap();
+        assert_eq!(hit.block, Point3::new(edge - 2, -1, -1));
+        assert_eq!(hit.face, Some(Face::PosX));
+        assert_close(hit.distance, 2.5);
+        assert_eq!(hit.adjacent(), Some(Point3::new(edge - 1, -1, -1)));
+    }
+
+    #[test]
+    fn fluids_and_air_are_passed_through() {
+        let mut world = world_with(&[[0, -3, 0]]);
+        world.set_block(Point3::new(0, -1, 0), Block::Water);
+        world.set_block(Point3::new(0, -2, 0), Block::Lava);
+        let hit = raycast(&world, center(), -Vector3::y(), REACH).unwrap();
+        assert_eq!(hit.block, Point3::new(0, -3, 0));
+        assert_eq!(hit.face, Some(Face::PosY));
+        assert_eq!(hit.adjacent(), Some(Point3::new(0, -2, 0)));
+    }
+
+    #[test]
+    fn rays_only_hit_the_boxes_of_blocks_with_a_model() {
+        let mut world = World::new();
+        world.set_models(BlockModels::load().unwrap());
+        let slab = Block::StoneSlab.state(Face::NegZ, Half::Bottom);
+        world.set_block_with_state(Point3::new(2, 0, 0), Block::StoneSlab, slab);
+
+        // over the slab through the empty upper half of its block
+        let origin = Point3::new(0.5, 0.75, 0.5);
+        assert_eq!(raycast(&world, origin, Vector3::x(), REACH), None);
+
+        // down onto its top, half a block into its block
+        let origin = Point3::new(2.5, 2.0, 0.5);
+        let hit = raycast(&world, origin, -Vector3::y(), REACH).unwrap();
+        assert_eq!(hit.block, Point3::new(2, 0, 0));
+        assert_eq!(hit.face, Some(Face::PosY));
+        assert_close(hit.distance, 1.5);
+        assert_close(hit.point.y, 0.5);
+
+        // its side below the top
+        let origin = Point3::new(0.5, 0.25, 0.5);
+        let hit = raycast(&world, origin, Vector3::x(), REACH).unwrap();
+        assert_eq!(hit.face, Some(Face::NegX));
+        assert_close(hit.distance, 1.5);
+        assert_eq!(hit.point, Point3::new(2.0, 0.25, 0.5));
+    }
+
+    #[test]
+    fn rays_without_a_direction_hit_nothing() {
+        let world = world_with(&[[1, 0, 0]]);
+        assert_eq!(raycast(&world, center(), Vector3::zeros(), REACH), None);
+    }
+}
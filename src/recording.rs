@@ -0,0 +1,159 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{self, Sender},
+    thread::{self, JoinHandle},
+};
+
+use crate::{config::Config, screenshot};
+
+pub struct Frame {
+    pub extent: [u32; 2],
+    pub rgba: Vec<u8>,
+}
+
+enum FrameSink {
+    PngSequence { directory: PathBuf, next_index: u32 },
+    Encoder { child: Child, extent: [u32; 2] },
+}
+
+impl FrameSink {
+    fn write(&mut self, frame: Frame) {
+        match self {
+            FrameSink::PngSequence {
+                directory,
+                next_index,
+            } => {
+                let path = directory.join(format!("frame_{:06}.png", next_index));
+                *next_index += 1;
+                if let Err(e) = screenshot::write_png(&path, frame.extent, &frame.rgba) {
+                    log::error!("Failed to write {}: {:?}", path.display(), e);
+                }
+            }
+            FrameSink::Encoder { child, extent } => {
+                // a raw video stream can't change its resolution
+                if frame.extent != *extent {
+                    log::warn!(
+                        "Skipping frame with size {:?}, the encoder was started with {:?}",
+                        frame.extent,
+                        extent
+                    );
+                    return;
+                }
+                if let Some(stdin) = child.stdin.as_mut() {
+                    if let Err(e) = stdin.write_all(&frame.rgba) {
+                        log::error!("Failed to write frame to encoder: {:?}", e);
+                        child.stdin = None;
+                    }
+                }
+            }
+        }
+    }
+
+    fn finish(self) {
+        if let FrameSink::Encoder { mut child, .. } = self {
+            // closing stdin tells the encoder that the stream ended
+            drop(child.stdin.take());
+            match child.wait() {
+                Ok(status) => log::info!("Encoder exited with {}", status),
+                Err(e) => log::error!("Failed to wait for encoder: {:?}", e),
+            }
+        }
+    }
+}
+
+/// Writes recorded frames in order on a background thread.
+///
+/// Dropping the recorder doesn't wait for the thread, it writes the frames that were already
+/// pushed on its own and then exits.
+pub struct Recorder {
+    sender: Sender<Frame>,
+    thread: JoinHandle<()>,
+}
+
+impl Recorder {
+    /// `previous` is a recording that may still be writing its last frames, the new one waits
+    /// for it so they don't write to the same files or encoder at the same time.
+    pub fn new(config: &Config, previous: Option<Recorder>) -> Self {
+        let (sender, receiver) = mpsc::channel::<Frame>();
+        let directory = config.record_directory.clone();
+        let fps = config.record_fps;
+        let command = config.record_command.clone();
+        let previous = previous.map(Recorder::finish);
+
+        let thread = thread::spawn(move || {
+            if let Some(previous) = previous {
+                let _ = previous.join();
+            }
+            let mut sink: Option<FrameSink> = None;
+            for frame in receiver {
+                if sink.is_none() {
+                    sink = match start_sink(&directory, command.as_deref(), fps, frame.extent) {
+                        Ok(sink) => Some(sink),
+                        Err(e) => {
+                            log::error!("Failed to start recording: {:?}", e);
+                            return;
+                        }
+                    };
+                }
+                sink.as_mut().unwrap().write(frame);
+            }
+            if let Some(sink) = sink {
+                sink.finish();
+            }
+            log::info!("Recording finished");
+        });
+
+        Self { sender, thread }
+    }
+
+    pub fn push(&self, frame: Frame) {
+        // the writer thread only goes away if it failed to start, which it already reported
+        let _ = self.sender.send(frame);
+    }
+
+    /// Stops taking frames, the returned thread ends once all pushed frames are written.
+    fn finish(self) -> JoinHandle<()> {
+        drop(self.sender);
+        self.thread
+    }
+}
+
+fn start_sink(
+    directory: &Path,
+    command: Option<&str>,
+    fps: u32,
+    extent: [u32; 2],
+) -> std::io::Result<FrameSink> {
+    match command {
+        Some(command) => {
+            let command = command
+                .replace("{width}", &extent[0].to_string())
+                .replace("{height}", &extent[1].to_string())
+                .replace("{fps}", &fps.to_string());
+            log::info!("Recording to encoder: {}", command);
+
+            let mut shell = if cfg!(target_os = "windows") {
+                let mut shell = Command::new("cmd");
+                shell.arg("/C");
+                shell
+            } else {
+                let mut shell = Command::new("sh");
+                shell.arg("-c");
+                shell
+            };
+            let child = shell.arg(command).stdin(Stdio::piped()).spawn()?;
+            Ok(FrameSink::Encoder { child, extent })
+        }
+        None => {
+            fs::create_dir_all(directory)?;
+            log::info!("Recording frames to {}", directory.display());
+            Ok(FrameSink::PngSequence {
+                directory: directory.to_path_buf(),
+                next_index: 0,
+            })
+        }
+    }
+}
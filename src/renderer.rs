@@ -9,145 +9,379 @@
 
 use std::sync::Arc;
 
-use crate::main_pipeline::MainPipeline;
+use crate::{
+    config::{Config, GpuSelector},
+    debug::DebugMessenger,
+    error::{Context, PoritzCraftError, Result},
+    main_pipeline::{FrameView, MainPipeline},
+    world::World,
+};
 
-use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType};
+use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType, QueueFamily};
 use vulkano::device::{DeviceCreateInfo, DeviceExtensions, Features, QueueCreateInfo};
 use vulkano::image::ImageUsage;
-use vulkano::instance::debug::{DebugUtilsMessenger, DebugUtilsMessengerCreateInfo, Message};
-use vulkano::instance::{layers_list, Instance, InstanceCreateInfo};
+use vulkano::instance::{layers_list, Instance, InstanceCreateInfo, InstanceExtensions};
 
 use vulkano::{
     device::Device,
-    swapchain::{Swapchain, SwapchainCreateInfo},
+    swapchain::{Surface, Swapchain, SwapchainCreateInfo},
 };
 
-use winit::event_loop::EventLoop;
-use winit::window::WindowBuilder;
+use winit::event_loop::EventLoopWindowTarget;
+use winit::window::{Window, WindowBuilder};
 
-pub struct PoritzCraftRenderer {
-    pub main_pipeline: MainPipeline,
+const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+
+fn required_device_extensions() -> DeviceExtensions {
+    DeviceExtensions {
+        khr_swapchain: true,
+        ext_descriptor_indexing: true,
+        ..DeviceExtensions::none()
+    }
 }
 
-impl PoritzCraftRenderer {
-    pub fn new(event_loop: &EventLoop<()>) -> Self {
-        let required_extensions = vulkano_win::required_extensions();
+fn required_features() -> Features {
+    Features {
+        sampler_anisotropy: true,
+        descriptor_indexing: true,
+        descriptor_binding_variable_descriptor_count: true,
+        // https://chunkstories.xyz/blog/a-note-on-descriptor-indexing/
+        // vulkaninfo
+        shader_sampled_image_array_dynamic_indexing: true,
+        shader_sampled_image_array_non_uniform_indexing: true,
+        runtime_descriptor_array: true,
+        ..Features::none()
+    }
+}
 
-        println!("List of Vulkan debugging layers available to use:");
-        let mut layers = layers_list().unwrap();
-        while let Some(l) = layers.next() {
-            //println!("\t{}", l.name());
-        }
+/// Lower is better.
+fn device_type_rank(device_type: PhysicalDeviceType) -> u32 {
+    match device_type {
+        PhysicalDeviceType::DiscreteGpu => 0,
+        PhysicalDeviceType::IntegratedGpu => 1,
+        PhysicalDeviceType::VirtualGpu => 2,
+        PhysicalDeviceType::Cpu => 3,
+        PhysicalDeviceType::Other => 4,
+    }
+}
 
-        let instance = Instance::new(InstanceCreateInfo {
-            enabled_extensions: required_extensions,
-            enabled_layers: vec!["VK_LAYER_KHRONOS_validation".to_owned()],
-            ..Default::default()
-        })
-        .unwrap();
+/// Explains why a device can't be used, `None` if it has everything we need.
+fn missing_requirements(physical_device: PhysicalDevice) -> Option<String> {
+    let extensions = required_device_extensions();
+    if !physical_device
+        .supported_extensions()
+        .is_superset_of(&extensions)
+    {
+        return Some(format!(
+            "missing extensions {:?}",
+            extensions.difference(physical_device.supported_extensions())
+        ));
+    }
 
-        let debug_create_info =
-            DebugUtilsMessengerCreateInfo::user_callback(Arc::new(|msg: &Message| {
-                println!("Debug callback: {:?}", msg.description);
-            }));
-        let _callback = unsafe { DebugUtilsMessenger::new(instance.clone(), debug_create_info) };
+    let features = required_features();
+    if !physical_device
+        .supported_features()
+        .is_superset_of(&features)
+    {
+        return Some(format!(
+            "missing features {:?}",
+            features.difference(physical_device.supported_features())
+        ));
+    }
 
-        let window = WindowBuilder::new()
-            .with_title("PoritzCraft")
-            .build(event_loop)
-            .unwrap();
+    None
+}
 
-        window.set_cursor_grab(true).unwrap();
-        window.set_cursor_visible(false);
+pub fn create_instance(config: &Config) -> Result<Arc<Instance>> {
+    let available_layers: Vec<String> = layers_list()
+        .context("list the Vulkan layers")?
+        .map(|l| l.name().to_owned())
+        .collect();
+    let validation_available = available_layers.iter().any(|l| l == VALIDATION_LAYER);
 
-        let surface = vulkano_win::create_surface_from_handle(window, instance.clone()).unwrap();
+    let enable_validation = match config.validation {
+        Some(true) if !validation_available => {
+            return Err(PoritzCraftError::Unsupported(format!(
+                "Validation was requested but {} is not installed, available layers: {:?}",
+                VALIDATION_LAYER, available_layers
+            )));
+        }
+        Some(enable) => enable,
+        None => {
+            if !validation_available {
+                log::warn!(
+                    "{} is not installed, validation is disabled",
+                    VALIDATION_LAYER
+                );
+            }
+            validation_available
+        }
+    };
 
-        let device_extensions = DeviceExtensions {
-            khr_swapchain: true,
-            ext_descriptor_indexing: true,
-            ..DeviceExtensions::none()
-        };
-        let (physical_device, queue_family) = PhysicalDevice::enumerate(&instance)
-            .filter(|&p| p.supported_extensions().is_superset_of(&device_extensions))
-            .filter_map(|p| {
-                p.queue_families()
-                    .find(|&q| {
-                        q.supports_graphics() && q.supports_surface(&surface).unwrap_or(false)
-                    })
-                    .map(|q| (p, q))
-            })
-            .max_by_key(|(p, _)| match p.properties().device_type {
-                PhysicalDeviceType::DiscreteGpu => 0,
-                PhysicalDeviceType::IntegratedGpu => 1,
-                PhysicalDeviceType::VirtualGpu => 2,
-                PhysicalDeviceType::Cpu => 3,
-                PhysicalDeviceType::Other => 4,
-            })
-            .unwrap();
+    Instance::new(InstanceCreateInfo {
+        enabled_extensions: InstanceExtensions {
+            // the validation layer reports its messages through debug utils
+            ext_debug_utils: enable_validation,
+            ..vulkano_win::required_extensions()
+        },
+        enabled_layers: if enable_validation {
+            vec![VALIDATION_LAYER.to_owned()]
+        } else {
+            vec![]
+        },
+        ..Default::default()
+    })
+    .context("create the Vulkan instance")
+}
+
+/// Prints every device with the properties that are relevant for choosing one with `--gpu`.
+pub fn list_devices(config: &Config) -> Result<()> {
+    let instance = create_instance(config)?;
 
+    for physical_device in PhysicalDevice::enumerate(&instance) {
+        let properties = physical_device.properties();
         println!(
-            "Using device: {} (type: {:?})",
-            physical_device.properties().device_name,
-            physical_device.properties().device_type,
+            "{}: {} (type: {:?}, api version: {})",
+            physical_device.index(),
+            properties.device_name,
+            properties.device_type,
+            properties.api_version,
         );
+        match missing_requirements(physical_device) {
+            Some(reason) => println!("\tunsupported: {}", reason),
+            None => println!("\tsupported"),
+        }
+        println!("\tfeatures: {:?}", physical_device.supported_features());
+    }
+
+    Ok(())
+}
 
-        let (device, mut queues) = Device::new(
-            physical_device,
-            DeviceCreateInfo {
-                enabled_extensions: physical_device
-                    .required_extensions()
-                    .union(&device_extensions),
-                enabled_features: Features {
-                    sampler_anisotropy: true,
-                    descriptor_indexing: true,
-                    descriptor_binding_variable_descriptor_count: true,
-                    // https://chunkstories.xyz/blog/a-note-on-descriptor-indexing/
-                    // vulkaninfo
-                    shader_sampled_image_array_dynamic_indexing: true,
-                    shader_sampled_image_array_non_uniform_indexing: true,
-                    runtime_descriptor_array: true,
-                    ..Features::none()
+fn select_physical_device<'a>(
+    instance: &'a Arc<Instance>,
+    surface: &Surface<Window>,
+    selector: Option<&GpuSelector>,
+) -> Result<(PhysicalDevice<'a>, QueueFamily<'a>)> {
+    let queue_family = |p: PhysicalDevice<'a>| {
+        p.queue_families()
+            .find(|&q| q.supports_graphics() && q.supports_surface(surface).unwrap_or(false))
+    };
+
+    match selector {
+        Some(selector) => {
+            let physical_device = PhysicalDevice::enumerate(instance)
+                .find(|p| match selector {
+                    GpuSelector::Index(index) => p.index() == *index,
+                    GpuSelector::Name(name) => p
+                        .properties()
+                        .device_name
+                        .to_lowercase()
+                        .contains(&name.to_lowercase()),
+                })
+                .ok_or_else(|| {
+                    PoritzCraftError::Unsupported(format!(
+                        "No device matches {:?}, use --list-devices to see the available ones",
+                        selector
+                    ))
+                })?;
+
+            if let Some(reason) = missing_requirements(physical_device) {
+                return Err(PoritzCraftError::Unsupported(format!(
+                    "{} can't be used: {}",
+                    physical_device.properties().device_name,
+                    reason
+                )));
+            }
+            let queue_family = queue_family(physical_device).ok_or_else(|| {
+                PoritzCraftError::Unsupported(format!(
+                    "{} has no queue family that supports graphics and presenting to the window",
+                    physical_device.properties().device_name
+                ))
+            })?;
+            Ok((physical_device, queue_family))
+        }
+        None => PhysicalDevice::enumerate(instance)
+            .filter(|&p| missing_requirements(p).is_none())
+            .filter_map(|p| queue_family(p).map(|q| (p, q)))
+            .min_by_key(|(p, _)| device_type_rank(p.properties().device_type))
+            .ok_or_else(|| {
+                PoritzCraftError::Unsupported(
+                    "No device supports the required extensions and features, \
+                     use --list-devices to see what is missing"
+                        .to_owned(),
+                )
+            }),
+    }
+}
+
+/// Creates the device, swapchain and pipeline for an existing surface.
+fn create_main_pipeline(
+    instance: &Arc<Instance>,
+    surface: Arc<Surface<Window>>,
+    config: &Config,
+    world: &World,
+) -> Result<MainPipeline> {
+    let (physical_device, queue_family) =
+        select_physical_device(instance, &surface, config.gpu.as_ref())?;
+
+    log::info!(
+        "Using device: {} (type: {:?})",
+        physical_device.properties().device_name,
+        physical_device.properties().device_type,
+    );
+
+    let (device, mut queues) = Device::new(
+        physical_device,
+        DeviceCreateInfo {
+            enabled_extensions: physical_device
+                .required_extensions()
+                .union(&required_device_extensions()),
+            enabled_features: required_features(),
+            queue_create_infos: vec![QueueCreateInfo::family(queue_family)],
+            ..Default::default()
+        },
+    )
+    .context("create the device")?;
+
+    let queue = queues.next().context("get the graphics queue")?;
+
+    let (swapchain, images) = {
+        let surface_capabilities = physical_device
+            .surface_capabilities(&surface, Default::default())
+            .context("query the surface capabilities")?;
+        let image_format = Some(
+            physical_device
+                .surface_formats(&surface, Default::default())
+                .context("query the surface formats")?[0]
+                .0,
+        );
+
+        // transfer source is needed to read back screenshots and recorded frames
+        let transfer_source = surface_capabilities.supported_usage_flags.transfer_source;
+        if !transfer_source {
+            log::warn!("The surface can't be copied from, screenshots and recording are disabled");
+        }
+
+        Swapchain::new(
+            device.clone(),
+            surface.clone(),
+            SwapchainCreateInfo {
+                min_image_count: surface_capabilities.min_image_count,
+                image_format,
+                image_extent: surface.window().inner_size().into(),
+                image_usage: ImageUsage {
+                    transfer_source,
+                    ..ImageUsage::color_attachment()
                 },
-                queue_create_infos: vec![QueueCreateInfo::family(queue_family)],
+                composite_alpha: surface_capabilities
+                    .supported_composite_alpha
+                    .iter()
+                    .next()
+                    .context("find a supported composite alpha mode")?,
                 ..Default::default()
             },
         )
-        .unwrap();
-
-        let queue = queues.next().unwrap();
-
-        let (swapchain, images) = {
-            let surface_capabilities = physical_device
-                .surface_capabilities(&surface, Default::default())
-                .unwrap();
-            let image_format = Some(
-                physical_device
-                    .surface_formats(&surface, Default::default())
-                    .unwrap()[0]
-                    .0,
-            );
-
-            Swapchain::new(
-                device.clone(),
-                surface.clone(),
-                SwapchainCreateInfo {
-                    min_image_count: surface_capabilities.min_image_count,
-                    image_format,
-                    image_extent: surface.window().inner_size().into(),
-                    image_usage: ImageUsage::color_attachment(),
-                    composite_alpha: surface_capabilities
-                        .supported_composite_alpha
-                        .iter()
-                        .next()
-                        .unwrap(),
-                    ..Default::default()
-                },
-            )
-            .unwrap()
+        .context("create the swapchain")?
+    };
+
+    MainPipeline::new(
+        device,
+        swapchain,
+        surface,
+        queue,
+        images,
+        world,
+        config.shadows,
+    )
+}
+
+pub struct PoritzCraftRenderer {
+    /// `None` while the device is recreated, and after that failed
+    main_pipeline: Option<MainPipeline>,
+    instance: Arc<Instance>,
+    surface: Arc<Surface<Window>>,
+    config: Config,
+    debug_messenger: Option<DebugMessenger>,
+}
+
+impl PoritzCraftRenderer {
+    pub fn new(
+        event_loop: &EventLoopWindowTarget<()>,
+        config: &Config,
+        world: &World,
+    ) -> Result<Self> {
+        let instance = create_instance(config)?;
+
+        let debug_messenger = if instance.enabled_extensions().ext_debug_utils {
+            Some(DebugMessenger::new(instance.clone(), config)?)
+        } else {
+            None
         };
 
-        Self {
-            main_pipeline: MainPipeline::new(device, swapchain, surface, queue, images),
+        let window = WindowBuilder::new()
+            .with_title("PoritzCraft")
+            .build(event_loop)
+            .context("create the window")?;
+
+        window.set_cursor_grab(true).context("grab the cursor")?;
+        window.set_cursor_visible(false);
+
+        let surface = vulkano_win::create_surface_from_handle(window, instance.clone())
+            .context("create the surface")?;
+
+        Ok(Self {
+            main_pipeline: Some(create_main_pipeline(
+                &instance,
+                surface.clone(),
+                config,
+                world,
+            )?),
+            instance,
+            surface,
+            config: config.clone(),
+            debug_messenger,
+        })
+    }
+
+    pub fn main_pipeline(&self) -> Option<&MainPipeline> {
+        self.main_pipeline.as_ref()
+    }
+
+    pub fn main_pipeline_mut(&mut self) -> Option<&mut MainPipeline> {
+        self.main_pipeline.as_mut()
+    }
+
+    /// Renders a frame, recreating the device if it was lost.
+    pub fn render(&mut self, world: &World, view: &FrameView) -> Result<()> {
+        let main_pipeline = self
+            .main_pipeline
+            .as_mut()
+            .context("render without a device")?;
+        match main_pipeline.render(view) {
+            Err(PoritzCraftError::DeviceLost) => {
+                log::error!("The device was lost, recreating it");
+                self.recreate_device(world)
+            }
+            result => result,
+        }
+    }
+
+    /// Throws away everything that belongs to the device and starts over on the same window.
+    fn recreate_device(&mut self, world: &World) -> Result<()> {
+        // the old swapchain has to be gone before the window can get a new one
+        self.main_pipeline = None;
+        self.main_pipeline = Some(create_main_pipeline(
+            &self.instance,
+            self.surface.clone(),
+            &self.config,
+            world,
+        )?);
+        Ok(())
+    }
+
+    pub fn check_validation_errors(&self) {
+        if let Some(debug_messenger) = &self.debug_messenger {
+            debug_messenger.check_validation_errors();
         }
     }
 }
@@ -0,0 +1,125 @@
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use vulkano::{buffer::CpuAccessibleBuffer, format::Format};
+
+pub const SCREENSHOT_DIRECTORY: &str = "screenshots";
+
+/// A copy of a swapchain image that was recorded into a command buffer but may not have
+/// finished executing on the GPU yet.
+pub struct PendingReadback {
+    pub buffer: Arc<CpuAccessibleBuffer<[u8]>>,
+    pub extent: [u32; 2],
+    pub format: Format,
+}
+
+impl PendingReadback {
+    /// Returns the image as tightly packed RGBA8 once the GPU has released the buffer.
+    /// This never blocks, so call it again on a later frame if it returns `None`.
+    pub fn try_read_rgba(&self) -> Option<Vec<u8>> {
+        let data = self.buffer.read().ok()?;
+        to_rgba8(self.format, &data)
+    }
+}
+
+/// Whether swapchain images of this format can be converted to RGBA8.
+pub fn is_supported(format: Format) -> bool {
+    matches!(
+        format,
+        Format::B8G8R8A8_SRGB
+            | Format::B8G8R8A8_UNORM
+            | Format::R8G8B8A8_SRGB
+            | Format::R8G8B8A8_UNORM
+    )
+}
+
+pub fn to_rgba8(format: Format, data: &[u8]) -> Option<Vec<u8>> {
+    let swap_red_blue = match format {
+        Format::B8G8R8A8_SRGB | Format::B8G8R8A8_UNORM => true,
+        Format::R8G8B8A8_SRGB | Format::R8G8B8A8_UNORM => false,
+        _ => return None,
+    };
+
+    let mut rgba = data.to_vec();
+    for pixel in rgba.chunks_exact_mut(4) {
+        if swap_red_blue {
+            pixel.swap(0, 2);
+        }
+        // the swapchain alpha depends on the composite mode, we always want an opaque image
+        pixel[3] = 255;
+    }
+    Some(rgba)
+}
+
+pub fn write_png(path: &Path, extent: [u32; 2], rgba: &[u8]) -> Result<(), png::EncodingError> {
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), extent[0], extent[1]);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgba)?;
+    Ok(())
+}
+
+fn timestamped_path() -> PathBuf {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Path::new(SCREENSHOT_DIRECTORY).join(format!(
+        "{}_{:03}.png",
+        now.as_secs(),
+        now.subsec_millis()
+    ))
+}
+
+/// Encodes and writes the screenshot on a separate thread so the render loop doesn't wait for it.
+pub fn save_screenshot(extent: [u32; 2], rgba: Vec<u8>) {
+    thread::spawn(move || {
+        if let Err(e) = fs::create_dir_all(SCREENSHOT_DIRECTORY) {
+            log::error!("Failed to create screenshot directory: {:?}", e);
+            return;
+        }
+        let path = timestamped_path();
+        match write_png(&path, extent, &rgba) {
+            Ok(()) => log::info!("Saved screenshot to {}", path.display()),
+            Err(e) => log::error!("Failed to save screenshot: {:?}", e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bgra_is_swapped_to_rgba() {
+        let bgra = [10, 20, 30, 40, 50, 60, 70, 80];
+        for format in [Format::B8G8R8A8_SRGB, Format::B8G8R8A8_UNORM] {
+            assert_eq!(
+                to_rgba8(format, &bgra),
+                Some(vec![30, 20, 10, 255, 70, 60, 50, 255])
+            );
+        }
+    }
+
+    #[test]
+    fn rgba_keeps_its_channels() {
+        // sRGB values are already what a PNG expects, so they aren't converted either
+        let rgba = [10, 20, 30, 40];
+        for format in [Format::R8G8B8A8_SRGB, Format::R8G8B8A8_UNORM] {
+            assert_eq!(to_rgba8(format, &rgba), Some(vec![10, 20, 30, 255]));
+        }
+    }
+
+    #[test]
+    fn other_formats_are_not_converted() {
+        assert!(!is_supported(Format::R16G16B16A16_SFLOAT));
+        assert_eq!(to_rgba8(Format::R16G16B16A16_SFLOAT, &[0; 8]), None);
+    }
+}
@@ -0,0 +1,283 @@
+//! Cascaded shadow maps for the sun.
+//!
+//! The part of the view frustum that gets shadows is split into slices, each of them is covered by
+//! its own orthographic projection from the sun. All cascades are rendered side by side into one
+//! depth texture, close slices get more texels per block than distant ones.
+
+use nalgebra::{IsometryMatrix3, Matrix4, Point3, Vector3, Vector4};
+
+pub const MAX_CASCADES: u32 = 4;
+/// In blocks, nothing further away from the camera gets shadows.
+pub const SHADOW_DISTANCE: f32 = 96.0;
+
+/// Blends between evenly spaced (0) and logarithmically spaced (1) cascade splits.
+const SPLIT_LAMBDA: f32 = 0.75;
+/// In render units, blocks up to this far behind a cascade still cast shadows into it.
+const CASTER_MARGIN: f32 = 640.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShadowSettings {
+    /// 0 disables shadows
+    pub cascades: u32,
+    /// width and height of every cascade in texels
+    pub resolution: u32,
+}
+
+impl ShadowSettings {
+    /// Lowers the resolution until all cascades side by side fit into an image at most
+    /// `max_width` texels wide.
+    pub fn fit_width(self, max_width: u32) -> Self {
+        let max_resolution = max_width / self.cascades.max(1);
+        if self.resolution <= max_resolution {
+            return self;
+        }
+        log::warn!(
+            "{} shadow cascades of {} texels don't fit into the maximum image width of {}, using {} texels",
+            self.cascades,
+            self.resolution,
+            max_width,
+            max_resolution
+        );
+        Self {
+            resolution: max_resolution,
+            ..self
+        }
+    }
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            cascades: 3,
+            resolution: 2048,
+        }
+    }
+}
+
+/// What the camera sees, the shadow cascades are fitted to it.
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    pub view: IsometryMatrix3<f32>,
+    /// vertical field of view in radians
+    pub fov: f32,
+    pub aspect_ratio: f32,
+    pub near: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cascade {
+    /// from render coordinates to the cascade's clip space, with depth from 0 to 1
+    pub view_projection: Matrix4<f32>,
+    /// view depth up to which this cascade is used
+    pub split: f32,
+    /// size of one texel in render units
+    pub texel_size: f32,
+}
+
+impl Cascade {
+    /// Whether anything inside the box between `min` and `max` in render coordinates can end up in
+    /// the cascade.
+    pub fn intersects(&self, min: Point3<f32>, max: Point3<f32>) -> bool {
+        let mut clip_min = Vector3::repeat(f32::INFINITY);
+        let mut clip_max = Vector3::repeat(f32::NEG_INFINITY);
+        for corner in 0..8 {
+            let point = Point3::new(
+                if corner & 1 == 0 { min.x } else { max.x },
+                if corner & 2 == 0 { min.y } else { max.y },
+                if corner & 4 == 0 { min.z } else { max.z },
+            );
+            // the projection is orthographic, so w stays 1
+            let clip = self.view_projection.transform_point(&point).coords;
+            clip_min = clip_min.inf(&clip);
+            clip_max = clip_max.sup(&clip);
+        }
+        clip_max.x >= -1.0
+            && clip_min.x <= 1.0
+            && clip_max.y >= -1.0
+            && clip_min.y <= 1.0
+            && clip_max.z >= 0.0
+            && clip_min.z <= 1.0
+    }
+}
+
+/// The view depths where the cascades end, the last one is `far`.
+pub fn cascade_splits(near: f32, far: f32, count: u32) -> Vec<f32> {
+    (1..=count)
+        .map(|i| {
+            let fraction = i as f32 / count as f32;
+            let logarithmic = near * (far / near).powf(fraction);
+            let uniform = near + (far - near) * fraction;
+            SPLIT_LAMBDA * logarithmic + (1.0 - SPLIT_LAMBDA) * uniform
+        })
+        .collect()
+}
+
+/// Fits `count` cascades to the frustum up to `distance` from the camera.
+///
+/// `light_direction` points to the light in render coordinates.
+pub fn cascades(
+    frustum: &Frustum,
+    light_direction: Vector3<f32>,
+    distance: f32,
+    settings: &ShadowSettings,
+) -> Vec<Cascade> {
+    let mut near = frustum.near;
+    cascade_splits(frustum.near, distance, settings.cascades)
+        .into_iter()
+        .map(|far| {
+            let cascade = fit_cascade(frustum, light_direction, near, far, settings.resolution);
+            near = far;
+            cascade
+        })
+        .collect()
+}
+
+/// An orthographic projection from the light around the slice of the frustum between `near`
+/// and `far`.
+fn fit_cascade(
+    frustum: &Frustum,
+    light_direction: Vector3<f32>,
+    near: f32,
+    far: f32,
+    resolution: u32,
+) -> Cascade {
+    let camera_to_world = frustum.view.inverse();
+    let tan_half_fov = (frustum.fov / 2.0).tan();
+    let mut corners = Vec::with_capacity(8);
+    for depth in [near, far] {
+        let half_height = depth * tan_half_fov;
+        let half_width = half_height * frustum.aspect_ratio;
+        for (x, y) in [(-1.0, -1.0), (-1.0, 1.0), (1.0, -1.0), (1.0, 1.0)] {
+            corners.push(camera_to_world * Point3::new(x * half_width, y * half_height, -depth));
+        }
+    }
+
+    // a sphere keeps the size of the cascade the same while the camera turns, which stops the
+    // shadow edges from flickering
+    let center = Point3::from(
+        corners
+            .iter()
+            .map(|corner| corner.coords)
+            .sum::<Vector3<f32>>()
+            / corners.len() as f32,
+    );
+    let radius = corners
+        .iter()
+        .map(|corner| (corner - center).norm())
+        .fold(0.0, f32::max);
+    let radius = (radius / 16.0).ceil() * 16.0;
+
+    let light_direction = light_direction.normalize();
+    let up = if light_direction.x.abs() < 0.99 {
+        Vector3::x()
+    } else {
+        Vector3::z()
+    };
+    let eye = center + light_direction * (radius + CASTER_MARGIN);
+    let light_view = Matrix4::look_at_rh(&eye, &center, &up);
+    let projection = depth_zero_to_one()
+        * Matrix4::new_orthographic(
+            -radius,
+            radius,
+            -radius,
+            radius,
+            0.0,
+            2.0 * radius + CASTER_MARGIN,
+        );
+    let mut view_projection = projection * light_view;
+
+    // only move the cascade by whole texels so the shadows don't swim when the camera moves
+    let origin = view_projection * Vector4::new(0.0, 0.0, 0.0, 1.0) * (resolution as f32 / 2.0);
+    let offset = (origin.xy().map(f32::round) - origin.xy()) * (2.0 / resolution as f32);
+    view_projection[(0, 3)] += offset.x;
+    view_projection[(1, 3)] += offset.y;
+
+    Cascade {
+        view_projection,
+        split: far,
+        texel_size: 2.0 * radius / resolution as f32,
+    }
+}
+
+/// nalgebra projects depth to -1..1 like OpenGL, Vulkan expects 0..1.
+fn depth_zero_to_one() -> Matrix4<f32> {
+    Matrix4::new(
+        1.0, 0.0, 0.0, 0.0, //
+        0.0, 1.0, 0.0, 0.0, //
+        0.0, 0.0, 0.5, 0.5, //
+        0.0, 0.0, 0.0, 1.0,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frustum() -> Frustum {
+        Frustum {
+            view: IsometryMatrix3::look_at_rh(
+                &Point3::origin(),
+                &Point3::new(0.0, 0.0, -1.0),
+                &Vector3::y(),
+            ),
+            fov: 1.2,
+            aspect_ratio: 1.5,
+            near: 1.0,
+        }
+    }
+
+    fn cube(center: Point3<f32>, half_size: f32) -> (Point3<f32>, Point3<f32>) {
+        let half = Vector3::repeat(half_size);
+        (center - half, center + half)
+    }
+
+    #[test]
+    fn resolution_is_lowered_to_fit_the_image_width() {
+        let settings = ShadowSettings {
+            cascades: 4,
+            resolution: 4096,
+        };
+        assert_eq!(settings.fit_width(16384), settings);
+        assert_eq!(settings.fit_width(8192).resolution, 2048);
+        assert_eq!(settings.fit_width(10000).resolution, 2500);
+        let disabled = ShadowSettings {
+            cascades: 0,
+            resolution: 4096,
+        };
+        assert_eq!(disabled.fit_width(4096), disabled);
+    }
+
+    #[test]
+    fn splits_end_at_the_distance() {
+        let splits = cascade_splits(1.0, 100.0, 3);
+        assert_eq!(splits.len(), 3);
+        assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!((splits[2] - 100.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn boxes_outside_of_a_cascade_are_culled() {
+        // the light shines along -y, cascades are squares around the slices of the view
+        let settings = ShadowSettings::default();
+        let cascades = cascades(&frustum(), Vector3::y(), 1000.0, &settings);
+        let first = &cascades[0];
+
+        let (min, max) = cube(Point3::new(0.0, 0.0, -5.0), 1.0);
+        assert!(first.intersects(min, max));
+        // blocks far closer to the light still cast shadows into the cascade
+        let (min, max) = cube(Point3::new(0.0, 300.0, -5.0), 1.0);
+        assert!(first.intersects(min, max));
+        // but not ones far away to the side or behind the area it covers
+        let (min, max) = cube(Point3::new(0.0, 0.0, -900.0), 1.0);
+        assert!(!first.intersects(min, max));
+        assert!(cascades[2].intersects(min, max));
+        let (min, max) = cube(Point3::new(5000.0, 0.0, -5.0), 1.0);
+        assert!(!first.intersects(min, max));
+        let (min, max) = cube(Point3::new(0.0, -5000.0, -5.0), 1.0);
+        assert!(!first.intersects(min, max));
+
+        // boxes larger than the cascade overlap it
+        let (min, max) = cube(Point3::new(0.0, 0.0, -5.0), 10000.0);
+        assert!(first.intersects(min, max));
+    }
+}
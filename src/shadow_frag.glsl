@@ -0,0 +1,5 @@
+#version 450
+
+// only the depth is written
+void main() {
+}
@@ -0,0 +1,12 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+
+layout(push_constant) uniform PushConstants {
+    // from render coordinates to the clip space of one cascade
+    mat4 view_projection;
+} cascade;
+
+void main() {
+    gl_Position = cascade.view_projection * vec4(position, 1.0);
+}
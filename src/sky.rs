@@ -0,0 +1,197 @@
+//! The time of day and everything that depends on it: where the sun is, the colors of the sky
+//! and how bright sky light is.
+
+use std::{f32::consts::TAU, time::Duration};
+
+use nalgebra::Vector3;
+
+/// Ticks per in-game day, like in Minecraft a day lasts 20 minutes.
+pub const DAY_LENGTH: u32 = 24000;
+/// How many ticks of world time pass per second.
+pub const TICKS_PER_SECOND: f32 = 20.0;
+
+/// At 0 the sun rises in the east (+x), at 6000 it is highest and at 18000 it is midnight.
+pub const SUNRISE: u32 = 0;
+pub const NOON: u32 = 6000;
+pub const SUNSET: u32 = 12000;
+pub const MIDNIGHT: u32 = 18000;
+
+/// Sky light is never darker than this, even at midnight.
+const MIN_SKY_LIGHT: f32 = 0.2;
+
+const DAY_ZENITH: [f32; 3] = [0.47, 0.65, 1.0];
+const DAY_HORIZON: [f32; 3] = [0.75, 0.85, 1.0];
+const NIGHT_ZENITH: [f32; 3] = [0.005, 0.008, 0.03];
+const NIGHT_HORIZON: [f32; 3] = [0.03, 0.04, 0.09];
+const SUNSET_HORIZON: [f32; 3] = [1.0, 0.5, 0.2];
+
+/// The world time, advanced while the game runs unless it is paused.
+#[derive(Clone, Debug)]
+pub struct WorldTime {
+    /// ticks since the start of the day, below [`DAY_LENGTH`]
+    pub time_of_day: u32,
+    pub paused: bool,
+    /// seconds that didn't add up to a whole tick yet
+    accumulated: f32,
+}
+
+impl WorldTime {
+    pub fn new(time_of_day: u32) -> Self {
+        Self {
+            time_of_day: time_of_day % DAY_LENGTH,
+            paused: false,
+            accumulated: 0.0,
+        }
+    }
+
+    pub fn set(&mut self, time_of_day: u32) {
+        self.time_of_day = time_of_day % DAY_LENGTH;
+        self.accumulated = 0.0;
+    }
+
+    /// Returns how many game ticks passed, they keep going while the time of day is paused.
+    pub fn update(&mut self, delta: Duration) -> u32 {
+        self.accumulated += delta.as_secs_f32() * TICKS_PER_SECOND;
+        let ticks = self.accumulated.floor();
+        self.accumulated -= ticks;
+        if !self.paused {
+            self.time_of_day = (self.time_of_day + ticks as u32) % DAY_LENGTH;
+        }
+        ticks as u32
+    }
+
+    /// The time including the part of the current tick, so the sun moves smoothly.
+    pub fn smooth_time_of_day(&self) -> f32 {
+        if self.paused {
+            self.time_of_day as f32
+        } else {
+            self.time_of_day as f32 + self.accumulated
+        }
+    }
+}
+
+/// What the sky looks like at one moment.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sky {
+    /// points to the sun in world coordinates, the moon is on the opposite side
+    pub sun_direction: Vector3<f32>,
+    /// multiplies sky light, from [`MIN_SKY_LIGHT`] at night to 1 during the day
+    pub sky_light: f32,
+    pub zenith_color: [f32; 3],
+    pub horizon_color: [f32; 3],
+    /// 0 during the day, 1 when all stars are visible
+    pub star_brightness: f32,
+}
+
+impl Sky {
+    pub fn at(time_of_day: f32) -> Self {
+        let angle = time_of_day / DAY_LENGTH as f32 * TAU;
+        let sun_direction = Vector3::new(angle.cos(), angle.sin(), 0.0);
+        let height = sun_direction.y;
+
+        // the sky brightens and darkens while the sun is close to the horizon
+        let daylight = (height * 4.0 + 0.5).clamp(0.0, 1.0);
+        let sunset = (1.0 - height.abs() * 4.0).clamp(0.0, 1.0);
+        let horizon_color = mix(
+            mix(NIGHT_HORIZON, DAY_HORIZON, daylight),
+            SUNSET_HORIZON,
+            sunset * 0.7,
+        );
+
+        Self {
+            sun_direction,
+            sky_light: MIN_SKY_LIGHT + (1.0 - MIN_SKY_LIGHT) * daylight,
+            zenith_color: mix(NIGHT_ZENITH, DAY_ZENITH, daylight),
+            horizon_color,
+            star_brightness: 1.0 - (daylight * 2.0).min(1.0),
+        }
+    }
+
+    /// Points to the sun during the day and to the moon at night.
+    pub fn light_direction(&self) -> Vector3<f32> {
+        if self.sun_direction.y >= 0.0 {
+            self.sun_direction
+        } else {
+            -self.sun_direction
+        }
+    }
+}
+
+fn mix(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_direction(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!(
+            (actual - expected).norm() < 1e-5,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn time_of_day_wraps_around() {
+        assert_eq!(WorldTime::new(DAY_LENGTH + NOON).time_of_day, NOON);
+        let mut time = WorldTime::new(0);
+        time.set(DAY_LENGTH * 3 + 5);
+        assert_eq!(time.time_of_day, 5);
+
+        time.set(DAY_LENGTH - 10);
+        assert_eq!(time.update(Duration::from_secs(1)), 20);
+        assert_eq!(time.time_of_day, 10);
+    }
+
+    #[test]
+    fn partial_ticks_add_up() {
+        let mut time = WorldTime::new(NOON);
+        assert_eq!(time.update(Duration::from_millis(30)), 0);
+        assert_eq!(time.time_of_day, NOON);
+        assert!((time.smooth_time_of_day() - (NOON as f32 + 0.6)).abs() < 1e-4);
+        assert_eq!(time.update(Duration::from_millis(30)), 1);
+        assert_eq!(time.time_of_day, NOON + 1);
+    }
+
+    #[test]
+    fn pausing_stops_the_time_of_day_but_not_the_ticks() {
+        let mut time = WorldTime::new(SUNSET);
+        time.paused = true;
+        assert_eq!(time.update(Duration::from_secs(2)), 40);
+        assert_eq!(time.time_of_day, SUNSET);
+        assert_eq!(time.smooth_time_of_day(), SUNSET as f32);
+    }
+
+    #[test]
+    fn the_sun_is_overhead_at_noon() {
+        let sky = Sky::at(NOON as f32);
+        assert_direction(sky.sun_direction, Vector3::y());
+        assert_direction(sky.light_direction(), Vector3::y());
+        assert_eq!(sky.sky_light, 1.0);
+        assert_eq!(sky.star_brightness, 0.0);
+    }
+
+    #[test]
+    fn the_moon_is_overhead_at_midnight() {
+        let sky = Sky::at(MIDNIGHT as f32);
+        assert_direction(sky.sun_direction, -Vector3::y());
+        assert_direction(sky.light_direction(), Vector3::y());
+        assert_eq!(sky.sky_light, MIN_SKY_LIGHT);
+        assert_eq!(sky.star_brightness, 1.0);
+    }
+
+    #[test]
+    fn the_sun_rises_in_the_east() {
+        assert_direction(Sky::at(SUNRISE as f32).sun_direction, Vector3::x());
+        assert_direction(Sky::at(SUNSET as f32).sun_direction, -Vector3::x());
+        let sky = Sky::at(SUNRISE as f32);
+        assert!(sky.sky_light > MIN_SKY_LIGHT && sky.sky_light < 1.0);
+    }
+}
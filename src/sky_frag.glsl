@@ -0,0 +1,56 @@
+#version 450
+
+layout(location = 0) in vec2 v_ndc;
+
+layout(location = 0) out vec4 f_color;
+
+layout(push_constant) uniform PushConstants {
+    // from screen coordinates to view directions, the camera position is left out
+    mat4 inverse_view_projection;
+    // world space, y up, w is the brightness of the stars
+    vec4 sun_direction;
+    vec4 zenith_color;
+    vec4 horizon_color;
+} sky;
+
+const float SUN_SIZE = 0.9995;
+const float MOON_SIZE = 0.9997;
+const vec3 SUN_COLOR = vec3(1.0, 0.95, 0.8);
+const vec3 MOON_COLOR = vec3(0.8, 0.85, 0.9);
+const float STAR_DENSITY = 400.0;
+
+float hash(vec3 p) {
+    return fract(sin(dot(p, vec3(12.9898, 78.233, 37.719))) * 43758.5453);
+}
+
+void main() {
+    vec4 far = sky.inverse_view_projection * vec4(v_ndc, 1.0, 1.0);
+    vec3 direction = normalize(far.xyz / far.w);
+    // render coordinates have y pointing down
+    direction.y = -direction.y;
+
+    float height = max(direction.y, 0.0);
+    vec3 color = mix(sky.horizon_color.rgb, sky.zenith_color.rgb, sqrt(height));
+    // below the horizon the sky fades to a darker version of the horizon color
+    color = mix(color, sky.horizon_color.rgb * 0.5, clamp(-direction.y * 4.0, 0.0, 1.0));
+
+    float stars = sky.sun_direction.w;
+    if (stars > 0.0 && direction.y > 0.0) {
+        vec3 cell = floor(direction * STAR_DENSITY);
+        float star = step(0.998, hash(cell));
+        color += vec3(star * stars * height);
+    }
+
+    vec3 sun = sky.sun_direction.xyz;
+    float sun_amount = dot(direction, sun);
+    // a soft glow around the sun and the disc itself
+    color += SUN_COLOR * pow(max(sun_amount, 0.0), 64.0) * 0.3;
+    if (sun_amount > SUN_SIZE) {
+        color = SUN_COLOR;
+    }
+    if (dot(direction, -sun) > MOON_SIZE) {
+        color = MOON_COLOR;
+    }
+
+    f_color = vec4(color, 1.0);
+}
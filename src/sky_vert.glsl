@@ -0,0 +1,10 @@
+#version 450
+
+// a single triangle covering the whole screen, no vertex buffer needed
+layout(location = 0) out vec2 v_ndc;
+
+void main() {
+    vec2 ndc = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2) * 2.0 - 1.0;
+    v_ndc = ndc;
+    gl_Position = vec4(ndc, 1.0, 1.0);
+}
@@ -0,0 +1,125 @@
+// Inputs, descriptor sets and lighting shared by the block and the water fragment shaders.
+
+layout(location = 0) flat in vec3 v_normal;
+layout(location = 1) in vec2 tex_coords;
+layout(location = 2) in flat uint v_block_type;
+// sky and block light from 0 to 1, sky light is already darker at night
+layout(location = 3) in vec2 v_light;
+// how directly the face points at the sun or moon
+layout(location = 4) in flat float v_sun_light;
+layout(location = 5) in float v_distance;
+layout(location = 6) in vec3 v_position;
+layout(location = 7) in float v_depth;
+layout(location = 8) in vec3 v_tint;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 1, binding = 0) uniform sampler2D tex[];
+
+// the cascades side by side
+layout(set = 2, binding = 0) uniform sampler2D shadow_map;
+layout(set = 2, binding = 1) uniform Shadows {
+    mat4 cascades[4];
+    // view depth where each cascade ends
+    vec4 splits;
+    // size of a shadow map texel in render units
+    vec4 texel_sizes;
+    // number of cascades and their resolution
+    vec4 settings;
+} shadows;
+// for every texture: the frame to show, the next frame, how far to blend towards it and the
+// number of frames stacked in the texture
+layout(set = 2, binding = 2) uniform Animations {
+    vec4 frames[16];
+} animations;
+
+layout(push_constant) uniform PushConstants {
+    // w is the mode: 0 off, 1 linear, 2 exponential
+    vec4 fog_color;
+    // start, end and density in render units
+    vec3 fog;
+    // pixels with less alpha are discarded, 0 for translucent blocks
+    float alpha_cutoff;
+    // the rest is only used by water, both shaders share the layout so the pipelines can be
+    // switched without binding the descriptor sets again
+    // points to the sun or the moon in render coordinates, w scales sky light over the day
+    vec4 sun;
+    // the camera position in render coordinates, w is the time in seconds
+    vec4 camera;
+    vec4 zenith_color;
+    vec4 horizon_color;
+} constants;
+
+// every light level is 80% as bright as the one above it
+const float FALLOFF = 0.8;
+const float MAX_LIGHT = 15.0;
+
+// 0 where there is no fog, 1 where only the fog color is left
+float fog_amount() {
+    float mode = constants.fog_color.w;
+    if (mode > 1.5) {
+        return 1.0 - exp(-constants.fog.z * max(v_distance - constants.fog.x, 0.0));
+    } else if (mode > 0.5) {
+        return clamp((v_distance - constants.fog.x) / (constants.fog.y - constants.fog.x), 0.0, 1.0);
+    }
+    return 0.0;
+}
+
+// 1 where the sun reaches the fragment, 0 in full shadow
+float sun_visibility() {
+    int count = int(shadows.settings.x);
+    int cascade = count;
+    for (int i = 0; i < count; i++) {
+        if (v_depth < shadows.splits[i]) {
+            cascade = i;
+            break;
+        }
+    }
+    if (cascade == count) {
+        return 1.0;
+    }
+
+    // moving along the normal keeps faces from shadowing themselves
+    vec3 position = v_position + normalize(v_normal) * shadows.texel_sizes[cascade] * 1.5;
+    vec4 light_position = shadows.cascades[cascade] * vec4(position, 1.0);
+    vec2 uv = light_position.xy * 0.5 + 0.5;
+    if (any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0))) || light_position.z > 1.0) {
+        return 1.0;
+    }
+
+    // percentage closer filtering over 3x3 texels softens the edges
+    float texel = 1.0 / shadows.settings.y;
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            // stay inside this cascade's part of the texture
+            vec2 sample_uv = clamp(uv + vec2(x, y) * texel, vec2(texel / 2.0), vec2(1.0 - texel / 2.0));
+            sample_uv.x = (float(cascade) + sample_uv.x) / float(count);
+            lit += light_position.z <= texture(shadow_map, sample_uv).r ? 1.0 : 0.0;
+        }
+    }
+    return lit / 9.0;
+}
+
+// how bright the sky and block light make the fragment
+float brightness() {
+    // faces along different axes are shaded differently so the edges of blocks stay visible
+    vec3 axis = abs(normalize(v_normal));
+    float shade = dot(axis, vec3(0.6, 1.0, 0.8));
+    float sky = pow(FALLOFF, MAX_LIGHT * (1.0 - v_light.x)) * mix(0.6, 1.0, v_sun_light * sun_visibility());
+    float block = pow(FALLOFF, MAX_LIGHT * (1.0 - v_light.y)) * shade;
+    return max(sky, block);
+}
+
+// the current frame of the block's texture
+vec4 block_texture() {
+    vec4 animation = animations.frames[v_block_type];
+    float count = max(animation.w, 1.0);
+    vec2 current = vec2(tex_coords.x, (tex_coords.y + animation.x) / count);
+    vec2 next = vec2(tex_coords.x, (tex_coords.y + animation.y) / count);
+    vec4 color = texture(tex[nonuniformEXT(v_block_type)], current);
+    if (animation.z > 0.0) {
+        color = mix(color, texture(tex[nonuniformEXT(v_block_type)], next), animation.z);
+    }
+    return color;
+}
@@ -0,0 +1,83 @@
+//! Monospaced text drawn from a bitmap font atlas.
+//!
+//! The atlas in `font/ascii.png` holds the printable ASCII characters in 16 columns of 8x16 pixel
+//! cells, rendered from DejaVu Sans Mono (see `font/LICENSE`). It is part of the texture array
+//! at [`FONT_TEXTURE`], so text goes through the overlay pipeline like every other HUD element.
+
+use crate::{overlay::OverlayBuilder, world::BLOCK_TEXTURES};
+
+/// Index of the font atlas in the texture array, after the block textures.
+pub const FONT_TEXTURE: u32 = BLOCK_TEXTURES.len() as u32;
+
+pub const GLYPH_WIDTH: f32 = 8.0;
+pub const GLYPH_HEIGHT: f32 = 16.0;
+
+const ATLAS_COLUMNS: u32 = 16;
+const ATLAS_ROWS: u32 = 6;
+const FIRST_CHARACTER: u32 = ' ' as u32;
+const LAST_CHARACTER: u32 = '~' as u32;
+
+/// The top left and bottom right texture coordinates of a character in the atlas.
+fn glyph_tex_coords(character: char) -> ([f32; 2], [f32; 2]) {
+    let code = match character as u32 {
+        code @ FIRST_CHARACTER..=LAST_CHARACTER => code,
+        _ => '?' as u32,
+    };
+    let index = code - FIRST_CHARACTER;
+    let column = (index % ATLAS_COLUMNS) as f32;
+    let row = (index / ATLAS_COLUMNS) as f32;
+    let size = [1.0 / ATLAS_COLUMNS as f32, 1.0 / ATLAS_ROWS as f32];
+    (
+        [column * size[0], row * size[1]],
+        [(column + 1.0) * size[0], (row + 1.0) * size[1]],
+    )
+}
+
+/// The size of `text` in pixels.
+pub fn text_size(text: &str, scale: f32) -> [f32; 2] {
+    [
+        text.chars().count() as f32 * GLYPH_WIDTH * scale,
+        GLYPH_HEIGHT * scale,
+    ]
+}
+
+/// Draws a single line of text with its top left corner at `position`.
+pub fn draw_text(
+    builder: &mut OverlayBuilder,
+    position: [f32; 2],
+    text: &str,
+    scale: f32,
+    color: [f32; 4],
+) {
+    for (index, character) in text.chars().enumerate() {
+        if character == ' ' {
+            continue;
+        }
+        let min = [
+            position[0] + index as f32 * GLYPH_WIDTH * scale,
+            position[1],
+        ];
+        let max = [min[0] + GLYPH_WIDTH * scale, min[1] + GLYPH_HEIGHT * scale];
+        let (tex_min, tex_max) = glyph_tex_coords(character);
+        builder.textured_quad(min, max, tex_min, tex_max, color, FONT_TEXTURE);
+    }
+}
+
+/// Text with a dark copy behind it so it stays readable on bright backgrounds.
+pub fn draw_text_with_shadow(
+    builder: &mut OverlayBuilder,
+    position: [f32; 2],
+    text: &str,
+    scale: f32,
+    color: [f32; 4],
+) {
+    let shadow = [color[0] * 0.25, color[1] * 0.25, color[2] * 0.25, color[3]];
+    draw_text(
+        builder,
+        [position[0] + scale, position[1] + scale],
+        text,
+        scale,
+        shadow,
+    );
+    draw_text(builder, position, text, scale, color);
+}
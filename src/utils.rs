@@ -6,25 +6,55 @@
 // at your option. All files in the project carrying such
 // notice may not be copied, modified, or distributed except
 // according to those terms.
+use std::io::Cursor;
+
 use bytemuck::{Pod, Zeroable};
 use vulkano::impl_vertex;
 use winit::event::ElementState;
 
+/// A vertex of a chunk mesh, the position is already in render coordinates.
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
-pub struct Vertex {
+pub struct ChunkVertex {
     pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub tex_coord: [f32; 2],
+    pub block_type: u32,
+    /// sky and block light of the face from 0 to 1
+    pub light: [f32; 2],
+    /// multiplies the texture color, [`UNTINTED`](crate::mesh::UNTINTED) for blocks without a
+    /// biome color
+    pub tint: [f32; 3],
 }
+impl_vertex!(
+    ChunkVertex,
+    position,
+    normal,
+    tex_coord,
+    block_type,
+    light,
+    tint
+);
 
-impl_vertex!(Vertex, position);
+/// A corner of the outline around the targeted block.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+pub struct OutlineVertex {
+    pub position: [f32; 3],
+}
+impl_vertex!(OutlineVertex, position);
 
+/// A vertex of the 2D overlay, positions are in pixels from the top left corner.
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
-pub struct InstanceData {
-    pub position_offset: [f32; 3],
-    pub block_type: u32,
+pub struct OverlayVertex {
+    pub position: [f32; 2],
+    pub tex_coord: [f32; 2],
+    pub color: [f32; 4],
+    /// index into the texture array, negative for a plain colored quad
+    pub texture: i32,
 }
-impl_vertex!(InstanceData, position_offset, block_type);
+impl_vertex!(OverlayVertex, position, tex_coord, color, texture);
 
 pub const SIZE: f32 = 10.0;
 
@@ -39,25 +69,124 @@ pub fn repeat_element<T: Clone>(
     it.flat_map(move |n| std::iter::repeat(n).take(cnt))
 }
 
-#[repr(C)]
-#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
-pub struct Normal {
-    pub normal: [f32; 3],
+pub fn state_is_pressed(state: ElementState) -> bool {
+    match state {
+        ElementState::Pressed => true,
+        ElementState::Released => false,
+    }
 }
 
-impl_vertex!(Normal, normal);
-
-#[repr(C)]
-#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
-pub struct TexCoord {
-    pub tex_coord: [f32; 2],
+/// Decodes a PNG of any color type and bit depth, returns its width, height and 8 bit RGBA pixels.
+pub fn decode_png_rgba8(png_bytes: &[u8]) -> Result<(u32, u32, Vec<u8>), png::DecodingError> {
+    let mut decoder = png::Decoder::new(Cursor::new(png_bytes));
+    // palettes and bit depths below 8 are expanded, 16 bit channels are cut down to 8 bits
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data)?;
+    let data = &data[..info.buffer_size()];
+    let rgba = match info.color_type {
+        png::ColorType::Grayscale => data.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+        png::ColorType::GrayscaleAlpha => data
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Rgb => data
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::Rgba => data.to_vec(),
+        png::ColorType::Indexed => unreachable!("palettes are expanded"),
+    };
+    Ok((info.width, info.height, rgba))
 }
 
-impl_vertex!(TexCoord, tex_coord);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-pub fn state_is_pressed(state: ElementState) -> bool {
-    match state {
-        ElementState::Pressed => true,
-        ElementState::Released => false,
+    fn encode(
+        width: u32,
+        color: png::ColorType,
+        depth: png::BitDepth,
+        palette: Option<&[u8]>,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, width, 1);
+        encoder.set_color(color);
+        encoder.set_depth(depth);
+        if let Some(palette) = palette {
+            encoder.set_palette(palette.to_vec());
+        }
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(data).unwrap();
+        writer.finish().unwrap();
+        bytes
+    }
+
+    #[test]
+    fn pngs_are_decoded_to_rgba8() {
+        let rgb = encode(
+            1,
+            png::ColorType::Rgb,
+            png::BitDepth::Eight,
+            None,
+            &[1, 2, 3],
+        );
+        assert_eq!(decode_png_rgba8(&rgb).unwrap(), (1, 1, vec![1, 2, 3, 255]));
+
+        let gray_alpha = encode(
+            2,
+            png::ColorType::GrayscaleAlpha,
+            png::BitDepth::Eight,
+            None,
+            &[10, 20, 30, 40],
+        );
+        assert_eq!(
+            decode_png_rgba8(&gray_alpha).unwrap().2,
+            [10, 10, 10, 20, 30, 30, 30, 40]
+        );
+    }
+
+    #[test]
+    fn sixteen_bit_channels_are_stripped() {
+        let rgb16 = encode(
+            1,
+            png::ColorType::Rgb,
+            png::BitDepth::Sixteen,
+            None,
+            &[0x12, 0x34, 0xab, 0xcd, 0xff, 0xff],
+        );
+        assert_eq!(decode_png_rgba8(&rgb16).unwrap().2, [0x12, 0xab, 0xff, 255]);
+    }
+
+    #[test]
+    fn palettes_and_low_bit_depths_are_expanded() {
+        let palette = [255, 0, 0, 0, 0, 255];
+        let indexed = encode(
+            2,
+            png::ColorType::Indexed,
+            png::BitDepth::Eight,
+            Some(&palette),
+            &[1, 0],
+        );
+        assert_eq!(
+            decode_png_rgba8(&indexed).unwrap().2,
+            [0, 0, 255, 255, 255, 0, 0, 255]
+        );
+
+        // two pixels of one bit each, black and white
+        let gray = encode(
+            2,
+            png::ColorType::Grayscale,
+            png::BitDepth::One,
+            None,
+            &[0b0100_0000],
+        );
+        assert_eq!(
+            decode_png_rgba8(&gray).unwrap().2,
+            [0, 0, 0, 255, 255, 255, 255, 255]
+        );
     }
 }
@@ -4,25 +4,42 @@
 layout(location = 0) in vec3 position;
 layout(location = 1) in vec3 normal;
 layout(location = 2) in vec2 tex_coord;
-
-// per-instance data
-layout(location = 3) in vec3 position_offset;
-layout(location = 4) in uint block_type;
+layout(location = 3) in uint block_type;
+layout(location = 4) in vec2 light;
+layout(location = 5) in vec3 tint;
 
 layout(location = 0) flat out vec3 v_normal;
 layout(location = 1) out vec2 v_tex_coord;
 layout(location = 2) out flat uint v_block_type;
+layout(location = 3) out vec2 v_light;
+layout(location = 4) out flat float v_sun_light;
+// from the camera in render units
+layout(location = 5) out float v_distance;
+layout(location = 6) out vec3 v_position;
+// distance along the view direction, this decides which shadow cascade is used
+layout(location = 7) out float v_depth;
+// the biome color the texture is multiplied with
+layout(location = 8) out vec3 v_tint;
 
 layout(set = 0, binding = 0) uniform Data {
     mat4 world;
     mat4 view;
     mat4 proj;
+    // points to the sun or the moon in render coordinates, w scales sky light over the day
+    vec4 sun;
 } uniforms;
 
 void main() {
     mat4 worldview = uniforms.view * uniforms.world;
     v_normal = mat3(uniforms.world) * normal;
-    gl_Position = uniforms.proj * worldview * vec4(position + position_offset, 1.0);
+    vec4 view_position = worldview * vec4(position, 1.0);
+    gl_Position = uniforms.proj * view_position;
+    v_distance = length(view_position.xyz);
+    v_position = (uniforms.world * vec4(position, 1.0)).xyz;
+    v_depth = -view_position.z;
     v_tex_coord = tex_coord;
     v_block_type = block_type;
-}
\ No newline at end of file
+    v_tint = tint;
+    v_light = vec2(light.x * uniforms.sun.w, light.y);
+    v_sun_light = max(dot(normalize(v_normal), uniforms.sun.xyz), 0.0);
+}
@@ -0,0 +1,51 @@
+#version 450
+
+#extension GL_EXT_nonuniform_qualifier : enable
+#extension GL_GOOGLE_include_directive : require
+
+#include "terrain.glsl"
+
+// how much light is reflected when looking straight down
+const float BASE_REFLECTANCE = 0.02;
+const vec3 SUN_COLOR = vec3(1.0, 0.95, 0.8);
+// in render units
+const float WAVE_LENGTH = 60.0;
+const float WAVE_HEIGHT = 0.08;
+
+// small waves moving in two directions, added to the normal of the surface
+vec3 wave_normal(vec3 position, float time) {
+    vec2 p = position.xz / WAVE_LENGTH;
+    float dx = cos(p.x * 2.0 + time * 1.3) + 0.5 * cos((p.x + p.y) * 3.7 + time * 2.1);
+    float dz = cos(p.y * 2.3 - time * 1.1) + 0.5 * cos((p.x - p.y) * 3.1 - time * 1.7);
+    return vec3(dx, 0.0, dz) * WAVE_HEIGHT;
+}
+
+void main() {
+    vec3 normal = normalize(v_normal);
+    float time = constants.camera.w;
+    // only the surface moves, y points down in render coordinates
+    if (normal.y < -0.5) {
+        normal = normalize(normal + wave_normal(v_position, time));
+    }
+
+    vec3 to_camera = normalize(constants.camera.xyz - v_position);
+    float facing = max(dot(normal, to_camera), 0.0);
+    // Schlick's approximation, grazing angles reflect almost everything
+    float fresnel = BASE_REFLECTANCE + (1.0 - BASE_REFLECTANCE) * pow(1.0 - facing, 5.0);
+
+    vec3 reflected = reflect(-to_camera, normal);
+    // the sky color in the reflected direction, like the sky shader draws it
+    float height = max(-reflected.y, 0.0);
+    vec3 sky = mix(constants.horizon_color.rgb, constants.zenith_color.rgb, sqrt(height));
+    float visibility = sun_visibility();
+    vec3 highlight = SUN_COLOR * pow(max(dot(reflected, constants.sun.xyz), 0.0), 128.0) * visibility;
+    // the sky can only be seen in water that is open to it
+    vec3 reflection = (sky + highlight) * v_light.x;
+
+    vec4 texture_color = block_texture();
+    vec3 water = texture_color.rgb * v_tint * brightness();
+    vec3 color = mix(water, reflection, fresnel);
+    // water looked at from above is see-through, from the side it reflects the sky
+    float alpha = mix(0.6, 1.0, fresnel);
+    f_color = vec4(mix(color, constants.fog_color.rgb, fog_amount()), alpha);
+}
@@ -7,25 +7,512 @@
 // notice may not be copied, modified, or distributed except
 // according to those terms.
 
-use crate::{renderer::PoritzCraftRenderer, utils::state_is_pressed};
+use std::{collections::HashSet, time::Duration};
 
-use nalgebra::{Isometry3, Matrix4, Rotation3, Translation3, UnitQuaternion, Vector3};
+use crate::{
+    camera::Camera,
+    clock::FrameClock,
+    command::{CommandContext, CommandRegistry},
+    config::Config,
+    console::Console,
+    error::Result,
+    fluid::{Fluid, FluidSimulation},
+    gamepad::{GamepadInput, GilrsSource},
+    hotbar::Hotbar,
+    input::{Action, Bindings},
+    light::{self, LightKind},
+    main_pipeline::{FrameView, DEFAULT_RENDER_DISTANCE},
+    mesh::affected_chunks,
+    model::BlockModels,
+    physics::{Aabb, MovementMode, Player, EYE_HEIGHT},
+    raycast::{raycast, RaycastHit, REACH},
+    recording::Recorder,
+    renderer::PoritzCraftRenderer,
+    sky::{Sky, WorldTime},
+    utils,
+    world::{
+        render_direction_to_world, render_to_world, world_to_render, Block, BlockState, Face, Half,
+        World,
+    },
+};
+
+use nalgebra::{Isometry3, Matrix4, Point3, Rotation3, Translation3, UnitQuaternion, Vector3};
 use winit::{
-    event::{DeviceEvent, Event, VirtualKeyCode, WindowEvent},
+    event::{DeviceEvent, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
 };
 
-pub struct PoritzCraftWindow {}
+pub struct PoritzCraftWindow {
+    config: Config,
+}
+
+/// Everything the event loop works with.
+struct GameState {
+    config: Config,
+    renderer: PoritzCraftRenderer,
+    clock: FrameClock,
+    bindings: Bindings,
+    gamepad: Option<GamepadInput<GilrsSource>>,
+    world: World,
+    player: Player,
+    camera: Camera,
+    hotbar: Hotbar,
+    /// chunks further away from the camera than this aren't drawn
+    render_distance: u32,
+    /// the next frame is saved as a screenshot
+    screenshot_requested: bool,
+    /// whether new frames are recorded
+    recording: bool,
+    /// writes the recorded frames, kept until the frames that were in flight are written
+    recorder: Option<Recorder>,
+    /// the block the crosshair points at
+    target: Option<RaycastHit>,
+    show_debug: bool,
+    console: Console,
+    commands: CommandRegistry<GameState>,
+    time: WorldTime,
+    fluids: FluidSimulation,
+}
+
+impl GameState {
+    fn handle_action(&mut self, action: Action, pressed: bool) {
+        if self.console.open {
+            return;
+        }
+        if self.camera.handle_action(action, pressed) || !pressed {
+            return;
+        }
+        match action {
+            Action::Screenshot => self.screenshot_requested = true,
+            Action::ToggleRecording => {
+                if self.recording {
+                    self.stop_recording();
+                } else {
+                    self.start_recording();
+                }
+            }
+            Action::ReloadBindings => match Bindings::load(&self.config.bindings) {
+                Ok(bindings) => {
+                    self.bindings = bindings;
+                    self.camera.release_all();
+                    log::info!("Reloaded bindings from {}", self.config.bindings.display());
+                }
+                Err(e) => log::error!("{}", e),
+            },
+            Action::Break => {
+                if let Some(hit) = self.target {
+                    self.set_block(hit.block, Block::Air);
+                }
+            }
+            Action::Place => {
+                let (hit, position) = match self.target.and_then(|hit| Some((hit, hit.adjacent()?)))
+                {
+                    Some(target) => target,
+                    None => return,
+                };
+                let block = match self.hotbar.selected_block() {
+                    Some(block) => block,
+                    None => return,
+                };
+                let blocked_by_player = self.player.mode == MovementMode::Walking
+                    && self.player.aabb().intersects(&Aabb::block(position));
+                if !blocked_by_player && !self.world.is_solid(position) {
+                    let state = block.state(self.looking_towards(), placed_half(&hit));
+                    self.place_block(position, block, state);
+                }
+            }
+            Action::ToggleDebug => self.show_debug = !self.show_debug,
+            Action::HotbarNext => self.hotbar.scroll(1),
+            Action::HotbarPrevious => self.hotbar.scroll(-1),
+            Action::ToggleNoclip => {
+                let mode = match self.player.mode {
+                    MovementMode::Walking => MovementMode::Noclip,
+                    MovementMode::Noclip => MovementMode::Walking,
+                };
+                self.set_movement_mode(mode);
+                log::info!("Movement mode: {:?}", self.player.mode);
+            }
+            Action::OpenConsole => self.open_console(""),
+            Action::OpenCommand => self.open_console("/"),
+            _ => {
+                if let Some(slot) = action.hotbar_slot() {
+                    self.hotbar.select(slot);
+                }
+            }
+        }
+    }
+
+    /// Starts passing every rendered frame to a new [`Recorder`], with a fixed timestep so the
+    /// recording plays at the right speed.
+    fn start_recording(&mut self) {
+        let pipeline = match self.renderer.main_pipeline_mut() {
+            Some(pipeline) if pipeline.can_read_back() => pipeline,
+            _ => {
+                log::warn!("Recording is not supported by the swapchain");
+                return;
+            }
+        };
+        pipeline.discard_recorded_frames();
+        // a previous recording that is still being written is finished first
+        self.recorder = Some(Recorder::new(&self.config, self.recorder.take()));
+        self.recording = true;
+        self.clock.fixed_timestep =
+            Some(Duration::from_secs_f64(1.0 / self.config.record_fps as f64));
+    }
+
+    /// Stops capturing new frames, frames that are still in flight will still be written.
+    fn stop_recording(&mut self) {
+        self.recording = false;
+        self.clock.fixed_timestep = None;
+    }
+
+    /// Hands the frames that were read back to the recorder.
+    fn collect_recorded_frames(&mut self) {
+        let pipeline = match self.renderer.main_pipeline_mut() {
+            Some(pipeline) => pipeline,
+            None => return,
+        };
+        // frames have to be handed to the recorder in the order they were rendered
+        for frame in pipeline.finished_frames() {
+            if let Some(recorder) = &self.recorder {
+                recorder.push(frame);
+            }
+        }
+        let can_record = pipeline.can_read_back();
+        let has_pending_frames = pipeline.has_pending_frames();
+        if self.recording && !can_record {
+            // the device was recreated with a swapchain that can't be copied from
+            log::warn!("Recording is not supported by the swapchain, stopping it");
+            self.stop_recording();
+        }
+        if !self.recording && !has_pending_frames {
+            // the writer thread finishes the remaining frames without holding up the frame
+            self.recorder = None;
+        }
+    }
+
+    /// Draws the world as the player sees it and passes recorded frames on.
+    fn render(&mut self) -> Result<()> {
+        let screenshot = std::mem::take(&mut self.screenshot_requested);
+        let view = FrameView {
+            camera: &self.camera,
+            hotbar: &self.hotbar,
+            outline: self
+                .target
+                .map(|hit| self.world.block_boxes(hit.block))
+                .unwrap_or_default(),
+            debug_lines: self.show_debug.then(|| self.debug_lines()),
+            console_lines: self.console.open.then(|| self.console_lines()),
+            render_distance: self.render_distance,
+            sky: Sky::at(self.time.smooth_time_of_day()),
+            fog: self.config.fog,
+            camera_fluid: self.camera_fluid(),
+            animation_time: self.clock.simulation_time.as_secs_f32(),
+            screenshot,
+            record: self.recording,
+        };
+        self.renderer.render(&self.world, &view)?;
+        self.collect_recorded_frames();
+        Ok(())
+    }
+
+    fn open_console(&mut self, prefix: &str) {
+        self.console.open(prefix);
+        // the keys that are held now won't be seen released
+        self.camera.release_all();
+        self.bindings.release_all();
+    }
+
+    /// Edits the console input, returns whether the key was used.
+    fn handle_console_key(&mut self, input: &KeyboardInput) -> bool {
+        if !self.console.open || !utils::state_is_pressed(input.state) {
+            return self.console.open;
+        }
+        match input.virtual_keycode {
+            Some(VirtualKeyCode::Escape) => self.console.close(),
+            Some(VirtualKeyCode::Back) => self.console.backspace(),
+            Some(VirtualKeyCode::Up) => self.console.browse_history(true),
+            Some(VirtualKeyCode::Down) => self.console.browse_history(false),
+            Some(VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter) => {
+                let line = self.console.submit();
+                self.run_command(&line);
+            }
+            _ => (),
+        }
+        true
+    }
+
+    fn run_command(&mut self, line: &str) {
+        if line.trim().is_empty() {
+            return;
+        }
+        self.console.print(line);
+        // the registry is only borrowed while the command changes the rest of the state
+        let commands = std::mem::take(&mut self.commands);
+        let result = commands.execute(self, line);
+        self.commands = commands;
+        let message = match result {
+            Ok(message) => message,
+            Err(e) => e.to_string(),
+        };
+        if !message.is_empty() {
+            log::info!("{}", message);
+            self.console.print(message);
+        }
+    }
+
+    fn console_lines(&self) -> Vec<String> {
+        let mut lines: Vec<_> = self.console.output.iter().cloned().collect();
+        lines.push(format!("> {}_", self.console.input));
+        lines
+    }
+
+    fn set_movement_mode(&mut self, mode: MovementMode) {
+        if self.player.mode == mode {
+            return;
+        }
+        if self.player.mode == MovementMode::Noclip {
+            // continue walking from wherever the camera flew to
+            self.player.position = self.feet_position();
+        }
+        self.player.toggle_mode();
+    }
+
+    fn camera_fluid(&self) -> Option<Fluid> {
+        let eye = render_to_world(self.camera.position());
+        self.world.block(eye.map(|c| c.floor() as i32)).fluid()
+    }
+
+    /// Runs the fluid simulation for `ticks` game ticks.
+    fn update_fluids(&mut self, ticks: u32) {
+        let mut changed = Vec::new();
+        for _ in 0..ticks {
+            changed.extend(self.fluids.step(&mut self.world));
+        }
+        if !changed.is_empty() {
+            self.blocks_changed(&changed);
+        }
+    }
+
+    /// Updates the light and the meshes around blocks that were changed in the world.
+    fn blocks_changed(&mut self, positions: &[Point3<i32>]) {
+        // includes the changed blocks themselves
+        let lit = light::update(&mut self.world, positions);
+        let chunks: HashSet<_> = lit.into_iter().flat_map(affected_chunks).collect();
+        for chunk in chunks {
+            // a pipeline that is created later meshes the whole world anyway
+            if let Some(pipeline) = self.renderer.main_pipeline_mut() {
+                if let Err(e) = pipeline.update_chunk(&self.world, chunk) {
+                    log::error!("{}", e);
+                }
+            }
+        }
+        // the target may be gone or covered now
+        self.update_target();
+    }
+
+    /// Where the player stands, in noclip mode this follows the camera.
+    fn feet_position(&self) -> Point3<f32> {
+        match self.player.mode {
+            MovementMode::Walking => self.player.position,
+            MovementMode::Noclip => {
+                render_to_world(self.camera.position()) - Vector3::new(0.0, EYE_HEIGHT, 0.0)
+            }
+        }
+    }
+
+    /// Moves the player and puts the camera at its eyes, or lets the camera fly freely in noclip mode.
+    fn update_movement(&mut self, delta: Duration) {
+        let camera = &mut self.camera;
+        match self.player.mode {
+            MovementMode::Walking => {
+                self.player
+                    .update(&self.world, &camera.player_input(), delta);
+                camera.translation =
+                    Translation3::from(-world_to_render(self.player.eye_position()).coords);
+            }
+            MovementMode::Noclip => camera.update(delta),
+        }
+    }
+
+    fn debug_lines(&self) -> Vec<String> {
+        let camera = &self.camera;
+        let position = render_to_world(camera.position());
+        let block_position = position.map(|c| c.floor() as i32);
+        let mut lines = vec![
+            format!("PoritzCraft {}", env!("CARGO_PKG_VERSION")),
+            format!(
+                "{:.0} fps ({:.2} ms)",
+                self.clock.fps(),
+                self.clock.average_frame_time.as_secs_f64() * 1000.0
+            ),
+            format!(
+                "XYZ: {:.3} / {:.3} / {:.3}",
+                position.x, position.y, position.z
+            ),
+            format!(
+                "Yaw: {:.1} Pitch: {:.1}",
+                camera.yaw.to_degrees(),
+                camera.pitch.to_degrees()
+            ),
+            format!("Mode: {:?}", self.player.mode),
+            format!(
+                "Time: {}{}",
+                self.time.time_of_day,
+                if self.time.paused { " (paused)" } else { "" }
+            ),
+            format!(
+                "Light: {} sky, {} block",
+                self.world.light(block_position, LightKind::Sky),
+                self.world.light(block_position, LightKind::Block)
+            ),
+            format!(
+                "Biome: {}",
+                self.world
+                    .climate(block_position.x, block_position.z)
+                    .biome()
+                    .name()
+            ),
+            format!("Fluid ticks: {} scheduled", self.fluids.pending()),
+        ];
+        if let Some(pipeline) = self.renderer.main_pipeline() {
+            lines.extend([
+                format!(
+                    "Chunks: {} loaded, {} drawn",
+                    self.world.chunk_positions().count(),
+                    pipeline.chunk_mesh_count()
+                ),
+                format!("Draw calls: {}", pipeline.draw_calls),
+                format!("GPU: {}", pipeline.device_name()),
+            ]);
+        }
+        if let Some(hit) = self.target {
+            lines.push(format!(
+                "Looking at: {} {} {} {:?}",
+                hit.block.x,
+                hit.block.y,
+                hit.block.z,
+                self.world.block(hit.block)
+            ));
+        }
+        lines
+    }
+
+    fn set_block(&mut self, position: Point3<i32>, block: Block) {
+        self.set_blocks(&[(position, block)]);
+    }
+
+    /// Like [`GameState::set_block`], but the block is turned as given by `state`.
+    fn place_block(&mut self, position: Point3<i32>, block: Block, state: BlockState) {
+        self.world.set_block_with_state(position, block, state);
+        self.fluids.block_changed(&self.world, position);
+        self.blocks_changed(&[position]);
+    }
+
+    /// The horizontal face the camera looks at most directly, blocks placed now face this way.
+    fn looking_towards(&self) -> Face {
+        let direction = render_direction_to_world(self.camera.direction());
+        let axis = if direction.x.abs() > direction.z.abs() {
+            0
+        } else {
+            2
+        };
+        Face::from_axis(axis, direction[axis] > 0.0)
+    }
+
+    fn update_target(&mut self) {
+        let camera = &self.camera;
+        let target = raycast(
+            &self.world,
+            render_to_world(camera.position()),
+            render_direction_to_world(camera.direction()),
+            REACH,
+        );
+        if target.map(|hit| hit.block) != self.target.map(|hit| hit.block) {
+            log::debug!("Looking at {:?}", target);
+        }
+        self.target = target;
+    }
+
+    fn poll_gamepad(&mut self, delta: Duration) {
+        let gamepad = match &mut self.gamepad {
+            Some(gamepad) => gamepad,
+            None => return,
+        };
+        let actions = gamepad.poll();
+        let camera = &mut self.camera;
+        camera.analog_movement = gamepad.movement();
+        let (yaw, pitch) = gamepad.look(delta);
+        camera.rotate(yaw, pitch);
+
+        for (action, pressed) in actions {
+            self.handle_action(action, pressed);
+        }
+    }
+}
+
+/// Blocks placed against the underside of a block or the upper half of its side go into the top
+/// half of their space.
+fn placed_half(hit: &RaycastHit) -> Half {
+    let upper = hit.point.y - hit.point.y.floor() > 0.5;
+    match hit.face {
+        Some(Face::NegY) => Half::Top,
+        Some(Face::PosY) => Half::Bottom,
+        _ if upper => Half::Top,
+        _ => Half::Bottom,
+    }
+}
 
 impl PoritzCraftWindow {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(config: Config) -> Self {
+        Self { config }
     }
 
-    pub fn run(&self) {
+    pub fn run(self) -> Result<()> {
         let event_loop = EventLoop::new();
 
-        let mut renderer = PoritzCraftRenderer::new(&event_loop);
+        let config = self.config;
+        let gamepad = if config.gamepad {
+            match GilrsSource::new() {
+                Ok(source) => Some(GamepadInput::new(source, config.gamepad_settings)),
+                Err(e) => {
+                    log::warn!("Gamepads are not available: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let mut world = World::generate_flat(rand::random());
+        world.set_models(BlockModels::load()?);
+        let player = Player::new(world.spawn_point());
+        let renderer = PoritzCraftRenderer::new(&event_loop, &config, &world)?;
+        let camera = Camera::new(
+            Translation3::from(-world_to_render(player.eye_position()).coords),
+            config.camera,
+        );
+
+        let mut state = GameState {
+            renderer,
+            clock: FrameClock::new(),
+            bindings: Bindings::load(&config.bindings)?,
+            gamepad,
+            config,
+            world,
+            player,
+            camera,
+            hotbar: Hotbar::default(),
+            render_distance: DEFAULT_RENDER_DISTANCE,
+            screenshot_requested: false,
+            recording: false,
+            recorder: None,
+            target: None,
+            show_debug: false,
+            console: Console::new(),
+            commands: CommandRegistry::with_defaults(),
+            time: WorldTime::new(1000),
+            fluids: FluidSimulation::new(),
+        };
 
         event_loop.run(move |event, _, control_flow| match event {
             Event::WindowEvent {
@@ -38,35 +525,40 @@ impl PoritzCraftWindow {
                 event: WindowEvent::Resized(_),
                 ..
             } => {
-                renderer.main_pipeline.recreate_swapchain = true;
+                if let Some(pipeline) = state.renderer.main_pipeline_mut() {
+                    pipeline.recreate_swapchain = true;
+                }
             }
             Event::WindowEvent {
-                event: WindowEvent::KeyboardInput { input, .. },
+                event: WindowEvent::Focused(false),
                 ..
             } => {
-                if let Some(key_code) = input.virtual_keycode {
-                    match key_code {
-                        VirtualKeyCode::W => {
-                            let trans = Translation3::<f32>::new(0.0, 0.0, 10.0);
-                            let d = renderer.main_pipeline.build_rotation().inverse() * trans;
-
-                            // Translation3::from(renderer.main_pipeline.view_translation.vector + trans.vector)
-                            renderer.main_pipeline.view_translation =
-                                d.translation * renderer.main_pipeline.view_translation;
-                        }
-                        _ => (),
-                    }
+                state.camera.release_all();
+                state.bindings.release_all();
+            }
+            Event::WindowEvent {
+                event: WindowEvent::ReceivedCharacter(character),
+                ..
+            } => {
+                if state.console.open {
+                    state.console.type_character(character);
                 }
             }
+            Event::WindowEvent {
+                event: WindowEvent::KeyboardInput { input, .. },
+                ..
+            } if state.handle_console_key(&input) => {}
             Event::WindowEvent {
                 event:
-                    WindowEvent::MouseInput {
-                        state: _,
-                        button: _,
-                        ..
-                    },
+                    window_event @ (WindowEvent::KeyboardInput { .. }
+                    | WindowEvent::MouseInput { .. }
+                    | WindowEvent::MouseWheel { .. }),
                 ..
-            } => {}
+            } => {
+                for (action, pressed) in state.bindings.resolve(&window_event) {
+                    state.handle_action(action, pressed);
+                }
+            }
             Event::WindowEvent {
                 event: WindowEvent::CursorMoved { position: _, .. },
                 ..
@@ -74,22 +566,74 @@ impl PoritzCraftWindow {
             Event::DeviceEvent {
                 event: DeviceEvent::MouseMotion { delta },
                 ..
-            } => {
+            } if !state.console.open => {
                 // from_axis_angle
                 //  Vector3::y_axis();
                 // rotation_between
 
-                renderer.main_pipeline.view_rotation_yaw += delta.0 * -0.025;
-                renderer.main_pipeline.view_rotation_pitch += delta.1 * -0.025;
+                state.camera.handle_mouse_motion(delta);
             }
-            Event::WindowEvent {
-                event: WindowEvent::MouseWheel { delta: _, .. },
-                ..
-            } => {}
             Event::RedrawEventsCleared => {
-                renderer.main_pipeline.render();
+                let delta = state.clock.tick();
+                state.poll_gamepad(delta);
+                state.update_movement(delta);
+                state.update_target();
+                let ticks = state.time.update(delta);
+                state.update_fluids(ticks);
+                if let Err(e) = state.render() {
+                    log::error!("{}", e);
+                    *control_flow = ControlFlow::Exit;
+                }
+                state.renderer.check_validation_errors();
+                state.console.end_frame();
             }
             _ => (),
         });
     }
 }
+
+impl CommandContext for GameState {
+    fn position(&self) -> Point3<f32> {
+        self.feet_position()
+    }
+
+    fn teleport(&mut self, position: Point3<f32>) {
+        self.player.position = position;
+        self.player.velocity = Vector3::zeros();
+        self.camera.translation =
+            Translation3::from(-world_to_render(self.player.eye_position()).coords);
+    }
+
+    fn seed(&self) -> u64 {
+        self.world.seed
+    }
+
+    /// Changes the blocks and rebuilds the meshes that show them.
+    fn set_blocks(&mut self, blocks: &[(Point3<i32>, Block)]) {
+        for &(position, block) in blocks {
+            self.world.set_block(position, block);
+        }
+        for &(position, _) in blocks {
+            // fluids flow into the gap or away from the new block
+            self.fluids.block_changed(&self.world, position);
+        }
+        let positions: Vec<_> = blocks.iter().map(|(position, _)| *position).collect();
+        self.blocks_changed(&positions);
+    }
+
+    fn set_time(&mut self, time: u32) {
+        self.time.set(time);
+    }
+
+    fn set_time_paused(&mut self, paused: bool) {
+        self.time.paused = paused;
+    }
+
+    fn set_movement_mode(&mut self, mode: MovementMode) {
+        GameState::set_movement_mode(self, mode);
+    }
+
+    fn set_render_distance(&mut self, chunks: u32) {
+        self.render_distance = chunks;
+    }
+}
@@ -0,0 +1,560 @@
+use std::collections::HashMap;
+
+use nalgebra::{Point3, Vector3};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    biome::{Climate, Tint},
+    fluid::{Fluid, SOURCE_LEVEL},
+    light::{self, LightKind, MAX_LIGHT},
+    model::{BlockModels, BlockShapes},
+    physics::Aabb,
+    utils::SIZE,
+};
+
+/// Width of a block in render units.
+pub const BLOCK_SIZE: f32 = 2.0 * SIZE;
+
+pub const CHUNK_SIZE: i32 = 16;
+const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+// world coordinates are measured in blocks with y pointing up, the block at `(x, y, z)`
+// fills the unit cube from `(x, y, z)` to `(x + 1, y + 1, z + 1)`.
+// render coordinates have y pointing down and block centers at multiples of BLOCK_SIZE.
+
+pub fn world_to_render(position: Point3<f32>) -> Point3<f32> {
+    Point3::new(
+        (position.x - 0.5) * BLOCK_SIZE,
+        -(position.y - 0.5) * BLOCK_SIZE,
+        (position.z - 0.5) * BLOCK_SIZE,
+    )
+}
+
+pub fn render_to_world(position: Point3<f32>) -> Point3<f32> {
+    Point3::new(
+        position.x / BLOCK_SIZE + 0.5,
+        -position.y / BLOCK_SIZE + 0.5,
+        position.z / BLOCK_SIZE + 0.5,
+    )
+}
+
+pub fn render_direction_to_world(direction: Vector3<f32>) -> Vector3<f32> {
+    Vector3::new(direction.x, -direction.y, direction.z)
+}
+
+/// The center of the block in render coordinates, this is what the instance data is offset by.
+pub fn block_render_position(position: Point3<i32>) -> [f32; 3] {
+    [
+        position.x as f32 * BLOCK_SIZE,
+        -position.y as f32 * BLOCK_SIZE,
+        position.z as f32 * BLOCK_SIZE,
+    ]
+}
+
+/// Which pass a block is drawn in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RenderLayer {
+    Opaque,
+    /// pixels are either fully visible or cut out by an alpha test, drawn with the opaque blocks
+    Cutout,
+    /// blended over everything behind it, drawn after the opaque blocks from back to front
+    Translucent,
+}
+
+/// The block textures in the order they are in the texture array, block models refer to them by
+/// these names.
+pub const BLOCK_TEXTURES: [&str; 12] = [
+    "block/dirt",
+    "block/stone",
+    "block/glowstone",
+    "block/oak_leaves",
+    "block/glass",
+    "block/light_blue_stained_glass",
+    "block/water_still",
+    "block/lava_still",
+    "block/grass_block_top",
+    "block/grass_block_side",
+    "block/poppy",
+    "block/grass",
+];
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Block {
+    Air,
+    Dirt,
+    Stone,
+    Glowstone,
+    Leaves,
+    Glass,
+    StainedGlass,
+    Water,
+    Lava,
+    Grass,
+    Poppy,
+    TallGrass,
+    StoneSlab,
+    StoneStairs,
+}
+
+impl Block {
+    pub const ALL: [Block; 14] = [
+        Block::Air,
+        Block::Dirt,
+        Block::Stone,
+        Block::Glowstone,
+        Block::Leaves,
+        Block::Glass,
+        Block::StainedGlass,
+        Block::Water,
+        Block::Lava,
+        Block::Grass,
+        Block::Poppy,
+        Block::TallGrass,
+        Block::StoneSlab,
+        Block::StoneStairs,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Block::Air => "air",
+            Block::Dirt => "dirt",
+            Block::Stone => "stone",
+            Block::Glowstone => "glowstone",
+            Block::Leaves => "leaves",
+            Block::Glass => "glass",
+            Block::StainedGlass => "stained_glass",
+            Block::Water => "water",
+            Block::Lava => "lava",
+            Block::Grass => "grass",
+            Block::Poppy => "poppy",
+            Block::TallGrass => "tall_grass",
+            Block::StoneSlab => "stone_slab",
+            Block::StoneStairs => "stone_stairs",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|block| block.name() == name)
+    }
+
+    pub fn is_solid(self) -> bool {
+        !matches!(
+            self,
+            Block::Air | Block::Water | Block::Lava | Block::Poppy | Block::TallGrass
+        )
+    }
+
+    /// Whether the crosshair can point at the block to break it or to place blocks against it.
+    pub fn is_targetable(self) -> bool {
+        self != Block::Air && self.fluid().is_none()
+    }
+
+    pub fn fluid(self) -> Option<Fluid> {
+        match self {
+            Block::Water => Some(Fluid::Water),
+            Block::Lava => Some(Fluid::Lava),
+            _ => None,
+        }
+    }
+
+    pub fn render_layer(self) -> RenderLayer {
+        match self {
+            Block::Leaves | Block::Glass | Block::Poppy | Block::TallGrass => RenderLayer::Cutout,
+            Block::StainedGlass | Block::Water => RenderLayer::Translucent,
+            _ => RenderLayer::Opaque,
+        }
+    }
+
+    /// Whether the block stops light and hides the faces of its neighbors.
+    pub fn is_opaque(self) -> bool {
+        self.is_solid() && self.render_layer() == RenderLayer::Opaque && self.blockstate().is_none()
+    }
+
+    /// The name of the blockstate file that picks the block's model for each of its states, `None`
+    /// for full cubes and fluids.
+    pub fn blockstate(self) -> Option<&'static str> {
+        match self {
+            Block::Poppy => Some("poppy"),
+            Block::TallGrass => Some("grass"),
+            Block::StoneSlab => Some("stone_slab"),
+            Block::StoneStairs => Some("stone_stairs"),
+            _ => None,
+        }
+    }
+
+    /// Whether the block turns to face the direction it was placed in.
+    pub fn has_facing(self) -> bool {
+        self == Block::StoneStairs
+    }
+
+    /// Whether the block can be placed in the top or the bottom half of the block space.
+    pub fn has_half(self) -> bool {
+        matches!(self, Block::StoneSlab | Block::StoneStairs)
+    }
+
+    /// The state of the block with the parts of `facing` and `half` it doesn't have left at their
+    /// defaults, so every look of the block has only one state.
+    pub fn state(self, facing: Face, half: Half) -> BlockState {
+        let default = BlockState::default();
+        BlockState {
+            facing: if self.has_facing() {
+                facing
+            } else {
+                default.facing
+            },
+            half: if self.has_half() { half } else { default.half },
+        }
+    }
+
+    /// Every state the block can be in.
+    pub fn states(self) -> Vec<BlockState> {
+        let facings: &[Face] = if self.has_facing() {
+            &HORIZONTAL_FACES
+        } else {
+            &[Face::NegZ]
+        };
+        let halves: &[Half] = if self.has_half() {
+            &[Half::Bottom, Half::Top]
+        } else {
+            &[Half::Bottom]
+        };
+        facings
+            .iter()
+            .flat_map(|&facing| halves.iter().map(move |&half| self.state(facing, half)))
+            .collect()
+    }
+
+    /// The level of block light the block gives off.
+    pub fn light_emission(self) -> u8 {
+        match self {
+            Block::Glowstone | Block::Lava => MAX_LIGHT,
+            _ => 0,
+        }
+    }
+
+    /// Index into the texture array, `None` for blocks that aren't drawn.
+    ///
+    /// This is what the block looks like in the hotbar, see [`Block::face_texture`] for the
+    /// texture of each side.
+    pub fn texture(self) -> Option<u32> {
+        match self {
+            Block::Air => None,
+            Block::Dirt => Some(0),
+            Block::Stone => Some(1),
+            Block::Glowstone => Some(2),
+            Block::Leaves => Some(3),
+            Block::Glass => Some(4),
+            Block::StainedGlass => Some(5),
+            Block::Water => Some(6),
+            Block::Lava => Some(7),
+            Block::Grass => Some(9),
+            Block::Poppy => Some(10),
+            Block::TallGrass => Some(11),
+            Block::StoneSlab | Block::StoneStairs => Block::Stone.texture(),
+        }
+    }
+
+    pub fn face_texture(self, face: Face) -> Option<u32> {
+        match (self, face) {
+            (Block::Grass, Face::PosY) => Some(8),
+            (Block::Grass, Face::NegY) => Block::Dirt.texture(),
+            _ => self.texture(),
+        }
+    }
+
+    /// The biome color the texture of `face` is multiplied with, `None` keeps it as it is.
+    pub fn tint(self, face: Face) -> Option<Tint> {
+        match (self, face) {
+            (Block::Grass, Face::PosY) | (Block::TallGrass, _) => Some(Tint::Grass),
+            (Block::Leaves, _) => Some(Tint::Foliage),
+            (Block::Water, _) => Some(Tint::Water),
+            _ => None,
+        }
+    }
+}
+
+/// The faces a block can be turned towards, in the order of the compass.
+pub const HORIZONTAL_FACES: [Face; 4] = [Face::NegZ, Face::PosX, Face::PosZ, Face::NegX];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Half {
+    Bottom,
+    Top,
+}
+
+/// How a block that can be placed in different ways was placed, see [`Block::state`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BlockState {
+    /// one of the [`HORIZONTAL_FACES`], the direction the player looked in while placing the block
+    pub facing: Face,
+    pub half: Half,
+}
+
+impl Default for BlockState {
+    fn default() -> Self {
+        Self {
+            facing: Face::NegZ,
+            half: Half::Bottom,
+        }
+    }
+}
+
+/// The six sides of a block, named after the direction their normal points to in world space.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Face {
+    NegX,
+    PosX,
+    /// the bottom
+    NegY,
+    /// the top
+    PosY,
+    NegZ,
+    PosZ,
+}
+
+impl Face {
+    pub const ALL: [Face; 6] = [
+        Face::NegX,
+        Face::PosX,
+        Face::NegY,
+        Face::PosY,
+        Face::NegZ,
+        Face::PosZ,
+    ];
+
+    /// The face on `axis` (0 = x, 1 = y, 2 = z) whose normal points to the `positive` side.
+    pub fn from_axis(axis: usize, positive: bool) -> Self {
+        match (axis, positive) {
+            (0, false) => Face::NegX,
+            (0, true) => Face::PosX,
+            (1, false) => Face::NegY,
+            (1, true) => Face::PosY,
+            (2, false) => Face::NegZ,
+            (2, true) => Face::PosZ,
+            _ => panic!("invalid axis {}", axis),
+        }
+    }
+
+    pub fn normal(self) -> Vector3<i32> {
+        match self {
+            Face::NegX => Vector3::new(-1, 0, 0),
+            Face::PosX => Vector3::new(1, 0, 0),
+            Face::NegY => Vector3::new(0, -1, 0),
+            Face::PosY => Vector3::new(0, 1, 0),
+            Face::NegZ => Vector3::new(0, 0, -1),
+            Face::PosZ => Vector3::new(0, 0, 1),
+        }
+    }
+}
+
+pub struct Chunk {
+    blocks: Box<[Block; CHUNK_VOLUME]>,
+    /// sky light in the upper and block light in the lower four bits
+    light: Box<[u8; CHUNK_VOLUME]>,
+    /// the level of fluid blocks, 0 for everything else
+    fluid_levels: Box<[u8; CHUNK_VOLUME]>,
+    states: Box<[BlockState; CHUNK_VOLUME]>,
+}
+
+impl Chunk {
+    /// An empty chunk is lit like the open sky it replaces.
+    fn new() -> Self {
+        Self {
+            blocks: Box::new([Block::Air; CHUNK_VOLUME]),
+            light: Box::new([MAX_LIGHT << 4; CHUNK_VOLUME]),
+            fluid_levels: Box::new([0; CHUNK_VOLUME]),
+            states: Box::new([BlockState::default(); CHUNK_VOLUME]),
+        }
+    }
+
+    fn index(local: Vector3<i32>) -> usize {
+        (local.x + local.y * CHUNK_SIZE + local.z * CHUNK_SIZE * CHUNK_SIZE) as usize
+    }
+}
+
+pub fn chunk_position(position: Point3<i32>) -> Point3<i32> {
+    position.map(|c| c.div_euclid(CHUNK_SIZE))
+}
+
+fn local_position(position: Point3<i32>) -> Vector3<i32> {
+    position.coords.map(|c| c.rem_euclid(CHUNK_SIZE))
+}
+
+#[derive(Default)]
+pub struct World {
+    chunks: HashMap<Point3<i32>, Chunk>,
+    /// what the world was generated from
+    pub seed: u64,
+    /// how blocks that aren't full cubes are drawn, set with [`World::set_models`]
+    pub models: BlockModels,
+    /// the boxes blocks with a model fill, the others are full cubes
+    pub shapes: BlockShapes,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draws blocks with `models` and collides with their shapes.
+    pub fn set_models(&mut self, models: BlockModels) {
+        self.shapes = models.shapes();
+        self.models = models;
+    }
+
+    /// The single layer of random dirt and stone the game started out with.
+    pub fn generate_flat(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut world = Self {
+            seed,
+            ..Self::new()
+        };
+        for x in 0..100 {
+            for z in 0..100 {
+                let block = if rng.gen() { Block::Dirt } else { Block::Stone };
+                world.set_block(Point3::new(x, 0, z), block);
+            }
+        }
+        light::relight(&mut world);
+        world
+    }
+
+    /// The climate of the column at `x`, `z`.
+    pub fn climate(&self, x: i32, z: i32) -> Climate {
+        Climate::at(self.seed, x, z)
+    }
+
+    /// Where a player spawns, in world coordinates.
+    pub fn spawn_point(&self) -> Point3<f32> {
+        Point3::new(50.5, 2.0, 50.5)
+    }
+
+    pub fn block(&self, position: Point3<i32>) -> Block {
+        match self.chunks.get(&chunk_position(position)) {
+            Some(chunk) => chunk.blocks[Chunk::index(local_position(position))],
+            None => Block::Air,
+        }
+    }
+
+    /// Doesn't update the light, call [`light::update`] afterwards.
+    ///
+    /// Placed fluids are sources.
+    pub fn set_block(&mut self, position: Point3<i32>, block: Block) {
+        let level = if block.fluid().is_some() {
+            SOURCE_LEVEL
+        } else {
+            0
+        };
+        self.set_fluid(position, block, level);
+    }
+
+    /// Like [`World::set_block`], but placed in `state`.
+    pub fn set_block_with_state(&mut self, position: Point3<i32>, block: Block, state: BlockState) {
+        self.set_block(position, block);
+        if let Some(chunk) = self.chunks.get_mut(&chunk_position(position)) {
+            chunk.states[Chunk::index(local_position(position))] = state;
+        }
+    }
+
+    /// Like [`World::set_block`], but with the fluid level of the block.
+    pub fn set_fluid(&mut self, position: Point3<i32>, block: Block, level: u8) {
+        let chunk = match self.chunks.get_mut(&chunk_position(position)) {
+            Some(chunk) => chunk,
+            None if block == Block::Air => return,
+            None => self
+                .chunks
+                .entry(chunk_position(position))
+                .or_insert_with(Chunk::new),
+        };
+        let index = Chunk::index(local_position(position));
+        chunk.blocks[index] = block;
+        chunk.fluid_levels[index] = level;
+        chunk.states[index] = BlockState::default();
+    }
+
+    pub fn block_state(&self, position: Point3<i32>) -> BlockState {
+        match self.chunks.get(&chunk_position(position)) {
+            Some(chunk) => chunk.states[Chunk::index(local_position(position))],
+            None => BlockState::default(),
+        }
+    }
+
+    /// The boxes the block at `position` fills in world coordinates, what the player collides
+    /// with and what the crosshair can point at.
+    pub fn block_boxes(&self, position: Point3<i32>) -> Vec<Aabb> {
+        let offset = position.coords.cast();
+        match self
+            .shapes
+            .get(self.block(position), self.block_state(position))
+        {
+            Some(boxes) => boxes.iter().map(|aabb| aabb.translated(offset)).collect(),
+            None => vec![Aabb::block(position)],
+        }
+    }
+
+    /// From 1 to [`SOURCE_LEVEL`] for fluids, 0 for everything else.
+    pub fn fluid_level(&self, position: Point3<i32>) -> u8 {
+        match self.chunks.get(&chunk_position(position)) {
+            Some(chunk) => chunk.fluid_levels[Chunk::index(local_position(position))],
+            None => 0,
+        }
+    }
+
+    /// Whether the chunk containing `position` exists.
+    pub fn is_loaded(&self, position: Point3<i32>) -> bool {
+        self.chunks.contains_key(&chunk_position(position))
+    }
+
+    /// Outside of the loaded chunks there is full sky light and no block light.
+    pub fn light(&self, position: Point3<i32>, kind: LightKind) -> u8 {
+        let packed = match self.chunks.get(&chunk_position(position)) {
+            Some(chunk) => chunk.light[Chunk::index(local_position(position))],
+            None => MAX_LIGHT << 4,
+        };
+        match kind {
+            LightKind::Sky => packed >> 4,
+            LightKind::Block => packed & 0xf,
+        }
+    }
+
+    /// Does nothing outside of the loaded chunks.
+    pub fn set_light(&mut self, position: Point3<i32>, kind: LightKind, level: u8) {
+        if let Some(chunk) = self.chunks.get_mut(&chunk_position(position)) {
+            let packed = &mut chunk.light[Chunk::index(local_position(position))];
+            *packed = match kind {
+                LightKind::Sky => (*packed & 0xf) | (level << 4),
+                LightKind::Block => (*packed & 0xf0) | level,
+            };
+        }
+    }
+
+    pub fn is_solid(&self, position: Point3<i32>) -> bool {
+        self.block(position).is_solid()
+    }
+
+    pub fn chunk_positions(&self) -> impl Iterator<Item = Point3<i32>> + '_ {
+        self.chunks.keys().copied()
+    }
+
+    /// All blocks that aren't air together with their world position.
+    pub fn blocks(&self) -> impl Iterator<Item = (Point3<i32>, Block)> + '_ {
+        self.chunks.iter().flat_map(|(chunk_position, chunk)| {
+            let origin = chunk_position * CHUNK_SIZE;
+            chunk
+                .blocks
+                .iter()
+                .enumerate()
+                .filter(|(_, block)| **block != Block::Air)
+                .map(move |(index, block)| {
+                    let index = index as i32;
+                    let local = Vector3::new(
+                        index % CHUNK_SIZE,
+                        index / CHUNK_SIZE % CHUNK_SIZE,
+                        index / (CHUNK_SIZE * CHUNK_SIZE),
+                    );
+                    (origin + local, *block)
+                })
+        })
+    }
+}